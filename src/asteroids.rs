@@ -0,0 +1,96 @@
+use std::f32::consts::PI;
+use rand::Rng;
+use nalgebra_glm::{Vec3, Mat4};
+use crate::mesh::{self, IndexedMesh};
+use crate::renderer::create_model_matrix;
+use crate::scene::AsteroidBeltConfig;
+use crate::create_rng;
+
+const RNG_SALT: u64 = 3;
+
+struct Asteroid {
+    orbit_radius: f32,
+    orbital_speed: f32,
+    phase: f32,
+    height: f32,
+    scale: f32,
+    rotation: Vec3,
+    spin: Vec3,
+}
+
+// Every rock shares `mesh`; only the per-instance model matrix changes
+pub struct AsteroidBelt {
+    pub shader: String,
+    pub mesh: IndexedMesh,
+    // Furthest vertex of `mesh` from its origin, before instance scaling
+    pub mesh_radius: f32,
+    // From mesh::next_generation() when `mesh` was built
+    pub mesh_generation: u64,
+    asteroids: Vec<Asteroid>,
+}
+
+impl AsteroidBelt {
+    pub fn new(config: &AsteroidBeltConfig, seed: Option<u64>) -> Self {
+        let mut rng = create_rng(seed, RNG_SALT);
+
+        let asteroids = (0..config.count).map(|_| {
+            let orbit_radius = rng.gen_range(config.inner_radius..=config.outer_radius);
+            Asteroid {
+                orbit_radius,
+                // Outer rocks trail behind inner ones, as they would under gravity
+                orbital_speed: config.orbital_speed * (config.inner_radius / orbit_radius).powf(1.5),
+                phase: rng.gen_range(0.0..2.0 * PI),
+                height: rng.gen_range(-0.5..=0.5) * config.thickness,
+                scale: rng.gen_range(config.min_scale..=config.max_scale),
+                rotation: Vec3::new(rng.gen_range(0.0..2.0 * PI), rng.gen_range(0.0..2.0 * PI), rng.gen_range(0.0..2.0 * PI)),
+                spin: Vec3::new(rng.gen_range(-1.0..1.0), rng.gen_range(-1.0..1.0), rng.gen_range(-1.0..1.0)),
+            }
+        }).collect();
+
+        let mesh = rock_mesh(&mut rng);
+        let mesh_radius = mesh.vertices.iter()
+            .map(|vertex| vertex.position.magnitude())
+            .fold(0.0, f32::max);
+
+        AsteroidBelt {
+            shader: config.shader.clone(),
+            mesh,
+            mesh_radius,
+            mesh_generation: mesh::next_generation(),
+            asteroids,
+        }
+    }
+
+    pub fn model_matrices(&self, time: f32, matrices: &mut Vec<Mat4>) {
+        matrices.clear();
+        for asteroid in &self.asteroids {
+            let angle = asteroid.phase + time * asteroid.orbital_speed;
+            let translation = Vec3::new(
+                asteroid.orbit_radius * angle.cos(),
+                asteroid.orbit_radius * angle.sin(),
+                asteroid.height,
+            );
+            let rotation = asteroid.rotation + asteroid.spin * time;
+            matrices.push(create_model_matrix(translation, asteroid.scale, rotation));
+        }
+    }
+}
+
+// A low-poly icosphere with jittered vertices so the rocks look irregular
+fn rock_mesh(rng: &mut impl Rng) -> IndexedMesh {
+    let mut rock = mesh::icosphere(0.5, 1);
+    for vertex in &mut rock.vertices {
+        let jitter = rng.gen_range(0.75..1.15);
+        vertex.position *= jitter;
+        vertex.transformed_position = vertex.position;
+    }
+
+    let positions: Vec<Vec3> = rock.vertices.iter().map(|vertex| vertex.position).collect();
+    let normals = mesh::smooth_normals(&positions, &rock.indices);
+    for (vertex, normal) in rock.vertices.iter_mut().zip(normals) {
+        vertex.normal = normal;
+        vertex.transformed_normal = normal;
+    }
+
+    rock
+}
@@ -0,0 +1,84 @@
+use crate::camera::Camera;
+use crate::color::Color;
+use crate::renderer::Renderer;
+use crate::scene::Scene;
+use crate::shaders::ShaderRegistry;
+
+// Draws the scene pass (bodies, shells, the belt and the sky) into the renderer's framebuffer.
+// The ship, effects, overlays and presentation stay on the CPU whichever backend drew it.
+pub trait RenderBackend {
+    fn name(&self) -> &'static str;
+
+    fn render_frame(&mut self, renderer: &mut Renderer, scene: &Scene, shaders: &ShaderRegistry, camera: &Camera, time: f32);
+}
+
+// The rasterizer in renderer.rs, running the Rust shaders as they are
+pub struct SoftwareBackend;
+
+impl RenderBackend for SoftwareBackend {
+    fn name(&self) -> &'static str {
+        "software"
+    }
+
+    fn render_frame(&mut self, renderer: &mut Renderer, scene: &Scene, shaders: &ShaderRegistry, camera: &Camera, time: f32) {
+        renderer.render_frame(scene, shaders, camera, time);
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BackendKind {
+    Software,
+    Gpu,
+}
+
+pub fn create_backend(kind: BackendKind) -> Result<Box<dyn RenderBackend>, String> {
+    match kind {
+        BackendKind::Software => Ok(Box::new(SoftwareBackend)),
+        #[cfg(feature = "gpu")]
+        BackendKind::Gpu => Ok(Box::new(crate::gpu::GpuBackend::new()?)),
+        #[cfg(not(feature = "gpu"))]
+        BackendKind::Gpu => Err("The GPU backend needs a build with `--features gpu`".to_string()),
+    }
+}
+
+// Families of procedural surface the GPU backend has a WGSL version of; each shader picks
+// the nearest one and fills in its colors
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SurfaceStyle {
+    // Four colors from low to high ground
+    Rocky,
+    // Alternating latitude bands, then a storm color
+    Bands,
+    // Emissive granulation between a dark and a bright color
+    Star,
+    // Deep and shallow water, then the highlight color
+    Ocean,
+    // Crust, ash, then glowing cracks from their edge to their core
+    Lava,
+    // Hull plating, panel lines and a dish
+    Metal,
+    // Transparent cover in the first color
+    Clouds,
+    // Additive glow at the poles, from the low to the high color
+    Aurora,
+}
+
+// What a shader looks like on the GPU backend: the closest WGSL style with the shader's colors
+#[derive(Debug, Clone, Copy)]
+pub struct GpuSurface {
+    pub style: SurfaceStyle,
+    pub colors: [Color; 4],
+}
+
+impl GpuSurface {
+    pub fn new(style: SurfaceStyle, colors: [Color; 4]) -> Self {
+        GpuSurface { style, colors }
+    }
+}
+
+impl Default for GpuSurface {
+    fn default() -> Self {
+        let gray = Color::new(150, 150, 150);
+        GpuSurface::new(SurfaceStyle::Rocky, [gray * 0.6, gray * 0.8, gray, gray * 1.2])
+    }
+}
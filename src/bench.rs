@@ -0,0 +1,87 @@
+use nalgebra_glm::Vec3;
+use std::f32::consts::PI;
+use std::fs;
+use std::time::Duration;
+use crate::camera::Camera;
+use crate::profiler;
+use crate::renderer::RenderStats;
+
+// Simulation time advanced per benchmark frame, independent of how long the frame took
+pub const BENCH_TIMESTEP: f32 = 1.0 / 60.0;
+// Used unless --seed picks another, so every run draws the same asteroids and particles
+pub const BENCH_SEED: u64 = 1;
+
+// The renderer's stages, then presenting the frame and the whole frame
+const COLUMNS: usize = profiler::STAGES.len() + 2;
+
+// One lap around the system over the whole run, swinging in close to the inner planets
+// and out past the belt while rising above and dipping below the ecliptic
+pub fn path_camera(progress: f32) -> Camera {
+    let angle = progress * 2.0 * PI;
+    let distance = 10.0 - 4.0 * (angle * 2.0).cos();
+    let height = 3.0 * (angle * 3.0).sin();
+    let eye = Vec3::new(distance * angle.sin(), height, distance * angle.cos());
+    Camera::new(eye, Vec3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0))
+}
+
+// Per-frame timings in milliseconds, in the order of stage_names()
+pub struct BenchReport {
+    frames: Vec<[f32; COLUMNS]>,
+}
+
+fn stage_names() -> impl Iterator<Item = &'static str> {
+    profiler::STAGES.into_iter().chain(["blit", "frame"])
+}
+
+impl BenchReport {
+    pub fn new() -> Self {
+        BenchReport { frames: Vec::new() }
+    }
+
+    pub fn record(&mut self, stats: &RenderStats, blit: Duration, frame: Duration) {
+        let mut times = [0.0; COLUMNS];
+        let stage_times = stats.stage_times().into_iter().chain([blit, frame]);
+        for (time, stage_time) in times.iter_mut().zip(stage_times) {
+            *time = stage_time.as_secs_f32() * 1000.0;
+        }
+        self.frames.push(times);
+    }
+
+    // Mean, min, median, 95th percentile and max of each stage
+    fn summary(&self) -> Vec<(&'static str, [f32; 5])> {
+        stage_names().enumerate().map(|(stage, name)| {
+            let mut times: Vec<f32> = self.frames.iter().map(|frame| frame[stage]).collect();
+            times.sort_by(f32::total_cmp);
+            if times.is_empty() {
+                return (name, [0.0; 5]);
+            }
+            let percentile = |p: f32| times[((times.len() - 1) as f32 * p).round() as usize];
+            let mean = times.iter().sum::<f32>() / times.len() as f32;
+            (name, [mean, times[0], percentile(0.5), percentile(0.95), times[times.len() - 1]])
+        }).collect()
+    }
+
+    pub fn write_csv(&self, path: &str) -> Result<(), String> {
+        let mut csv = String::from("stage,mean_ms,min_ms,p50_ms,p95_ms,max_ms\n");
+        for (name, values) in self.summary() {
+            let values: Vec<String> = values.iter().map(|value| format!("{:.3}", value)).collect();
+            csv.push_str(&format!("{},{}\n", name, values.join(",")));
+        }
+        fs::write(path, csv).map_err(|e| format!("Failed to write {}: {}", path, e))
+    }
+
+    pub fn print(&self) {
+        println!("{} frames", self.frames.len());
+        println!("{:<10}{:>10}{:>10}{:>10}{:>10}{:>10}", "stage", "mean", "min", "p50", "p95", "max");
+        for (name, values) in self.summary() {
+            let [mean, min, p50, p95, max] = values;
+            println!("{:<10}{:>10.3}{:>10.3}{:>10.3}{:>10.3}{:>10.3}", name, mean, min, p50, p95, max);
+        }
+    }
+}
+
+impl Default for BenchReport {
+    fn default() -> Self {
+        Self::new()
+    }
+}
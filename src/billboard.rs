@@ -0,0 +1,65 @@
+use nalgebra_glm::{Vec2, Vec3};
+use crate::color::Color;
+use crate::framebuffer::Framebuffer;
+use crate::texture::Texture;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BlendMode {
+    Alpha,
+    Additive,
+}
+
+pub struct Sprite<'a> {
+    pub texture: Option<&'a Texture>,
+    pub color: Color,
+    pub alpha: f32,
+    pub blend: BlendMode,
+}
+
+// A sprite anchored at a world position that always faces the camera
+pub struct Billboard<'a> {
+    pub position: Vec3,
+    pub size: f32,
+    pub sprite: Sprite<'a>,
+}
+
+// Draws a square sprite centered on a screen position. Sprites are depth tested
+// but never write depth, so overlapping translucent sprites all stay visible.
+pub fn draw_sprite(framebuffer: &mut Framebuffer, sprite: &Sprite, center: Vec3, half_size: f32) {
+    if half_size <= 0.0 || sprite.alpha <= 0.0 {
+        return;
+    }
+
+    let min_x = (center.x - half_size).floor().max(0.0);
+    let min_y = (center.y - half_size).floor().max(0.0);
+    let max_x = (center.x + half_size).ceil().min(framebuffer.width as f32 - 1.0);
+    let max_y = (center.y + half_size).ceil().min(framebuffer.height as f32 - 1.0);
+    if min_x > max_x || min_y > max_y {
+        return;
+    }
+
+    for y in min_y as usize..=max_y as usize {
+        for x in min_x as usize..=max_x as usize {
+            let uv = Vec2::new(
+                (x as f32 + 0.5 - (center.x - half_size)) / (2.0 * half_size),
+                (y as f32 + 0.5 - (center.y - half_size)) / (2.0 * half_size),
+            );
+            if uv.x < 0.0 || uv.x >= 1.0 || uv.y < 0.0 || uv.y >= 1.0 {
+                continue;
+            }
+
+            let (color, alpha) = match sprite.texture {
+                Some(texture) => (texture.sample(uv).blend_multiply(&sprite.color), texture.sample_alpha(uv) * sprite.alpha),
+                None => (sprite.color, sprite.alpha),
+            };
+            if alpha <= 0.0 {
+                continue;
+            }
+
+            match sprite.blend {
+                BlendMode::Alpha => framebuffer.blend_color(x, y, center.z, color, alpha),
+                BlendMode::Additive => framebuffer.add_color(x, y, center.z, color * alpha),
+            }
+        }
+    }
+}
@@ -1,9 +1,84 @@
 
-use nalgebra_glm::{Vec3, rotate_vec3};
+use nalgebra_glm::{Vec3, rotate_vec3, slerp};
 use std::f32::consts::PI;
-use crate::Fragment;
-use crate::Uniforms;
-use crate::Color;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CameraMode {
+  Orbit,
+  Fly,
+}
+
+pub struct CameraTransition {
+  start_eye: Vec3,
+  start_center: Vec3,
+  target_eye: Vec3,
+  target_center: Vec3,
+  elapsed: f32,
+  duration: f32,
+}
+
+impl CameraTransition {
+  pub fn is_finished(&self) -> bool {
+    self.elapsed >= self.duration
+  }
+}
+
+fn ease_in_out(t: f32) -> f32 {
+  let t = t.clamp(0.0, 1.0);
+  t * t * (3.0 - 2.0 * t)
+}
+
+const CHASE_DISTANCE: f32 = 0.6;
+const CHASE_HEIGHT: f32 = 0.15;
+const CHASE_LOOK_AHEAD: f32 = 1.5;
+
+// Vertical field of view in degrees
+pub const MIN_FOV: f32 = 20.0;
+pub const MAX_FOV: f32 = 120.0;
+// Depth precision is spread between the clip planes, but the near one can't reach the eye
+pub const MIN_NEAR: f32 = 0.001;
+
+// Warps covering more than this distance go through hyperspace
+const HYPERSPACE_DISTANCE: f32 = 6.0;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WarpPhase {
+  Accelerating,
+  Decelerating,
+}
+
+pub struct Warp {
+  pub phase: WarpPhase,
+  pub hyperspace: bool,
+  elapsed: f32,
+  phase_duration: f32,
+  start_eye: Vec3,
+  start_center: Vec3,
+  target_eye: Vec3,
+  target_center: Vec3,
+}
+
+impl Warp {
+  fn phase_progress(&self) -> f32 {
+    (self.elapsed / self.phase_duration).clamp(0.0, 1.0)
+  }
+
+  fn path_progress(&self) -> f32 {
+    let u = self.phase_progress();
+    match self.phase {
+      WarpPhase::Accelerating => 0.5 * u * u,
+      WarpPhase::Decelerating => 0.5 + 0.5 * (1.0 - (1.0 - u) * (1.0 - u)),
+    }
+  }
+
+  pub fn intensity(&self) -> f32 {
+    let u = self.phase_progress();
+    match self.phase {
+      WarpPhase::Accelerating => u,
+      WarpPhase::Decelerating => 1.0 - u,
+    }
+  }
+}
 
 pub struct Camera {
   pub eye: Vec3,
@@ -11,6 +86,24 @@ pub struct Camera {
   pub up: Vec3,
   pub has_changed: bool,
   pub planet_index: usize, 
+  pub mouse_sensitivity: f32,
+  pub scroll_sensitivity: f32,
+  pub pan_sensitivity: f32,
+  pub last_mouse_pos: Option<(f32, f32)>,
+  pub mode: CameraMode,
+  pub fly_speed: f32,
+  pub transition: Option<CameraTransition>,
+  pub transition_duration: f32,
+  pub follow_target: Option<usize>,
+  pub follow_distance: f32,
+  pub map_view: bool,
+  saved_view: Option<(Vec3, Vec3, Vec3)>,
+  pub fov: f32,
+  pub base_fov: f32,
+  // Clip planes of the perspective projection
+  pub near: f32,
+  pub far: f32,
+  pub warp: Option<Warp>,
 }
 
 impl Camera {
@@ -21,6 +114,23 @@ impl Camera {
           up,
           has_changed: true,
           planet_index: 0, 
+          mouse_sensitivity: 0.005,
+          scroll_sensitivity: 0.1,
+          pan_sensitivity: 0.01,
+          last_mouse_pos: None,
+          mode: CameraMode::Orbit,
+          fly_speed: 0.1,
+          transition: None,
+          transition_duration: 1.0,
+          follow_target: None,
+          follow_distance: 5.0,
+          map_view: false,
+          saved_view: None,
+          fov: 45.0,
+          base_fov: 45.0,
+          near: 0.1,
+          far: 1000.0,
+          warp: None,
       }
   }
 
@@ -60,6 +170,12 @@ impl Camera {
   }
 
   pub fn zoom(&mut self, delta: f32) {
+    if self.follow_target.is_some() {
+      self.follow_distance = (self.follow_distance - delta).max(0.5);
+      self.has_changed = true;
+      return;
+    }
+
     let direction = (self.center - self.eye).normalize();
     self.eye += direction * delta;
     self.has_changed = true;
@@ -81,6 +197,221 @@ impl Camera {
     self.has_changed = true;
   }
 
+  pub fn pan(&mut self, delta_x: f32, delta_y: f32) {
+    let forward = (self.center - self.eye).normalize();
+    let right = forward.cross(&self.up).normalize();
+    let up = right.cross(&forward).normalize();
+
+    let offset = right * delta_x + up * delta_y;
+    self.eye += offset;
+    self.center += offset;
+    self.has_changed = true;
+  }
+
+  pub fn toggle_mode(&mut self) {
+    self.mode = match self.mode {
+      CameraMode::Orbit => CameraMode::Fly,
+      CameraMode::Fly => CameraMode::Orbit,
+    };
+    self.has_changed = true;
+  }
+
+  pub fn fly(&mut self, forward_amount: f32, right_amount: f32, up_amount: f32) {
+    let forward = (self.center - self.eye).normalize();
+    let right = forward.cross(&self.up).normalize();
+
+    let offset = forward * forward_amount + right * right_amount + self.up * up_amount;
+    self.eye += offset * self.fly_speed;
+    self.center += offset * self.fly_speed;
+    self.has_changed = true;
+  }
+
+  pub fn look(&mut self, delta_yaw: f32, delta_pitch: f32) {
+    let look_vector = self.center - self.eye;
+    let distance = look_vector.magnitude();
+
+    let current_yaw = look_vector.z.atan2(look_vector.x);
+    let distance_xz = (look_vector.x * look_vector.x + look_vector.z * look_vector.z).sqrt();
+    let current_pitch = look_vector.y.atan2(distance_xz);
+
+    let new_yaw = (current_yaw + delta_yaw) % (2.0 * PI);
+    let new_pitch = (current_pitch + delta_pitch).clamp(-PI / 2.0 + 0.1, PI / 2.0 - 0.1);
+
+    self.center = self.eye + Vec3::new(
+      distance * new_yaw.cos() * new_pitch.cos(),
+      distance * new_pitch.sin(),
+      distance * new_yaw.sin() * new_pitch.cos()
+    );
+    self.has_changed = true;
+  }
+
+  pub fn start_transition(&mut self, target_eye: Vec3, target_center: Vec3) {
+    self.transition = Some(CameraTransition {
+      start_eye: self.eye,
+      start_center: self.center,
+      target_eye,
+      target_center,
+      elapsed: 0.0,
+      duration: self.transition_duration,
+    });
+  }
+
+  pub fn update_transition(&mut self, delta_time: f32) {
+    let Some(transition) = self.transition.as_mut() else {
+      return;
+    };
+
+    transition.elapsed += delta_time;
+    let t = ease_in_out(transition.elapsed / transition.duration);
+
+    let start_forward = transition.start_center - transition.start_eye;
+    let target_forward = transition.target_center - transition.target_eye;
+    let start_distance = start_forward.magnitude();
+    let target_distance = target_forward.magnitude();
+    let start_direction = start_forward.normalize();
+    let target_direction = target_forward.normalize();
+
+    // slerp is undefined for opposite directions, fall back to a normalized lerp
+    let direction = if start_direction.dot(&target_direction) > -0.999 {
+      slerp(&start_direction, &target_direction, t)
+    } else {
+      (start_direction + (target_direction - start_direction) * t + self.up * 0.001).normalize()
+    };
+
+    self.eye = transition.start_eye + (transition.target_eye - transition.start_eye) * t;
+    self.center = self.eye + direction * (start_distance + (target_distance - start_distance) * t);
+    self.has_changed = true;
+
+    if transition.is_finished() {
+      self.eye = transition.target_eye;
+      self.center = transition.target_center;
+      self.transition = None;
+    }
+  }
+
+  pub fn toggle_follow(&mut self, planet_index: usize) {
+    self.follow_target = match self.follow_target {
+      Some(_) => None,
+      None => {
+        self.follow_distance = (self.eye - self.center).magnitude();
+        Some(planet_index)
+      }
+    };
+    self.has_changed = true;
+  }
+
+  // Sits behind and above a moving object, looking past it along its heading
+  pub fn chase(&mut self, position: Vec3, forward: Vec3, up: Vec3) {
+    self.eye = position - forward * CHASE_DISTANCE + up * CHASE_HEIGHT;
+    self.center = position + forward * CHASE_LOOK_AHEAD;
+    self.up = up;
+    self.has_changed = true;
+  }
+
+  pub fn follow(&mut self, target: Vec3) {
+    let offset = self.eye - self.center;
+    let direction = if offset.magnitude() > 0.0 {
+      offset.normalize()
+    } else {
+      Vec3::new(0.0, 0.0, 1.0)
+    };
+
+    self.center = target;
+    self.eye = target + direction * self.follow_distance;
+    self.has_changed = true;
+  }
+
+  // A warp in progress keeps widening the view from the new base
+  pub fn set_fov(&mut self, fov: f32) {
+    self.base_fov = fov.clamp(MIN_FOV, MAX_FOV);
+    if self.warp.is_none() {
+      self.fov = self.base_fov;
+    }
+    self.has_changed = true;
+  }
+
+  // The far plane stays beyond the near one, or the projection would turn inside out
+  pub fn set_clip_planes(&mut self, near: f32, far: f32) {
+    self.near = near.max(MIN_NEAR);
+    self.far = far.max(self.near * 2.0);
+    self.has_changed = true;
+  }
+
+  pub fn toggle_map_view(&mut self) {
+    // A warp in flight would keep steering the camera away from the map
+    if self.warp.take().is_some() {
+      self.fov = self.base_fov;
+    }
+    if let Some((eye, center, up)) = self.saved_view.take() {
+      self.eye = eye;
+      self.center = center;
+      self.up = up;
+      self.map_view = false;
+    } else {
+      self.saved_view = Some((self.eye, self.center, self.up));
+      self.eye = Vec3::new(0.0, 0.0, 50.0);
+      self.center = Vec3::new(0.0, 0.0, 0.0);
+      self.up = Vec3::new(0.0, 1.0, 0.0);
+      self.map_view = true;
+    }
+    self.has_changed = true;
+  }
+
+  pub fn start_warp(&mut self, target_eye: Vec3, target_center: Vec3) {
+    self.transition = None;
+    self.warp = Some(Warp {
+      phase: WarpPhase::Accelerating,
+      hyperspace: (target_eye - self.eye).magnitude() > HYPERSPACE_DISTANCE,
+      elapsed: 0.0,
+      phase_duration: 0.75,
+      start_eye: self.eye,
+      start_center: self.center,
+      target_eye,
+      target_center,
+    });
+  }
+
+  // Long warps drop into hyperspace for the fastest part of the jump
+  pub fn hyperspace_intensity(&self) -> f32 {
+    match &self.warp {
+      Some(warp) if warp.hyperspace => ((warp.intensity() - 0.5) * 2.0).max(0.0),
+      _ => 0.0,
+    }
+  }
+
+  pub fn in_hyperspace(&self) -> bool {
+    self.hyperspace_intensity() > 0.0
+  }
+
+  pub fn warp_intensity(&self) -> f32 {
+    self.warp.as_ref().map_or(0.0, |warp| warp.intensity())
+  }
+
+  pub fn update_warp(&mut self, delta_time: f32) {
+    let Some(warp) = self.warp.as_mut() else {
+      return;
+    };
+
+    warp.elapsed += delta_time;
+    if warp.phase == WarpPhase::Accelerating && warp.elapsed >= warp.phase_duration {
+      warp.phase = WarpPhase::Decelerating;
+      warp.elapsed -= warp.phase_duration;
+    }
+
+    let s = warp.path_progress();
+    self.eye = warp.start_eye + (warp.target_eye - warp.start_eye) * s;
+    self.center = warp.start_center + (warp.target_center - warp.start_center) * s;
+    self.fov = self.base_fov + 45.0 * warp.intensity();
+    self.has_changed = true;
+
+    if warp.phase == WarpPhase::Decelerating && warp.elapsed >= warp.phase_duration {
+      self.eye = warp.target_eye;
+      self.center = warp.target_center;
+      self.fov = self.base_fov;
+      self.warp = None;
+    }
+  }
+
   pub fn check_if_changed(&mut self) -> bool {
     if self.has_changed {
       self.has_changed = false;
@@ -89,14 +420,7 @@ impl Camera {
       false
     }
   }
-pub fn move_to_next_planet(
-    &mut self,
-    solar_objects: &[(Box<dyn Fn(&Fragment, &Uniforms) -> Color>, Vec3, f32, f32)],
-    current_index: usize,
-) {
-    if let Some((_, position, _, _)) = solar_objects.get(current_index) {
-        self.center = *position;
-        self.eye = position + Vec3::new(0.0, 0.0, 5.0); 
-    }
+pub fn move_to_next_planet(&mut self, position: Vec3) {
+    self.start_transition(position + Vec3::new(0.0, 0.0, 5.0), position);
   }
 }
\ No newline at end of file
@@ -0,0 +1,71 @@
+const MIN_SCALE: f32 = 0.1;
+const MAX_SCALE: f32 = 100.0;
+// Simulated seconds skipped by a single step backward or forward
+pub const STEP: f32 = 10.0;
+
+// Simulation time, decoupled from the wall clock so it can be paused, slowed or sped up.
+// Time is signed and the scale may be negative: every orbit is a pure function of it.
+pub struct SimulationClock {
+    pub time: f32,
+    pub scale: f32,
+    pub paused: bool,
+}
+
+impl SimulationClock {
+    pub fn new() -> Self {
+        SimulationClock {
+            time: 0.0,
+            scale: 1.0,
+            paused: false,
+        }
+    }
+
+    // Advances by a wall-clock step and returns the simulated step
+    pub fn advance(&mut self, delta_time: f32) -> f32 {
+        if self.paused {
+            return 0.0;
+        }
+        let step = delta_time * self.scale;
+        self.time += step;
+        step
+    }
+
+    pub fn toggle_pause(&mut self) {
+        self.paused = !self.paused;
+    }
+
+    // Speed changes keep the direction, only the magnitude is clamped
+    pub fn faster(&mut self) {
+        self.scale = self.scale.signum() * (self.scale.abs() * 2.0).min(MAX_SCALE);
+    }
+
+    pub fn slower(&mut self) {
+        self.scale = self.scale.signum() * (self.scale.abs() / 2.0).max(MIN_SCALE);
+    }
+
+    pub fn reverse(&mut self) {
+        self.scale = -self.scale;
+    }
+
+    pub fn step(&mut self, seconds: f32) {
+        self.time += seconds;
+    }
+
+    pub fn jump_to(&mut self, time: f32) {
+        self.time = time;
+    }
+
+    pub fn describe(&self) -> String {
+        if self.paused {
+            format!("Paused at t={:.1}", self.time)
+        } else {
+            format!("Time x{:.1}", self.scale)
+        }
+    }
+}
+
+impl Default for SimulationClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
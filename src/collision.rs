@@ -0,0 +1,50 @@
+use nalgebra_glm::Vec3;
+use crate::scene::Scene;
+
+// Radius of the shared body mesh before scaling
+pub const BODY_RADIUS: f32 = 0.5;
+
+#[derive(Debug, Clone, Copy)]
+pub struct BoundingSphere {
+    pub center: Vec3,
+    pub radius: f32,
+}
+
+impl BoundingSphere {
+    // Direction from `other` towards this sphere and how deep they overlap, if they do
+    pub fn contact(&self, other: &BoundingSphere) -> Option<(Vec3, f32)> {
+        let offset = self.center - other.center;
+        let depth = self.radius + other.radius - offset.magnitude();
+        if depth <= 0.0 {
+            return None;
+        }
+        Some((offset.try_normalize(f32::EPSILON).unwrap_or(Vec3::y()), depth))
+    }
+}
+
+// World-space bounds of every scene object, indexed like `scene.objects` and refreshed each frame
+#[derive(Default)]
+pub struct Colliders {
+    pub spheres: Vec<BoundingSphere>,
+}
+
+impl Colliders {
+    pub fn new() -> Self {
+        Colliders { spheres: Vec::new() }
+    }
+
+    pub fn update(&mut self, scene: &Scene, time: f32) {
+        self.spheres.clear();
+        self.spheres.extend((0..scene.objects.len()).map(|index| BoundingSphere {
+            center: scene.position(index, time),
+            radius: scene.objects[index].scale * BODY_RADIUS,
+        }));
+    }
+
+    // First object overlapping `sphere`, with the push-out normal and penetration depth
+    pub fn first_hit(&self, sphere: &BoundingSphere) -> Option<(usize, Vec3, f32)> {
+        self.spheres.iter().enumerate().find_map(|(index, body)| {
+            sphere.contact(body).map(|(normal, depth)| (index, normal, depth))
+        })
+    }
+}
@@ -1,43 +1,169 @@
 use std::fmt;
+use nalgebra_glm::Vec3;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
+// Channels are on the 0-255 scale but stored as floats and never clamped, so lighting
+// can go brighter than white; the framebuffer tone maps them when presenting
 #[derive(Debug, Clone, Copy)]
 pub struct Color {
-    r: u8,
-    g: u8,
-    b: u8,
+    r: f32,
+    g: f32,
+    b: f32,
 }
 
 impl Color {
     pub const fn new(r: u8, g: u8, b: u8) -> Self {
-        Color { r, g, b }
+        Color { r: r as f32, g: g as f32, b: b as f32 }
     }
 
     pub const fn from_hex(hex: u32) -> Self {
         let r = ((hex >> 16) & 0xFF) as u8;
         let g = ((hex >> 8) & 0xFF) as u8;
         let b = (hex & 0xFF) as u8;
-        Color { r, g, b }
+        Color::new(r, g, b)
+    }
+
+    // Channels where 1.0 is full brightness; values above it are kept
+    pub const fn from_rgb(r: f32, g: f32, b: f32) -> Self {
+        Color { r: r * 255.0, g: g * 255.0, b: b * 255.0 }
+    }
+
+    // Hue in degrees, saturation and value in 0..1
+    pub fn from_hsv(hue: f32, saturation: f32, value: f32) -> Self {
+        let chroma = value * saturation.clamp(0.0, 1.0);
+        Self::from_hue(hue, chroma, value - chroma)
+    }
+
+    // Hue in degrees, saturation and lightness in 0..1
+    pub fn from_hsl(hue: f32, saturation: f32, lightness: f32) -> Self {
+        let chroma = (1.0 - (2.0 * lightness - 1.0).abs()) * saturation.clamp(0.0, 1.0);
+        Self::from_hue(hue, chroma, lightness - chroma / 2.0)
+    }
+
+    // Shared by the HSV and HSL conversions, which only differ in chroma and the offset added to every channel
+    fn from_hue(hue: f32, chroma: f32, offset: f32) -> Self {
+        let sector = hue.rem_euclid(360.0) / 60.0;
+        let x = chroma * (1.0 - (sector % 2.0 - 1.0).abs());
+        let (r, g, b) = match sector as u32 {
+            0 => (chroma, x, 0.0),
+            1 => (x, chroma, 0.0),
+            2 => (0.0, chroma, x),
+            3 => (0.0, x, chroma),
+            4 => (x, 0.0, chroma),
+            _ => (chroma, 0.0, x),
+        };
+        Self::from_rgb(r + offset, g + offset, b + offset)
+    }
+
+    // Hue in degrees, shared by HSV and HSL
+    fn hue(&self) -> f32 {
+        let rgb = self.to_vec3();
+        let (r, g, b) = (rgb.x, rgb.y, rgb.z);
+        let (max, min) = (r.max(g).max(b), r.min(g).min(b));
+        let chroma = max - min;
+        if chroma <= 0.0 {
+            return 0.0;
+        }
+        let sector = if max == r {
+            ((g - b) / chroma).rem_euclid(6.0)
+        } else if max == g {
+            (b - r) / chroma + 2.0
+        } else {
+            (r - g) / chroma + 4.0
+        };
+        sector * 60.0
+    }
+
+    pub fn to_hsv(&self) -> (f32, f32, f32) {
+        let rgb = self.to_vec3();
+        let (max, min) = (rgb.max(), rgb.min());
+        let saturation = if max > 0.0 { (max - min) / max } else { 0.0 };
+        (self.hue(), saturation, max)
+    }
+
+    pub fn to_hsl(&self) -> (f32, f32, f32) {
+        let rgb = self.to_vec3();
+        let (max, min) = (rgb.max(), rgb.min());
+        let lightness = (max + min) / 2.0;
+        let saturation = if max == min { 0.0 } else { (max - min) / (1.0 - (2.0 * lightness - 1.0).abs()).max(f32::EPSILON) };
+        (self.hue(), saturation.min(1.0), lightness)
+    }
+
+    // Channels treat their value as sRGB; these convert to and from linear light,
+    // where blending and lighting math behave physically
+    pub fn to_linear(&self) -> Self {
+        let channel = |value: f32| {
+            let value = value / 255.0;
+            let linear = if value <= 0.04045 { value / 12.92 } else { ((value + 0.055) / 1.055).powf(2.4) };
+            linear * 255.0
+        };
+        Color { r: channel(self.r), g: channel(self.g), b: channel(self.b) }
+    }
+
+    pub fn to_srgb(&self) -> Self {
+        let channel = |value: f32| {
+            let value = value.max(0.0) / 255.0;
+            let encoded = if value <= 0.0031308 { value * 12.92 } else { 1.055 * value.powf(1.0 / 2.4) - 0.055 };
+            encoded * 255.0
+        };
+        Color { r: channel(self.r), g: channel(self.g), b: channel(self.b) }
+    }
+
+    // Blends in linear light, so halfway between two saturated colors doesn't dip darker
+    pub fn lerp_linear(&self, other: &Color, t: f32) -> Self {
+        self.to_linear().lerp(&other.to_linear(), t).to_srgb()
+    }
+
+    // Rec. 709 relative luminance of the linear color, 1.0 for white
+    pub fn luminance(&self) -> f32 {
+        let linear = self.to_linear().to_vec3();
+        linear.dot(&Vec3::new(0.2126, 0.7152, 0.0722))
+    }
+
+    // 0 turns the color gray, 1 leaves it alone and above 1 makes it more vivid
+    pub fn saturate(&self, amount: f32) -> Self {
+        let gray = Color::from_rgb(1.0, 1.0, 1.0) * self.luminance();
+        let linear = self.to_linear();
+        let mix = |gray: f32, value: f32| (gray + (value - gray) * amount).max(0.0);
+        Color { r: mix(gray.r, linear.r), g: mix(gray.g, linear.g), b: mix(gray.b, linear.b) }.to_srgb()
+    }
+
+    // Moves the color towards white by `amount`, or towards black when it is negative
+    pub fn brighten(&self, amount: f32) -> Self {
+        let target = if amount >= 0.0 { Color::from_rgb(1.0, 1.0, 1.0) } else { Color::black() };
+        self.lerp_linear(&target, amount.abs())
     }
 
     pub const fn black() -> Self {
-        Color { r: 0, g: 0, b: 0 }
+        Color { r: 0.0, g: 0.0, b: 0.0 }
     }
 
+    // Clamps to displayable values
     pub fn to_hex(&self) -> u32 {
-        ((self.r as u32) << 16) | ((self.g as u32) << 8) | (self.b as u32)
+        let channel = |value: f32| value.round().clamp(0.0, 255.0) as u32;
+        (channel(self.r) << 16) | (channel(self.g) << 8) | channel(self.b)
+    }
+
+    // Channels as floats where 1.0 is full brightness
+    pub fn to_vec3(&self) -> Vec3 {
+        Vec3::new(self.r, self.g, self.b) / 255.0
+    }
+
+    pub fn from_vec3(color: Vec3) -> Self {
+        Color { r: color.x.max(0.0) * 255.0, g: color.y.max(0.0) * 255.0, b: color.z.max(0.0) * 255.0 }
     }
 
     pub fn lerp(&self, other: &Color, t: f32) -> Self {
         let t = t.clamp(0.0, 1.0);
         Color {
-            r: (self.r as f32 + (other.r as f32 - self.r as f32) * t).round() as u8,
-            g: (self.g as f32 + (other.g as f32 - self.g as f32) * t).round() as u8,
-            b: (self.b as f32 + (other.b as f32 - self.b as f32) * t).round() as u8,
+            r: self.r + (other.r - self.r) * t,
+            g: self.g + (other.g - self.g) * t,
+            b: self.b + (other.b - self.b) * t,
         }
     }
 
     pub fn is_black(&self) -> bool {
-        self.r == 0 && self.g == 0 && self.b == 0 
+        self.r <= 0.0 && self.g <= 0.0 && self.b <= 0.0
     }
 
     pub fn blend_normal(&self, blend: &Color) -> Color {
@@ -45,27 +171,61 @@ impl Color {
       }
     
     pub fn blend_multiply(&self, blend: &Color) -> Color {
-        Color::new(
-            ((self.r as f32 * blend.r as f32) / 255.0) as u8,
-            ((self.g as f32 * blend.g as f32) / 255.0) as u8,
-            ((self.b as f32 * blend.b as f32) / 255.0) as u8
-        )
+        Color {
+            r: self.r * blend.r / 255.0,
+            g: self.g * blend.g / 255.0,
+            b: self.b * blend.b / 255.0,
+        }
     }
     
     pub fn blend_add(&self, blend: &Color) -> Color {
-        Color::new(
-            (self.r as u16 + blend.r as u16).min(255) as u8,
-            (self.g as u16 + blend.g as u16).min(255) as u8,
-            (self.b as u16 + blend.b as u16).min(255) as u8
-        )
+        *self + *blend
     }
     
     pub fn blend_subtract(&self, blend: &Color) -> Color {
-        let r = (self.r as i16 - blend.r as i16).max(0).min(255) as u8;
-        let g = (self.g as i16 - blend.g as i16).max(0).min(255) as u8;
-        let b = (self.b as i16 - blend.b as i16).max(0).min(255) as u8;
+        Color {
+            r: (self.r - blend.r).max(0.0),
+            g: (self.g - blend.g).max(0.0),
+            b: (self.b - blend.b).max(0.0),
+        }
+    }
+}
 
-        Color::new(r, g, b)
+impl From<u32> for Color {
+    fn from(hex: u32) -> Self {
+        Color::from_hex(hex)
+    }
+}
+
+impl From<Color> for u32 {
+    fn from(color: Color) -> Self {
+        color.to_hex()
+    }
+}
+
+// In config files a color is 0xRRGGBB, or [r, g, b] on the 0-255 scale for channels
+// brighter than white
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum ColorValue {
+    Hex(u32),
+    Channels([f32; 3]),
+}
+
+impl Serialize for Color {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let channels = [self.r, self.g, self.b];
+        let fits_hex = channels.iter().all(|channel| channel.fract() == 0.0 && (0.0..=255.0).contains(channel));
+        if fits_hex { ColorValue::Hex(self.to_hex()) } else { ColorValue::Channels(channels) }.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Color {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Ok(match ColorValue::deserialize(deserializer)? {
+            ColorValue::Hex(hex) => Color::from_hex(hex),
+            ColorValue::Channels([r, g, b]) => Color { r, g, b },
+        })
     }
 }
 
@@ -76,9 +236,9 @@ impl Add for Color {
 
     fn add(self, other: Color) -> Color {
         Color {
-            r: self.r.saturating_add(other.r),
-            g: self.g.saturating_add(other.g),
-            b: self.b.saturating_add(other.b),
+            r: self.r + other.r,
+            g: self.g + other.g,
+            b: self.b + other.b,
         }
     }
 }
@@ -89,16 +249,17 @@ impl Mul<f32> for Color {
     type Output = Color;
 
     fn mul(self, scalar: f32) -> Color {
+        let scalar = scalar.max(0.0);
         Color {
-            r: (self.r as f32 * scalar).clamp(0.0, 255.0) as u8,
-            g: (self.g as f32 * scalar).clamp(0.0, 255.0) as u8,
-            b: (self.b as f32 * scalar).clamp(0.0, 255.0) as u8,
+            r: self.r * scalar,
+            g: self.g * scalar,
+            b: self.b * scalar,
         }
     }
 }
 
 impl fmt::Display for Color {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "Color(r: {}, g: {}, b: {})", self.r, self.g, self.b)
+        write!(f, "Color(r: {:.1}, g: {:.1}, b: {:.1})", self.r, self.g, self.b)
     }
 }
\ No newline at end of file
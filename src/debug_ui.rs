@@ -0,0 +1,141 @@
+use crate::color::Color;
+use crate::framebuffer::{Framebuffer, Rect};
+use crate::text::{draw_text, GLYPH_SIZE};
+
+const PANEL_X: usize = 8;
+// Below the frame stats, which share the left edge
+const PANEL_Y: usize = 96;
+const PANEL_WIDTH: usize = 220;
+const PADDING: usize = 6;
+const HEADING_HEIGHT: usize = 16;
+const SLIDER_HEIGHT: usize = 24;
+const TRACK_HEIGHT: usize = 4;
+
+// Immediate-mode tuning panel: widgets are declared every frame straight against the values
+// they edit, and only which slider is being dragged survives between frames
+pub struct DebugUi {
+    pub visible: bool,
+    // Slider grabbed by the mouse, by its position in the frame's widget order
+    active: Option<usize>,
+    was_down: bool,
+    hovered: bool,
+}
+
+impl DebugUi {
+    pub fn new() -> Self {
+        DebugUi { visible: false, active: None, was_down: false, hovered: false }
+    }
+
+    // While true the mouse belongs to the panel, not the camera
+    pub fn wants_mouse(&self) -> bool {
+        self.visible && (self.hovered || self.active.is_some())
+    }
+
+    // `mouse` is in framebuffer pixels; widgets added to the panel draw immediately
+    pub fn begin<'a>(&'a mut self, framebuffer: &'a mut Framebuffer, mouse: Option<(f32, f32)>, down: bool) -> Panel<'a> {
+        let pressed = down && !self.was_down;
+        self.was_down = down;
+        if !down {
+            self.active = None;
+        }
+        self.hovered = false;
+        Panel { ui: self, framebuffer, mouse, pressed, y: PANEL_Y, next_id: 0 }
+    }
+}
+
+impl Default for DebugUi {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub struct Panel<'a> {
+    ui: &'a mut DebugUi,
+    framebuffer: &'a mut Framebuffer,
+    mouse: Option<(f32, f32)>,
+    pressed: bool,
+    y: usize,
+    next_id: usize,
+}
+
+impl Panel<'_> {
+    // Lays out the next row below the previous one, on the panel's background
+    fn row(&mut self, height: usize) -> Rect {
+        let rect = Rect { x: PANEL_X, y: self.y, width: PANEL_WIDTH, height };
+        self.y += height;
+        self.framebuffer.fill_rect(rect, Color::new(10, 15, 30), 0.75);
+        if self.mouse_over(rect) {
+            self.ui.hovered = true;
+        }
+        rect
+    }
+
+    fn mouse_over(&self, rect: Rect) -> bool {
+        self.mouse.is_some_and(|(x, y)| x >= 0.0 && y >= 0.0 && rect.contains(x as usize, y as usize))
+    }
+
+    pub fn heading(&mut self, text: &str) {
+        let rect = self.row(HEADING_HEIGHT);
+        draw_text(self.framebuffer, text, rect.x + PADDING, rect.y + HEADING_HEIGHT - GLYPH_SIZE - 2, 1, 0xFFFF00);
+    }
+
+    // Drag anywhere on the row to set the value; returns true on the frames it changes
+    pub fn slider(&mut self, label: &str, value: &mut f32, min: f32, max: f32) -> bool {
+        self.drag(label, value, min, max, |value| format_value(value, max - min))
+    }
+
+    pub fn slider_int(&mut self, label: &str, value: &mut i32, min: i32, max: i32) -> bool {
+        let mut dragged = *value as f32;
+        self.drag(label, &mut dragged, min as f32, max as f32, |value| format!("{}", value.round()));
+        let changed = dragged.round() as i32 != *value;
+        *value = dragged.round() as i32;
+        changed
+    }
+
+    fn drag(&mut self, label: &str, value: &mut f32, min: f32, max: f32, format: impl Fn(f32) -> String) -> bool {
+        let id = self.next_id;
+        self.next_id += 1;
+        let rect = self.row(SLIDER_HEIGHT);
+        let track = Rect {
+            x: rect.x + PADDING,
+            y: rect.y + GLYPH_SIZE + 8,
+            width: rect.width - PADDING * 2,
+            height: TRACK_HEIGHT,
+        };
+        if self.pressed && self.mouse_over(rect) {
+            self.ui.active = Some(id);
+        }
+
+        let mut changed = false;
+        if self.ui.active == Some(id) {
+            if let Some((x, _)) = self.mouse {
+                let t = ((x - track.x as f32) / track.width as f32).clamp(0.0, 1.0);
+                let dragged = min + (max - min) * t;
+                changed = dragged != *value;
+                *value = dragged;
+            }
+        }
+
+        let text = format!("{}: {}", label, format(*value));
+        draw_text(self.framebuffer, &text, rect.x + PADDING, rect.y + 4, 1, 0xFFFFFF);
+
+        let t = ((*value - min) / (max - min)).clamp(0.0, 1.0);
+        let filled = (track.width as f32 * t) as usize;
+        self.framebuffer.fill_rect(track, Color::new(60, 60, 80), 1.0);
+        self.framebuffer.fill_rect(Rect { width: filled, ..track }, Color::new(80, 180, 255), 1.0);
+        let handle = Rect { x: track.x + filled.min(track.width - 2), y: track.y - 2, width: 3, height: TRACK_HEIGHT + 4 };
+        self.framebuffer.fill_rect(handle, Color::new(255, 255, 255), 1.0);
+        changed
+    }
+}
+
+// Enough decimals to see the slider move across its range
+fn format_value(value: f32, range: f32) -> String {
+    if range >= 100.0 {
+        format!("{:.0}", value)
+    } else if range >= 1.0 {
+        format!("{:.2}", value)
+    } else {
+        format!("{:.4}", value)
+    }
+}
@@ -0,0 +1,299 @@
+use std::f32::consts::PI;
+use rand::Rng;
+use rand::rngs::StdRng;
+use nalgebra_glm::Vec3;
+use crate::color::Color;
+use crate::particles::{Emitter, ParticleSystem};
+use crate::scene::{Scene, TailConfig};
+use crate::collision::BODY_RADIUS;
+use crate::create_rng;
+
+const RNG_SALT: u64 = 2;
+
+// Seconds a tail particle lives; tails get longer by moving faster, not by living longer
+const ION_LIFETIME: f32 = 2.0;
+const DUST_LIFETIME: f32 = 3.0;
+// Caps how much a comet brightens at perihelion
+const MAX_ACTIVITY: f32 = 4.0;
+// Step back in time used to estimate a comet's velocity
+const MOTION_SAMPLE: f32 = 0.05;
+
+// Longest wait between solar flares, in simulated seconds
+const MAX_FLARE_INTERVAL: f32 = 6.0;
+
+const LASER_CHARGE_TIME: f32 = 1.0;
+const LASER_FIRE_TIME: f32 = 0.8;
+const LASER_IMPACT_TIME: f32 = 1.0;
+const LASER_COLOR: Color = Color::new(80, 255, 80);
+
+// Glow of gas around a comet's nucleus, drawn as a billboard
+pub struct Coma {
+    pub position: Vec3,
+    pub radius: f32,
+    pub size: f32,
+    pub color: Color,
+    pub brightness: f32,
+}
+
+// A comet's tails: ions blown straight away from the sun and heavier dust that curves back
+// along the orbit
+struct Comet {
+    index: usize,
+    ion: Emitter,
+    dust: Emitter,
+    coma: Coma,
+}
+
+impl Comet {
+    fn new(index: usize, tail: &TailConfig) -> Self {
+        let ion_color = Color::from_hex(tail.ion_color);
+        let dust_color = Color::from_hex(tail.dust_color);
+
+        let mut ion = Emitter::new(0.0, ION_LIFETIME, 0.05, ion_color, Color::new(40, 60, 200));
+        ion.spread = 0.04;
+
+        let mut dust = Emitter::new(0.0, DUST_LIFETIME, 0.07, dust_color, Color::new(120, 90, 60));
+        dust.spread = 0.2;
+
+        Comet {
+            index,
+            ion,
+            dust,
+            coma: Coma { position: Vec3::zeros(), radius: 0.0, size: 0.0, color: ion_color.lerp(&dust_color, 0.5), brightness: 0.0 },
+        }
+    }
+
+    fn update(&mut self, scene: &Scene, sun_position: Vec3, time: f32, delta_time: f32, particles: &mut ParticleSystem) {
+        let object = &scene.objects[self.index];
+        let Some(tail) = &object.tail else {
+            return;
+        };
+
+        let position = scene.position(self.index, time);
+        let away = position - sun_position;
+        let distance = away.magnitude().max(f32::EPSILON);
+        let away = away / distance;
+        let activity = (tail.reference_distance / distance).powi(2).min(MAX_ACTIVITY);
+
+        // Particles leave with the comet's own velocity, so the tails stream from the nucleus
+        // instead of being left behind along the orbit
+        let velocity = (position - scene.position(self.index, time - MOTION_SAMPLE)) / MOTION_SAMPLE;
+
+        // Ion tails reach `length` at the reference distance, whatever the comet's speed
+        self.ion.position = position;
+        self.ion.direction = away;
+        self.ion.carrier_velocity = velocity;
+        self.ion.speed = tail.length * activity / ION_LIFETIME;
+        self.ion.rate = 150.0 * activity;
+        self.ion.size = object.scale * 0.25;
+        self.ion.emit(delta_time, particles);
+
+        // Slower dust lags behind the turn of the orbit and fans out into a curve
+        self.dust.position = position;
+        self.dust.direction = match velocity.try_normalize(f32::EPSILON) {
+            Some(motion) => (away - motion * 0.6).try_normalize(f32::EPSILON).unwrap_or(away),
+            None => away,
+        };
+        self.dust.carrier_velocity = velocity;
+        self.dust.speed = tail.length * activity * 0.4 / DUST_LIFETIME;
+        self.dust.rate = 60.0 * activity;
+        self.dust.size = object.scale * 0.6;
+        self.dust.emit(delta_time, particles);
+
+        self.coma.position = position;
+        self.coma.radius = object.scale * BODY_RADIUS;
+        self.coma.size = object.scale * (0.5 + activity * 0.4);
+        self.coma.brightness = (activity / MAX_ACTIVITY).sqrt() * 0.7;
+    }
+}
+
+pub struct Beam {
+    pub from: Vec3,
+    pub to: Vec3,
+    pub width: f32,
+    pub color: Color,
+    pub intensity: f32,
+}
+
+struct Superlaser {
+    source: usize,
+    target: usize,
+    elapsed: f32,
+    impacted: bool,
+}
+
+// Particle-driven scenery on top of the scene's bodies: comet tails, solar flares, the
+// Death Star's superlaser and ship explosions
+pub struct Effects {
+    pub particles: ParticleSystem,
+    comets: Vec<Comet>,
+    flare: Emitter,
+    next_flare: f32,
+    laser_charge: Emitter,
+    laser_impact: Emitter,
+    superlaser: Option<Superlaser>,
+    explosion: Emitter,
+    // Where and when flares erupt
+    rng: StdRng,
+}
+
+impl Effects {
+    // `seed` makes every run's particles and flares the same
+    pub fn new(seed: Option<u64>) -> Self {
+        let mut flare = Emitter::new(0.0, 1.5, 0.08, Color::new(255, 240, 160), Color::new(255, 80, 0));
+        flare.spread = 0.35;
+        flare.speed = 1.2;
+
+        let mut laser_charge = Emitter::new(90.0, 0.4, 0.05, Color::new(200, 255, 200), LASER_COLOR);
+        laser_charge.spread = 1.0;
+        laser_charge.speed = 0.3;
+
+        let mut laser_impact = Emitter::new(0.0, 1.0, 0.07, Color::new(220, 255, 150), Color::new(255, 120, 0));
+        laser_impact.spread = 0.9;
+        laser_impact.speed = 0.9;
+
+        let mut explosion = Emitter::new(0.0, 1.2, 0.06, Color::new(255, 240, 200), Color::new(200, 40, 0));
+        explosion.spread = 1.5;
+        explosion.speed = 0.8;
+
+        Effects {
+            particles: ParticleSystem::new(seed),
+            comets: Vec::new(),
+            flare,
+            next_flare: 2.0,
+            laser_charge,
+            laser_impact,
+            superlaser: None,
+            explosion,
+            rng: create_rng(seed, RNG_SALT),
+        }
+    }
+
+    // Debris sprays mostly away from the surface that was hit
+    pub fn explode(&mut self, position: Vec3, direction: Vec3) {
+        self.explosion.position = position;
+        self.explosion.direction = direction;
+        self.explosion.burst(200, &mut self.particles);
+    }
+
+    pub fn comas(&self) -> impl Iterator<Item = &Coma> {
+        self.comets.iter().map(|comet| &comet.coma)
+    }
+
+    pub fn update(&mut self, scene: &Scene, time: f32, delta_time: f32) {
+        let sun = scene.sun();
+        let sun_position = sun.map_or(Vec3::zeros(), |index| scene.position(index, time));
+
+        // Reloading the scene can add, remove or reorder comets
+        if !self.comets.iter().map(|comet| comet.index).eq(scene.comets()) {
+            self.comets = scene.comets()
+                .filter_map(|index| scene.objects[index].tail.as_ref().map(|tail| Comet::new(index, tail)))
+                .collect();
+        }
+        for comet in &mut self.comets {
+            comet.update(scene, sun_position, time, delta_time, &mut self.particles);
+        }
+
+        if let Some(index) = sun {
+            // A jump back in time would otherwise hold the next flare off until it is reached again
+            if time >= self.next_flare || self.next_flare - time > MAX_FLARE_INTERVAL {
+                let rng = &mut self.rng;
+                let (theta, phi) = (rng.gen_range(0.0..2.0 * PI), rng.gen_range(0.0..PI));
+                let normal = Vec3::new(phi.sin() * theta.cos(), phi.sin() * theta.sin(), phi.cos());
+                let sun_radius = scene.objects[index].scale * 0.5;
+
+                // Flares fall back onto the sun, tracing an arc
+                self.flare.position = sun_position + normal * sun_radius;
+                self.flare.direction = normal;
+                self.flare.attractor = Some((sun_position, 1.8));
+                self.flare.burst(60, &mut self.particles);
+
+                self.next_flare = time + rng.gen_range(3.0..MAX_FLARE_INTERVAL);
+            }
+        }
+
+        self.update_superlaser(scene, time, delta_time);
+
+        self.particles.update(delta_time);
+    }
+
+    pub fn fire_superlaser(&mut self, scene: &Scene, target: usize) -> Result<(), String> {
+        if self.superlaser.is_some() {
+            return Err("The superlaser is still recharging".to_string());
+        }
+        let source = scene.objects.iter().position(|object| object.shader == "death_star")
+            .ok_or_else(|| "There is no Death Star in this scene".to_string())?;
+        if source == target {
+            return Err("The Death Star cannot fire at itself".to_string());
+        }
+
+        self.superlaser = Some(Superlaser { source, target, elapsed: 0.0, impacted: false });
+        Ok(())
+    }
+
+    // Beam endpoints sit on the two bodies' surfaces
+    fn superlaser_endpoints(scene: &Scene, laser: &Superlaser, time: f32) -> (Vec3, Vec3) {
+        let source = scene.position(laser.source, time);
+        let target = scene.position(laser.target, time);
+        let direction = (target - source).try_normalize(f32::EPSILON).unwrap_or(Vec3::x());
+
+        (
+            source + direction * scene.objects[laser.source].scale * BODY_RADIUS,
+            target - direction * scene.objects[laser.target].scale * BODY_RADIUS,
+        )
+    }
+
+    fn update_superlaser(&mut self, scene: &Scene, time: f32, delta_time: f32) {
+        let Some(laser) = &mut self.superlaser else {
+            return;
+        };
+        if laser.source >= scene.objects.len() || laser.target >= scene.objects.len() {
+            self.superlaser = None;
+            return;
+        }
+
+        laser.elapsed += delta_time;
+        let (muzzle, impact) = Self::superlaser_endpoints(scene, laser, time);
+
+        if laser.elapsed < LASER_CHARGE_TIME {
+            // Sparks gather at the dish while it charges
+            self.laser_charge.position = muzzle;
+            self.laser_charge.attractor = Some((muzzle, 2.0));
+            self.laser_charge.emit(delta_time, &mut self.particles);
+        } else if laser.elapsed >= LASER_CHARGE_TIME + LASER_FIRE_TIME * 0.5 && !laser.impacted {
+            self.laser_impact.position = impact;
+            self.laser_impact.direction = (muzzle - impact).try_normalize(f32::EPSILON).unwrap_or(Vec3::x());
+            self.laser_impact.burst(150, &mut self.particles);
+            laser.impacted = true;
+        }
+
+        if laser.elapsed >= LASER_CHARGE_TIME + LASER_FIRE_TIME + LASER_IMPACT_TIME {
+            self.superlaser = None;
+        }
+    }
+
+    pub fn superlaser_beam(&self, scene: &Scene, time: f32) -> Option<Beam> {
+        let laser = self.superlaser.as_ref()?;
+        let firing = laser.elapsed - LASER_CHARGE_TIME;
+        if !(0.0..LASER_FIRE_TIME).contains(&firing) {
+            return None;
+        }
+
+        // Snap on, then fade out over the last third of the shot
+        let fade_start = LASER_FIRE_TIME * 2.0 / 3.0;
+        let intensity = if firing < fade_start {
+            1.0
+        } else {
+            1.0 - (firing - fade_start) / (LASER_FIRE_TIME - fade_start)
+        };
+        let (from, to) = Self::superlaser_endpoints(scene, laser, time);
+
+        Some(Beam { from, to, width: 0.06, color: LASER_COLOR, intensity })
+    }
+}
+
+impl Default for Effects {
+    fn default() -> Self {
+        Self::new(None)
+    }
+}
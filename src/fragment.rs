@@ -1,25 +1,70 @@
 
 use nalgebra_glm::{Vec2, Vec3};
 use crate::color::Color;
+use crate::Light;
 
 pub struct Fragment {
     pub position: Vec2,
     pub color: Color,
     pub depth: f32,
     pub normal: Vec3,
+    // Summed Lambert term over every light, weighted by their intensities
     pub intensity: f32,
+    // The same sum carrying each light's color, on the 0-255 scale
+    pub light: Color,
     pub vertex_position: Vec3,
+    pub tex_coords: Vec2,
+    pub world_position: Vec3,
 }
 
 impl Fragment {
-    pub fn new(x: f32, y: f32, color: Color, depth: f32, normal: Vec3, intensity: f32, vertex_position: Vec3,) -> Self {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(x: f32, y: f32, color: Color, depth: f32, normal: Vec3, intensity: f32, light: Color, vertex_position: Vec3, tex_coords: Vec2, world_position: Vec3) -> Self {
         Fragment {
             position: Vec2::new(x, y),
             color,
             depth,
             normal,
             intensity,
-            vertex_position
+            light,
+            vertex_position,
+            tex_coords,
+            world_position,
         }
     }
-}
\ No newline at end of file
+
+    // Same fragment facing another way, with its lighting redone, for detail finer than the mesh
+    pub fn with_normal(&self, normal: Vec3, lights: &[Light]) -> Fragment {
+        let (intensity, light) = lighting(&normal, &self.world_position, lights);
+        Fragment { normal, intensity, light, ..*self }
+    }
+
+    // Diffusely lit surface color, tinted by the lights that reach it
+    pub fn lit(&self, color: Color) -> Color {
+        color.blend_multiply(&self.light)
+    }
+
+    // Spherical coordinates of the object-space position, in radians. Computed on demand
+    // because the trigonometry costs more than rasterizing the fragment itself
+    pub fn latitude(&self) -> f32 {
+        let radius = self.vertex_position.magnitude().max(f32::EPSILON);
+        (self.vertex_position.y / radius).clamp(-1.0, 1.0).asin()
+    }
+
+    pub fn longitude(&self) -> f32 {
+        self.vertex_position.z.atan2(self.vertex_position.x)
+    }
+}
+
+// Lambert term summed over the lights as a plain intensity and in their colors. Lights are
+// points, so their direction changes across the surface and the terminator follows them
+pub fn lighting(normal: &Vec3, position: &Vec3, lights: &[Light]) -> (f32, Color) {
+    let mut intensity = 0.0;
+    let mut light = Color::black();
+    for source in lights {
+        let lambert = normal.dot(&source.direction(position)).max(0.0);
+        intensity += lambert * source.intensity;
+        light = light + source.radiance() * lambert;
+    }
+    (intensity, light)
+}
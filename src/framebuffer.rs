@@ -1,12 +1,194 @@
 use rand::Rng;
+use nalgebra_glm::{Vec2, Vec3};
+use crate::color::Color;
+use crate::texture::Texture;
+use std::fs::File;
+use std::io::BufWriter;
 
+pub const NO_OBJECT: u32 = u32::MAX;
+// Depth is reversed: 1 at the near plane falling to 0 at the far one, so larger is nearer
+// and the far distances, squeezed towards 0, get the fine end of the float range
+pub const CLEAR_DEPTH: f32 = f32::NEG_INFINITY;
+// Side of the square pixel blocks summarised in the coarse depth buffer
+pub const DEPTH_TILE: usize = 16;
+// Behind anything that writes depth, where the stars and nebula sit
+pub const SKY_DEPTH: f32 = f32::MIN;
+
+// Flat color standing for an object id, black for NO_OBJECT
+pub fn object_id_color(id: u32) -> u32 {
+    match id {
+        NO_OBJECT => 0x000000,
+        // Spread consecutive ids across the color cube
+        id => (id.wrapping_add(1).wrapping_mul(0x9E3779B1) >> 8) | 0x404040,
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Filter {
+    Nearest,
+    Bilinear,
+}
+
+// Supersampling renders at a multiple of the target size and averages it back down
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Antialiasing {
+    Off,
+    Ssaa2x,
+}
+
+impl Antialiasing {
+    pub fn next(self) -> Self {
+        match self {
+            Antialiasing::Off => Antialiasing::Ssaa2x,
+            Antialiasing::Ssaa2x => Antialiasing::Off,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Antialiasing::Off => "off",
+            Antialiasing::Ssaa2x => "SSAA 2x",
+        }
+    }
+
+    // Internal resolution relative to the render scale; at 2x a bilinear resample lands
+    // between four pixels, averaging each 2x2 block
+    pub fn factor(self) -> f32 {
+        match self {
+            Antialiasing::Off => 1.0,
+            Antialiasing::Ssaa2x => 2.0,
+        }
+    }
+}
+
+// How scene colors brighter than white are brought into display range
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ToneMapping {
+    Clamp,
+    Reinhard,
+    Aces,
+}
+
+impl ToneMapping {
+    pub fn next(self) -> Self {
+        match self {
+            ToneMapping::Clamp => ToneMapping::Reinhard,
+            ToneMapping::Reinhard => ToneMapping::Aces,
+            ToneMapping::Aces => ToneMapping::Clamp,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ToneMapping::Clamp => "clamp",
+            ToneMapping::Reinhard => "Reinhard",
+            ToneMapping::Aces => "ACES",
+        }
+    }
+
+    fn apply(self, value: f32) -> f32 {
+        match self {
+            ToneMapping::Clamp => value,
+            // Extended Reinhard, reaching white at REINHARD_WHITE instead of never
+            ToneMapping::Reinhard => value * (1.0 + value / (REINHARD_WHITE * REINHARD_WHITE)) / (1.0 + value),
+            // Narkowicz's fit of the ACES filmic curve
+            ToneMapping::Aces => value * (2.51 * value + 0.03) / (value * (2.43 * value + 0.59) + 0.14),
+        }
+    }
+}
+
+const REINHARD_WHITE: f32 = 4.0;
+
+// How resolve() turns HDR colors into displayable ones
+#[derive(Debug, Clone, Copy)]
+pub struct Presentation {
+    pub tone_mapping: ToneMapping,
+    pub exposure: f32,
+    pub dither: bool,
+    // How much of the blurred emission is added back as glow; 0 turns bloom off
+    pub bloom: f32,
+}
+
+impl Presentation {
+    // Writes colors out as they are, for images that hold data instead of light
+    pub fn unprocessed() -> Self {
+        Presentation { tone_mapping: ToneMapping::Clamp, exposure: 1.0, dither: false, bloom: 0.0 }
+    }
+}
+
+impl Default for Presentation {
+    fn default() -> Self {
+        Presentation { tone_mapping: ToneMapping::Aces, exposure: 1.0, dither: true, bloom: 0.5 }
+    }
+}
+
+// Bloom is blurred at a fraction of the resolution, which widens the glow for free
+const BLOOM_DOWNSAMPLE: usize = 4;
+const BLOOM_RADIUS: usize = 3;
+
+const BAYER: [[u8; 4]; 4] = [
+    [0, 8, 2, 10],
+    [12, 4, 14, 6],
+    [3, 11, 1, 9],
+    [15, 7, 13, 5],
+];
+
+// Pixel rectangle inside the framebuffer, for passes that only draw into part of it
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Rect {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl Rect {
+    pub fn contains(&self, x: usize, y: usize) -> bool {
+        x >= self.x && y >= self.y && x < self.x + self.width && y < self.y + self.height
+    }
+
+    // The part of this rectangle inside a width x height buffer
+    fn clamp_to(&self, width: usize, height: usize) -> Rect {
+        let x = self.x.min(width);
+        let y = self.y.min(height);
+        Rect {
+            x,
+            y,
+            width: self.width.min(width - x),
+            height: self.height.min(height - y),
+        }
+    }
+}
+
+// 3D passes write unclamped colors to `hdr`; resolve() tone maps them into `buffer`,
+// which holds the displayed image and anything drawn on top of it, like the HUD
 pub struct Framebuffer {
     pub width: usize,
     pub height: usize,
     pub buffer: Vec<u32>,
+    pub hdr: Vec<Color>,
+    // The part of `hdr` that surfaces and effects give off themselves, which bloom spreads around
+    pub emission: Vec<Color>,
     pub zbuffer: Vec<f32>,
+    pub idbuffer: Vec<u32>,
+    // Farthest depth in each DEPTH_TILE block, a conservative low-resolution z-buffer
+    depth_tiles: Vec<f32>,
+    tiles_x: usize,
+    // The static sky (nebula and Milky Way glow) behind each pixel, re-blitted into clean tiles
+    sky: Vec<Color>,
+    sky_view: Option<u64>,
+    // Per DEPTH_TILE block: under some object's screen bounds this frame, and cleared and
+    // redrawn this frame because an object is or was there
+    dirty_tiles: Vec<bool>,
+    redraw_tiles: Vec<bool>,
+    // Where 3D passes map normalized device coordinates, and the only pixels any draw may touch
+    viewport: Rect,
+    scissor: Rect,
     background_color: u32,
-    current_color: u32,
+    current_color: Color,
+    current_emission: Color,
+    current_object: u32,
+    pub presentation: Presentation,
 }
 
 impl Framebuffer {
@@ -15,49 +197,555 @@ impl Framebuffer {
             width,
             height,
             buffer: vec![0; width * height],
-            zbuffer: vec![f32::INFINITY; width * height],
+            hdr: vec![Color::black(); width * height],
+            emission: vec![Color::black(); width * height],
+            zbuffer: vec![CLEAR_DEPTH; width * height],
+            idbuffer: vec![NO_OBJECT; width * height],
+            depth_tiles: vec![CLEAR_DEPTH; width.div_ceil(DEPTH_TILE) * height.div_ceil(DEPTH_TILE)],
+            tiles_x: width.div_ceil(DEPTH_TILE),
+            sky: vec![Color::black(); width * height],
+            sky_view: None,
+            dirty_tiles: vec![true; width.div_ceil(DEPTH_TILE) * height.div_ceil(DEPTH_TILE)],
+            redraw_tiles: vec![true; width.div_ceil(DEPTH_TILE) * height.div_ceil(DEPTH_TILE)],
+            viewport: Rect { x: 0, y: 0, width, height },
+            scissor: Rect { x: 0, y: 0, width, height },
             background_color: 0x000000,
-            current_color: 0xFFFFFF
+            current_color: Color::new(255, 255, 255),
+            current_emission: Color::black(),
+            current_object: NO_OBJECT,
+            presentation: Presentation::default(),
         }
     }
 
+    // Later draws land in this sub-rectangle and are clipped to it
+    pub fn set_viewport(&mut self, x: usize, y: usize, width: usize, height: usize) {
+        self.viewport = Rect { x, y, width, height }.clamp_to(self.width, self.height);
+        self.scissor = self.viewport;
+    }
+
+    pub fn reset_viewport(&mut self) {
+        self.set_viewport(0, 0, self.width, self.height);
+    }
+
+    pub fn viewport(&self) -> Rect {
+        self.viewport
+    }
+
+    // Narrows clipping without moving the viewport, e.g. to keep a border around a view
+    pub fn set_scissor(&mut self, rect: Rect) {
+        self.scissor = rect.clamp_to(self.width, self.height);
+    }
+
+    pub fn scissor(&self) -> Rect {
+        self.scissor
+    }
+
+    pub fn in_scissor(&self, x: usize, y: usize) -> bool {
+        self.scissor.contains(x, y)
+    }
+
+    // Only clears inside the scissor rectangle, so one view can't wipe another
     pub fn clear(&mut self) {
-        for pixel in self.buffer.iter_mut() {
-            *pixel = self.background_color;
+        self.sky_view = None;
+        self.dirty_tiles.fill(true);
+        self.redraw_tiles.fill(true);
+        let Rect { x, y, width, height } = self.scissor;
+        for row in y..y + height {
+            self.clear_span(row, x, x + width);
+        }
+        if width == 0 || height == 0 {
+            return;
+        }
+        for tile_y in y / DEPTH_TILE..=(y + height - 1) / DEPTH_TILE {
+            for tile_x in x / DEPTH_TILE..=(x + width - 1) / DEPTH_TILE {
+                self.depth_tiles[tile_y * self.tiles_x + tile_x] = CLEAR_DEPTH;
+            }
         }
-        for depth in self.zbuffer.iter_mut() {
-            *depth = f32::INFINITY;
+    }
+
+    fn clear_span(&mut self, row: usize, start: usize, end: usize) {
+        let span = row * self.width + start..row * self.width + end;
+        self.buffer[span.clone()].fill(self.background_color);
+        self.hdr[span.clone()].fill(Color::from_hex(self.background_color));
+        self.emission[span.clone()].fill(Color::black());
+        self.zbuffer[span.clone()].fill(CLEAR_DEPTH);
+        self.idbuffer[span].fill(NO_OBJECT);
+    }
+
+    // Starts a frame that may keep last frame's sky: every tile is clean while the full-screen
+    // view is the same as last frame's, until mark_dirty() says an object's bounds reach it
+    pub fn begin_dirty_tracking(&mut self, view: u64) {
+        let full_screen = self.scissor == Rect { x: 0, y: 0, width: self.width, height: self.height };
+        let unchanged = full_screen && self.sky_view == Some(view);
+        // Tiles under last frame's objects are redrawn too, to erase what they left behind
+        self.redraw_tiles.copy_from_slice(&self.dirty_tiles);
+        self.dirty_tiles.fill(!unchanged);
+        self.sky_view = full_screen.then_some(view);
+    }
+
+    pub fn mark_dirty(&mut self, min_x: f32, min_y: f32, max_x: f32, max_y: f32) {
+        let Some((tile_min_x, tile_min_y, tile_max_x, tile_max_y)) = self.tile_range(min_x, min_y, max_x, max_y) else {
+            return;
+        };
+
+        for tile_y in tile_min_y..=tile_max_y {
+            self.dirty_tiles[tile_y * self.tiles_x + tile_min_x..=tile_y * self.tiles_x + tile_max_x].fill(true);
         }
     }
 
+    pub fn mark_all_dirty(&mut self) {
+        self.dirty_tiles.fill(true);
+    }
+
+    // Clears the tiles that are dirty now or were last frame and re-blits the rest from the
+    // static sky kept from the last frame, which covers anything drawn over them since
+    pub fn clear_dirty(&mut self) {
+        // Only a full-screen view keeps its sky; a split view's half is cleared as usual
+        if self.sky_view.is_none() {
+            self.clear();
+            return;
+        }
+        for (redraw, &dirty) in self.redraw_tiles.iter_mut().zip(&self.dirty_tiles) {
+            *redraw |= dirty;
+        }
+        for tile_y in 0..self.height.div_ceil(DEPTH_TILE) {
+            for tile_x in 0..self.tiles_x {
+                let tile = tile_y * self.tiles_x + tile_x;
+                let (start, end) = (tile_x * DEPTH_TILE, ((tile_x + 1) * DEPTH_TILE).min(self.width));
+                for row in tile_y * DEPTH_TILE..((tile_y + 1) * DEPTH_TILE).min(self.height) {
+                    let span = row * self.width + start..row * self.width + end;
+                    if self.redraw_tiles[tile] {
+                        self.clear_span(row, start, end);
+                        self.sky[span].fill(Color::from_hex(self.background_color));
+                    } else {
+                        self.hdr[span.clone()].copy_from_slice(&self.sky[span.clone()]);
+                        self.emission[span.clone()].fill(Color::black());
+                        self.zbuffer[span.clone()].fill(CLEAR_DEPTH);
+                        self.idbuffer[span].fill(NO_OBJECT);
+                    }
+                }
+                self.depth_tiles[tile] = CLEAR_DEPTH;
+            }
+        }
+    }
+
+    // Tile range covering a screen rectangle, or None when it is entirely off screen
+    fn tile_range(&self, min_x: f32, min_y: f32, max_x: f32, max_y: f32) -> Option<(usize, usize, usize, usize)> {
+        if max_x < 0.0 || max_y < 0.0 || min_x >= self.width as f32 || min_y >= self.height as f32 {
+            return None;
+        }
+        let clamp_x = |x: f32| (x.max(0.0) as usize).min(self.width - 1) / DEPTH_TILE;
+        let clamp_y = |y: f32| (y.max(0.0) as usize).min(self.height - 1) / DEPTH_TILE;
+        Some((clamp_x(min_x), clamp_y(min_y), clamp_x(max_x), clamp_y(max_y)))
+    }
+
+    // Refreshes the coarse depth of every tile under a rectangle that was just drawn into
+    pub fn update_depth_tiles(&mut self, min_x: f32, min_y: f32, max_x: f32, max_y: f32) {
+        let Some((tile_min_x, tile_min_y, tile_max_x, tile_max_y)) = self.tile_range(min_x, min_y, max_x, max_y) else {
+            return;
+        };
+
+        for tile_y in tile_min_y..=tile_max_y {
+            for tile_x in tile_min_x..=tile_max_x {
+                let mut farthest = f32::INFINITY;
+                for y in tile_y * DEPTH_TILE..((tile_y + 1) * DEPTH_TILE).min(self.height) {
+                    let row = y * self.width;
+                    for x in tile_x * DEPTH_TILE..((tile_x + 1) * DEPTH_TILE).min(self.width) {
+                        farthest = farthest.min(self.zbuffer[row + x]);
+                    }
+                }
+                self.depth_tiles[tile_y * self.tiles_x + tile_x] = farthest;
+            }
+        }
+    }
+
+    // True when every tile under the rectangle already holds something nearer than `depth`
+    pub fn is_occluded(&self, min_x: f32, min_y: f32, max_x: f32, max_y: f32, depth: f32) -> bool {
+        let Some((tile_min_x, tile_min_y, tile_max_x, tile_max_y)) = self.tile_range(min_x, min_y, max_x, max_y) else {
+            return false;
+        };
+
+        (tile_min_y..=tile_max_y).all(|tile_y| {
+            (tile_min_x..=tile_max_x).all(|tile_x| self.depth_tiles[tile_y * self.tiles_x + tile_x] > depth)
+        })
+    }
+
     pub fn point(&mut self, x: usize, y: usize, depth: f32) {
-        if x < self.width && y < self.height {
+        if self.in_scissor(x, y) {
             let index = y * self.width + x;
 
-            if self.zbuffer[index] > depth {
-                self.buffer[index] = self.current_color;
+            if self.zbuffer[index] < depth {
+                self.hdr[index] = self.current_color + self.current_emission;
+                self.emission[index] = self.current_emission;
                 self.zbuffer[index] = depth;
+                self.idbuffer[index] = self.current_object;
+            }
+        }
+    }
+
+    // Lets callers skip shading fragments that would be rejected anyway
+    pub fn depth_test(&self, x: usize, y: usize, depth: f32) -> bool {
+        self.in_scissor(x, y) && self.zbuffer[y * self.width + x] < depth
+    }
+
+    pub fn draw_pixel(&mut self, x: usize, y: usize, color: u32) {
+        if self.in_scissor(x, y) {
+            self.buffer[y * self.width + x] = color;
+        }
+    }
+
+    // Additive blend that respects depth without writing it, for glowing effects, which also bloom
+    pub fn add_color(&mut self, x: usize, y: usize, depth: f32, color: Color) {
+        if self.in_scissor(x, y) {
+            let index = y * self.width + x;
+
+            if self.zbuffer[index] < depth {
+                self.hdr[index] = self.hdr[index].blend_add(&color);
+                self.emission[index] = self.emission[index].blend_add(&color);
             }
         }
     }
 
+    pub fn blend_color(&mut self, x: usize, y: usize, depth: f32, color: Color, alpha: f32) {
+        if self.in_scissor(x, y) {
+            let index = y * self.width + x;
+
+            if self.zbuffer[index] < depth {
+                self.hdr[index] = self.hdr[index].lerp(&color, alpha);
+                self.emission[index] = self.emission[index] * (1.0 - alpha);
+            }
+        }
+    }
+
+    // Tints a rectangle without touching depth, for panels behind overlays
+    pub fn fill_rect(&mut self, rect: Rect, color: Color, alpha: f32) {
+        for y in rect.y..(rect.y + rect.height).min(self.height) {
+            for x in rect.x..(rect.x + rect.width).min(self.width) {
+                if self.in_scissor(x, y) {
+                    let index = y * self.width + x;
+                    self.buffer[index] = Color::from_hex(self.buffer[index]).lerp(&color, alpha).to_hex();
+                }
+            }
+        }
+    }
+
+    pub fn stroke_rect(&mut self, rect: Rect, color: u32) {
+        let (right, bottom) = (rect.x + rect.width.max(1) - 1, rect.y + rect.height.max(1) - 1);
+        for x in rect.x..=right {
+            self.draw_pixel(x, rect.y, color);
+            self.draw_pixel(x, bottom, color);
+        }
+        for y in rect.y..=bottom {
+            self.draw_pixel(rect.x, y, color);
+            self.draw_pixel(right, y, color);
+        }
+    }
+
+    pub fn set_current_object(&mut self, id: u32) {
+        self.current_object = id;
+    }
+
+    pub fn current_object(&self) -> u32 {
+        self.current_object
+    }
+
+    pub fn object_at(&self, x: usize, y: usize) -> Option<u32> {
+        if x >= self.width || y >= self.height {
+            return None;
+        }
+
+        match self.idbuffer[y * self.width + x] {
+            NO_OBJECT => None,
+            id => Some(id),
+        }
+    }
+
+    pub fn draw_outline(&mut self, id: u32, thickness: usize, color: u32) {
+        let radius = thickness as isize;
+        let mut outline = Vec::new();
+
+        // Only scan around the object's own pixels
+        let mut min_x = self.width;
+        let mut min_y = self.height;
+        let mut max_x = 0;
+        let mut max_y = 0;
+        for (index, _) in self.idbuffer.iter().enumerate().filter(|(_, owner)| **owner == id) {
+            let (x, y) = (index % self.width, index / self.width);
+            min_x = min_x.min(x);
+            min_y = min_y.min(y);
+            max_x = max_x.max(x);
+            max_y = max_y.max(y);
+        }
+        if min_x > max_x {
+            return;
+        }
+        let min_x = min_x.saturating_sub(thickness);
+        let min_y = min_y.saturating_sub(thickness);
+        let max_x = (max_x + thickness).min(self.width - 1);
+        let max_y = (max_y + thickness).min(self.height - 1);
+
+        for y in min_y..=max_y {
+            for x in min_x..=max_x {
+                if self.idbuffer[y * self.width + x] == id {
+                    continue;
+                }
+
+                let touches_object = (-radius..=radius).any(|dy| {
+                    (-radius..=radius).any(|dx| {
+                        let nx = x as isize + dx;
+                        let ny = y as isize + dy;
+                        nx >= 0 && ny >= 0
+                            && (nx as usize) < self.width && (ny as usize) < self.height
+                            && self.idbuffer[ny as usize * self.width + nx as usize] == id
+                    })
+                });
+                if touches_object {
+                    outline.push(y * self.width + x);
+                }
+            }
+        }
+
+        for index in outline {
+            self.buffer[index] = color;
+        }
+    }
+
     pub fn set_background_color(&mut self, color: u32) {
         self.background_color = color;
     }
 
-    pub fn set_current_color(&mut self, color: u32) {
+    pub fn set_current_color(&mut self, color: Color) {
         self.current_color = color;
     }
-    pub fn draw_stars(&mut self, num_stars: usize) {
-        let mut rng = rand::thread_rng();
 
-        for _ in 0..num_stars {
-            let x = rng.gen_range(0..self.width);
-            let y = rng.gen_range(0..self.height);
-            let brightness = rng.gen_range(200..256); 
-            let star_color = (brightness << 16) | (brightness << 8) | brightness; 
+    // Added unlit on top of the current color by point(), and recorded for bloom
+    pub fn set_current_emission(&mut self, color: Color) {
+        self.current_emission = color;
+    }
+
+    // Adds light at the very back of the scene, so only pixels no geometry has covered get it
+    pub fn add_background(&mut self, x: usize, y: usize, color: Color) {
+        if self.depth_test(x, y, SKY_DEPTH) {
+            let index = y * self.width + x;
+            self.hdr[index] = self.hdr[index].blend_add(&color);
+        }
+    }
+
+    // False in clean tiles, whose static sky clear_dirty() already put back
+    pub fn redraws(&self, x: usize, y: usize) -> bool {
+        self.redraw_tiles[(y / DEPTH_TILE) * self.tiles_x + x / DEPTH_TILE]
+    }
+
+    // add_background() for the static sky, kept to be re-blitted while the tile stays clean
+    pub fn add_sky(&mut self, x: usize, y: usize, color: Color) {
+        if self.depth_test(x, y, SKY_DEPTH) && self.redraws(x, y) {
+            let index = y * self.width + x;
+            self.sky[index] = self.sky[index].blend_add(&color);
+            self.add_background(x, y, color);
+        }
+    }
+
+    pub fn clean_tile_count(&self) -> usize {
+        self.redraw_tiles.iter().filter(|&&redraw| !redraw).count()
+    }
+
+    pub fn draw_warp_streaks(&mut self, rng: &mut impl Rng, num_streaks: usize, intensity: f32) {
+        let viewport = self.viewport;
+        let center_x = viewport.x as f32 + viewport.width as f32 / 2.0;
+        let center_y = viewport.y as f32 + viewport.height as f32 / 2.0;
+        let max_radius = (viewport.width as f32 / 2.0).hypot(viewport.height as f32 / 2.0);
+
+        for _ in 0..num_streaks {
+            let angle = rng.gen_range(0.0..std::f32::consts::TAU);
+            let start_radius = rng.gen_range(0.05..1.0) * max_radius;
+            let length = start_radius * intensity * 0.5;
+            let brightness = rng.gen_range(150..=255);
+            let streak_color = Color::new(brightness, brightness, brightness);
+
+            let (dir_y, dir_x) = angle.sin_cos();
+            let steps = length.ceil() as usize;
+            for step in 0..steps {
+                let radius = start_radius + step as f32;
+                let x = center_x + dir_x * radius;
+                let y = center_y + dir_y * radius;
+                if x < 0.0 || y < 0.0 {
+                    break;
+                }
+
+                let (x, y) = (x as usize, y as usize);
+                if !self.in_scissor(x, y) {
+                    break;
+                }
 
-            self.buffer[y * self.width + x] = star_color;
+                // Only streak over empty space so planets stay visible
+                let index = y * self.width + x;
+                if self.zbuffer[index] == CLEAR_DEPTH {
+                    self.hdr[index] = streak_color;
+                }
+            }
+        }
+    }
+
+    // Presents the scene: tone maps the HDR colors inside the scissor into the display buffer.
+    // Runs once the 3D passes are done and before overlays, which draw straight into `buffer`.
+    pub fn resolve(&mut self) {
+        let Rect { x, y, width, height } = self.scissor;
+        let Presentation { tone_mapping, exposure, dither, bloom } = self.presentation;
+        let glow = if bloom > 0.0 && width > 0 && height > 0 { Some(self.bloom()) } else { None };
+        for row in y..y + height {
+            let span = row * self.width + x..row * self.width + x + width;
+            for (column, (pixel, color)) in self.buffer[span.clone()].iter_mut().zip(&self.hdr[span]).enumerate() {
+                let mut linear = color.to_vec3();
+                if let Some((glow, glow_width, glow_height)) = &glow {
+                    let u = (column as f32 + 0.5) / BLOOM_DOWNSAMPLE as f32 - 0.5;
+                    let v = ((row - y) as f32 + 0.5) / BLOOM_DOWNSAMPLE as f32 - 0.5;
+                    linear += sample_bilinear(glow, *glow_width, *glow_height, u, v) * bloom;
+                }
+                let mapped = (linear * exposure).map(|channel| tone_mapping.apply(channel));
+                // Ordered dithering: a fixed sub-step offset per pixel turns 8-bit bands into a fine pattern
+                let offset = if dither {
+                    (BAYER[row % 4][(x + column) % 4] as f32 + 0.5) / 16.0 - 0.5
+                } else {
+                    0.0
+                };
+                *pixel = Color::from_vec3(mapped.add_scalar(offset / 255.0)).to_hex();
+            }
         }
     }
-}
\ No newline at end of file
+
+    // Emission inside the scissor, averaged down by BLOOM_DOWNSAMPLE and blurred
+    fn bloom(&self) -> (Vec<Vec3>, usize, usize) {
+        let Rect { x, y, width, height } = self.scissor;
+        let (glow_width, glow_height) = (width.div_ceil(BLOOM_DOWNSAMPLE), height.div_ceil(BLOOM_DOWNSAMPLE));
+        let mut glow = vec![Vec3::zeros(); glow_width * glow_height];
+        for row in 0..height {
+            for column in 0..width {
+                let cell = (row / BLOOM_DOWNSAMPLE) * glow_width + column / BLOOM_DOWNSAMPLE;
+                glow[cell] += self.emission[(y + row) * self.width + x + column].to_vec3();
+            }
+        }
+        let average = 1.0 / (BLOOM_DOWNSAMPLE * BLOOM_DOWNSAMPLE) as f32;
+        glow.iter_mut().for_each(|value| *value *= average);
+
+        // Two box blurs along each axis come close to a Gaussian
+        let mut scratch = vec![Vec3::zeros(); glow.len()];
+        for _ in 0..2 {
+            box_blur(&glow, &mut scratch, glow_width, glow_height, true);
+            box_blur(&scratch, &mut glow, glow_width, glow_height, false);
+        }
+        (glow, glow_width, glow_height)
+    }
+
+    // Copies another buffer's colors in with its top-left corner at (x, y), clipped to the scissor
+    pub fn blit(&mut self, source: &Framebuffer, x: usize, y: usize) {
+        for source_y in 0..source.height {
+            for source_x in 0..source.width {
+                self.draw_pixel(x + source_x, y + source_y, source.buffer[source_y * source.width + source_x]);
+            }
+        }
+    }
+
+    // Lets an offscreen pass be read back like a texture, with clamped bilinear filtering
+    pub fn sample(&self, uv: Vec2) -> Color {
+        let x = (uv.x * self.width as f32 - 0.5).clamp(0.0, (self.width - 1) as f32);
+        let y = (uv.y * self.height as f32 - 0.5).clamp(0.0, (self.height - 1) as f32);
+        let (x0, y0) = (x as usize, y as usize);
+        let (x1, y1) = ((x0 + 1).min(self.width - 1), (y0 + 1).min(self.height - 1));
+        let (tx, ty) = (x - x0 as f32, y - y0 as f32);
+
+        let texel = |x: usize, y: usize| Color::from_hex(self.buffer[y * self.width + x]);
+        let top = texel(x0, y0).lerp(&texel(x1, y0), tx);
+        let bottom = texel(x0, y1).lerp(&texel(x1, y1), tx);
+        top.lerp(&bottom, ty)
+    }
+
+    // Nearest depth, since blending depths across an edge gives a value no surface has
+    pub fn sample_depth(&self, uv: Vec2) -> f32 {
+        let x = ((uv.x.clamp(0.0, 1.0) * self.width as f32) as usize).min(self.width - 1);
+        let y = ((uv.y.clamp(0.0, 1.0) * self.height as f32) as usize).min(self.height - 1);
+        self.zbuffer[y * self.width + x]
+    }
+
+    pub fn to_texture(&self) -> Texture {
+        Texture::new(self.width, self.height, self.buffer.iter().map(|&pixel| Color::from_hex(pixel)).collect())
+    }
+
+    // Resamples the color buffer to another resolution, for rendering below the window size
+    pub fn upscale(&self, out: &mut Vec<u32>, out_width: usize, out_height: usize, filter: Filter) {
+        out.resize(out_width * out_height, 0);
+        let scale_x = self.width as f32 / out_width as f32;
+        let scale_y = self.height as f32 / out_height as f32;
+
+        for y in 0..out_height {
+            let source_y = ((y as f32 + 0.5) * scale_y - 0.5).max(0.0);
+            for x in 0..out_width {
+                let source_x = ((x as f32 + 0.5) * scale_x - 0.5).max(0.0);
+                out[y * out_width + x] = match filter {
+                    Filter::Nearest => {
+                        let nearest_x = (source_x.round() as usize).min(self.width - 1);
+                        let nearest_y = (source_y.round() as usize).min(self.height - 1);
+                        self.buffer[nearest_y * self.width + nearest_x]
+                    }
+                    Filter::Bilinear => {
+                        let (x0, y0) = ((source_x as usize).min(self.width - 1), (source_y as usize).min(self.height - 1));
+                        let (x1, y1) = ((x0 + 1).min(self.width - 1), (y0 + 1).min(self.height - 1));
+                        let (tx, ty) = (source_x - x0 as f32, source_y - y0 as f32);
+
+                        let texel = |x: usize, y: usize| Color::from_hex(self.buffer[y * self.width + x]);
+                        let top = texel(x0, y0).lerp(&texel(x1, y0), tx);
+                        let bottom = texel(x0, y1).lerp(&texel(x1, y1), tx);
+                        top.lerp(&bottom, ty).to_hex()
+                    }
+                };
+            }
+        }
+    }
+
+    pub fn save_png(&self, path: &str) -> Result<(), String> {
+        let file = File::create(path).map_err(|e| format!("Failed to create {}: {}", path, e))?;
+        let mut encoder = png::Encoder::new(BufWriter::new(file), self.width as u32, self.height as u32);
+        encoder.set_color(png::ColorType::Rgb);
+        encoder.set_depth(png::BitDepth::Eight);
+
+        let mut data = Vec::with_capacity(self.buffer.len() * 3);
+        for pixel in &self.buffer {
+            data.push(((pixel >> 16) & 0xFF) as u8);
+            data.push(((pixel >> 8) & 0xFF) as u8);
+            data.push((pixel & 0xFF) as u8);
+        }
+
+        let mut writer = encoder.write_header().map_err(|e| format!("Failed to write {}: {}", path, e))?;
+        writer.write_image_data(&data).map_err(|e| format!("Failed to write {}: {}", path, e))
+    }
+}
+
+// Averages each value with its BLOOM_RADIUS neighbours along one axis, clamping at the edges
+fn box_blur(source: &[Vec3], target: &mut [Vec3], width: usize, height: usize, horizontal: bool) {
+    let taps = (BLOOM_RADIUS * 2 + 1) as f32;
+    for y in 0..height {
+        for x in 0..width {
+            let mut sum = Vec3::zeros();
+            for offset in 0..=BLOOM_RADIUS * 2 {
+                let (sx, sy) = if horizontal {
+                    ((x + offset).saturating_sub(BLOOM_RADIUS).min(width - 1), y)
+                } else {
+                    (x, (y + offset).saturating_sub(BLOOM_RADIUS).min(height - 1))
+                };
+                sum += source[sy * width + sx];
+            }
+            target[y * width + x] = sum / taps;
+        }
+    }
+}
+
+fn sample_bilinear(values: &[Vec3], width: usize, height: usize, x: f32, y: f32) -> Vec3 {
+    let x = x.clamp(0.0, (width - 1) as f32);
+    let y = y.clamp(0.0, (height - 1) as f32);
+    let (x0, y0) = (x as usize, y as usize);
+    let (x1, y1) = ((x0 + 1).min(width - 1), (y0 + 1).min(height - 1));
+    let (tx, ty) = (x - x0 as f32, y - y0 as f32);
+
+    let top = values[y0 * width + x0].lerp(&values[y0 * width + x1], tx);
+    let bottom = values[y1 * width + x0].lerp(&values[y1 * width + x1], tx);
+    top.lerp(&bottom, ty)
+}
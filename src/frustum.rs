@@ -0,0 +1,28 @@
+use nalgebra_glm::{Vec3, Vec4, Mat4};
+
+// Six inward-facing planes (a, b, c, d) with a*x + b*y + c*z + d >= 0 inside
+pub struct Frustum {
+    planes: [Vec4; 6],
+}
+
+impl Frustum {
+    // Extracts the planes from a combined projection * view matrix (Gribb-Hartmann)
+    pub fn from_matrix(matrix: &Mat4) -> Self {
+        let row = |i: usize| Vec4::new(matrix[(i, 0)], matrix[(i, 1)], matrix[(i, 2)], matrix[(i, 3)]);
+        let (x, y, z, w) = (row(0), row(1), row(2), row(3));
+
+        // Depth is reversed, running from 1 at the near plane to 0 at the far one
+        let planes = [w + x, w - x, w + y, w - y, w - z, z].map(|plane| {
+            let length = Vec3::new(plane.x, plane.y, plane.z).magnitude();
+            if length > 0.0 { plane / length } else { plane }
+        });
+
+        Frustum { planes }
+    }
+
+    pub fn intersects_sphere(&self, center: Vec3, radius: f32) -> bool {
+        self.planes.iter().all(|plane| {
+            plane.x * center.x + plane.y * center.y + plane.z * center.z + plane.w >= -radius
+        })
+    }
+}
@@ -0,0 +1,113 @@
+use nalgebra_glm::{Vec2, Vec3};
+use crate::vertex::Vertex;
+use crate::color::Color;
+use crate::texture::Texture;
+use crate::material::Material;
+use crate::mesh::{IndexedMesh, smooth_normals};
+
+pub struct Gltf {
+    pub mesh: IndexedMesh,
+    pub materials: Vec<Material>,
+}
+
+struct Primitive {
+    vertices: Vec<Vertex>,
+    indices: Vec<u32>,
+    material: Option<usize>,
+}
+
+// Loads every triangle primitive of a .gltf/.glb file into a single mesh.
+// Node transforms are ignored, so meshes are placed in their local space.
+pub fn load(filename: &str) -> Result<Gltf, String> {
+    let (document, buffers, images) = gltf::import(filename)
+        .map_err(|e| format!("Failed to load {}: {}", filename, e))?;
+
+    let textures: Vec<Texture> = images.iter().map(image_to_texture).collect();
+
+    let materials = document.materials().map(|material| {
+        let pbr = material.pbr_metallic_roughness();
+        let [r, g, b, _] = pbr.base_color_factor();
+
+        Material {
+            name: material.name().unwrap_or("").to_string(),
+            diffuse: Vec3::new(r, g, b),
+            specular: Vec3::zeros(),
+            shininess: 0.0,
+            diffuse_texture: pbr.base_color_texture()
+                .and_then(|info| textures.get(info.texture().source().index()).cloned()),
+        }
+    }).collect();
+
+    let mut primitives = Vec::new();
+    for mesh in document.meshes() {
+        for primitive in mesh.primitives() {
+            if primitive.mode() != gltf::mesh::Mode::Triangles {
+                continue;
+            }
+
+            let reader = primitive.reader(|buffer| Some(buffers[buffer.index()].0.as_slice()));
+
+            let positions: Vec<Vec3> = match reader.read_positions() {
+                Some(positions) => positions.map(|p| Vec3::new(p[0], p[1], p[2])).collect(),
+                None => continue,
+            };
+            let indices: Vec<u32> = match reader.read_indices() {
+                Some(indices) => indices.into_u32().collect(),
+                None => (0..positions.len() as u32).collect(),
+            };
+            let normals: Vec<Vec3> = match reader.read_normals() {
+                Some(normals) => normals.map(|n| Vec3::new(n[0], n[1], n[2])).collect(),
+                None => smooth_normals(&positions, &indices),
+            };
+            // glTF already uses a top-left UV origin, unlike OBJ
+            let tex_coords: Vec<Vec2> = match reader.read_tex_coords(0) {
+                Some(tex_coords) => tex_coords.into_f32().map(|t| Vec2::new(t[0], t[1])).collect(),
+                None => vec![Vec2::new(0.0, 0.0); positions.len()],
+            };
+
+            let vertices = positions.iter().enumerate()
+                .map(|(i, &position)| Vertex::new(position, normals[i], tex_coords[i]))
+                .collect();
+
+            primitives.push(Primitive {
+                vertices,
+                indices,
+                material: primitive.material().index(),
+            });
+        }
+    }
+
+    // Sort by material so faces sharing a material form a single group
+    primitives.sort_by_key(|primitive| primitive.material);
+
+    let mut mesh = IndexedMesh::new();
+    for primitive in primitives {
+        mesh.append(primitive.vertices, &primitive.indices, primitive.material);
+    }
+
+    Ok(Gltf { mesh, materials })
+}
+
+fn image_to_texture(image: &gltf::image::Data) -> Texture {
+    use gltf::image::Format;
+
+    let channels = match image.format {
+        Format::R8 => 1,
+        Format::R8G8 => 2,
+        Format::R8G8B8 => 3,
+        Format::R8G8B8A8 => 4,
+        _ => {
+            eprintln!("Unsupported glTF texture format, using a white texture");
+            return Texture::new(1, 1, vec![Color::new(255, 255, 255)]);
+        }
+    };
+
+    let pixels = image.pixels.chunks_exact(channels)
+        .map(|p| match channels {
+            1 | 2 => Color::new(p[0], p[0], p[0]),
+            _ => Color::new(p[0], p[1], p[2]),
+        })
+        .collect();
+
+    Texture::new(image.width as usize, image.height as usize, pixels)
+}
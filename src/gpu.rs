@@ -0,0 +1,734 @@
+use std::sync::mpsc;
+use std::time::Instant;
+use bytemuck::{Pod, Zeroable};
+use nalgebra_glm::{Mat4, Vec3};
+use crate::backend::{GpuSurface, RenderBackend, SurfaceStyle};
+use crate::camera::Camera;
+use crate::color::Color;
+use crate::framebuffer::{Framebuffer, Rect, CLEAR_DEPTH, NO_OBJECT};
+use crate::mesh::IndexedMesh;
+use crate::nebula::Nebula;
+use crate::profiler::ObjectProfile;
+use crate::renderer::{create_model_matrix, DebugView, RenderStats, Renderer};
+use crate::scene::Scene;
+use crate::shaders::ShaderRegistry;
+use crate::Light;
+
+// Must match the array sizes in gpu.wgsl
+const MAX_LIGHTS: usize = 4;
+const MAX_NEBULA_LAYERS: usize = 4;
+
+const HDR_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+const OBJECT_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::R32Uint;
+// Depth is also written out as a color, which every backend can copy back to the CPU, as
+// the bits of the float since not every backend can render to float formats
+const DEPTH_OUTPUT_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::R32Uint;
+const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
+// Left in the depth output where nothing was drawn; reverse-Z never goes below 0
+const EMPTY_DEPTH: f32 = -1.0;
+
+#[repr(C)]
+#[derive(Clone, Copy, Pod, Zeroable)]
+struct GpuVertex {
+    position: [f32; 3],
+    normal: [f32; 3],
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Pod, Zeroable)]
+struct Instance {
+    model: [[f32; 4]; 4],
+    colors: [[f32; 4]; 4],
+    // Style, object id, lit from the viewer, noise seed
+    kind: [u32; 4],
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Pod, Zeroable)]
+struct FrameUniforms {
+    view_projection: [[f32; 4]; 4],
+    inverse_view_projection: [[f32; 4]; 4],
+    eye: [f32; 4],
+    light_positions: [[f32; 4]; MAX_LIGHTS],
+    light_radiance: [[f32; 4]; MAX_LIGHTS],
+    counts: [u32; 4],
+    layer_distances: [f32; MAX_NEBULA_LAYERS],
+}
+
+struct MeshBuffers {
+    vertices: wgpu::Buffer,
+    indices: wgpu::Buffer,
+    index_count: u32,
+}
+
+impl MeshBuffers {
+    fn new(device: &wgpu::Device, mesh: &IndexedMesh) -> Self {
+        let vertices: Vec<GpuVertex> = mesh.vertices.iter().map(|vertex| GpuVertex {
+            position: vertex.position.into(),
+            normal: vertex.normal.into(),
+        }).collect();
+        MeshBuffers {
+            vertices: create_buffer(device, bytemuck::cast_slice(&vertices), wgpu::BufferUsages::VERTEX),
+            indices: create_buffer(device, bytemuck::cast_slice(&mesh.indices), wgpu::BufferUsages::INDEX),
+            index_count: mesh.indices.len() as u32,
+        }
+    }
+}
+
+// One offscreen color target and the buffer it is copied into to be read on the CPU
+struct Target {
+    texture: wgpu::Texture,
+    view: wgpu::TextureView,
+    readback: wgpu::Buffer,
+    // Rows of the readback buffer are padded to wgpu's copy alignment
+    padded_row: usize,
+    texel_size: usize,
+}
+
+impl Target {
+    fn new(device: &wgpu::Device, width: usize, height: usize, format: wgpu::TextureFormat, texel_size: usize) -> Self {
+        let texture = create_texture(device, width, height, 1, format, wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC);
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let padded_row = (width * texel_size).next_multiple_of(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT as usize);
+        let readback = device.create_buffer(&wgpu::BufferDescriptor {
+            label: None,
+            size: (padded_row * height) as u64,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+        Target { texture, view, readback, padded_row, texel_size }
+    }
+
+    fn copy_out(&self, encoder: &mut wgpu::CommandEncoder, region: Rect) {
+        encoder.copy_texture_to_buffer(
+            wgpu::TexelCopyTextureInfo {
+                texture: &self.texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d { x: region.x as u32, y: region.y as u32, z: 0 },
+                aspect: wgpu::TextureAspect::All,
+            },
+            wgpu::TexelCopyBufferInfo {
+                buffer: &self.readback,
+                layout: wgpu::TexelCopyBufferLayout { offset: 0, bytes_per_row: Some(self.padded_row as u32), rows_per_image: None },
+            },
+            wgpu::Extent3d { width: region.width as u32, height: region.height as u32, depth_or_array_layers: 1 },
+        );
+    }
+
+    // Calls `texel` with each copied texel's bytes and its framebuffer index
+    fn read(&self, device: &wgpu::Device, region: Rect, framebuffer_width: usize, mut texel: impl FnMut(usize, &[u8])) -> Result<(), String> {
+        let slice = self.readback.slice(..);
+        let (sender, receiver) = mpsc::channel();
+        slice.map_async(wgpu::MapMode::Read, move |result| {
+            let _ = sender.send(result);
+        });
+        let _ = device.poll(wgpu::Maintain::Wait);
+        receiver.recv().map_err(|e| e.to_string())?.map_err(|e| format!("Failed to read back a GPU target: {}", e))?;
+
+        {
+            let bytes = slice.get_mapped_range();
+            for row in 0..region.height {
+                let start = row * self.padded_row;
+                let texels = bytes[start..start + region.width * self.texel_size].chunks_exact(self.texel_size);
+                let first = (region.y + row) * framebuffer_width + region.x;
+                for (column, bytes) in texels.enumerate() {
+                    texel(first + column, bytes);
+                }
+            }
+        }
+        self.readback.unmap();
+        Ok(())
+    }
+}
+
+struct Targets {
+    width: usize,
+    height: usize,
+    hdr: Target,
+    emission: Target,
+    object: Target,
+    depth_output: Target,
+    depth: wgpu::TextureView,
+}
+
+impl Targets {
+    fn new(device: &wgpu::Device, width: usize, height: usize) -> Self {
+        let depth = create_texture(device, width, height, 1, DEPTH_FORMAT, wgpu::TextureUsages::RENDER_ATTACHMENT);
+        Targets {
+            width,
+            height,
+            hdr: Target::new(device, width, height, HDR_FORMAT, 8),
+            emission: Target::new(device, width, height, HDR_FORMAT, 8),
+            object: Target::new(device, width, height, OBJECT_FORMAT, 4),
+            depth_output: Target::new(device, width, height, DEPTH_OUTPUT_FORMAT, 4),
+            depth: depth.create_view(&wgpu::TextureViewDescriptor::default()),
+        }
+    }
+}
+
+// Draws the bodies, their shells, the belt and the nebula with wgpu into offscreen targets,
+// then reads color, emission, depth and object ids back into the framebuffer. That keeps
+// picking, labels, the ship and effects depth testing against the scene as they do on the
+// CPU. Wireframe, the debug views and hyperspace fall back to the software rasterizer.
+pub struct GpuBackend {
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+    adapter_name: String,
+    layout: wgpu::BindGroupLayout,
+    body_pipeline: wgpu::RenderPipeline,
+    shell_pipeline: wgpu::RenderPipeline,
+    sky_pipeline: wgpu::RenderPipeline,
+    uniforms: wgpu::Buffer,
+    sampler: wgpu::Sampler,
+    bind_group: wgpu::BindGroup,
+    targets: Option<Targets>,
+    // The renderer's body mesh and the belt's rock, each uploaded on first use with the
+    // generation it was read at
+    body_mesh: Option<(u64, MeshBuffers)>,
+    belt_mesh: Option<(u64, MeshBuffers)>,
+    nebula_fingerprint: Option<u64>,
+    instances: Vec<Instance>,
+    // Grows to fit the most instances seen so far, with room for that many
+    instance_buffer: Option<(wgpu::Buffer, usize)>,
+    lights: Vec<Light>,
+}
+
+impl GpuBackend {
+    pub fn new() -> Result<Self, String> {
+        let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor::default());
+        let adapter = pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
+            power_preference: wgpu::PowerPreference::HighPerformance,
+            force_fallback_adapter: false,
+            compatible_surface: None,
+        })).ok_or("No GPU adapter found")?;
+        let (device, queue) = pollster::block_on(adapter.request_device(&wgpu::DeviceDescriptor {
+            label: None,
+            required_features: wgpu::Features::empty(),
+            required_limits: wgpu::Limits::downlevel_defaults().using_resolution(adapter.limits()),
+            memory_hints: wgpu::MemoryHints::Performance,
+        }, None)).map_err(|e| format!("Failed to open the GPU: {}", e))?;
+
+        let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("gpu.wgsl"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("gpu.wgsl").into()),
+        });
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: None,
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2Array,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: None,
+            bind_group_layouts: &[&layout],
+            push_constant_ranges: &[],
+        });
+
+        let opaque = |format| Some(wgpu::ColorTargetState { format, blend: None, write_mask: wgpu::ColorWrites::ALL });
+        // Every pipeline lists all of the pass's targets, masking off the ones it leaves alone
+        let untouched = |format| Some(wgpu::ColorTargetState { format, blend: None, write_mask: wgpu::ColorWrites::empty() });
+        let blended = Some(wgpu::ColorTargetState {
+            format: HDR_FORMAT,
+            blend: Some(wgpu::BlendState::PREMULTIPLIED_ALPHA_BLENDING),
+            write_mask: wgpu::ColorWrites::ALL,
+        });
+        let body_pipeline = create_pipeline(&device, &pipeline_layout, &module, "fs_body", true, &[
+            opaque(HDR_FORMAT), opaque(HDR_FORMAT), opaque(OBJECT_FORMAT), opaque(DEPTH_OUTPUT_FORMAT),
+        ]);
+        // Shells blend over what is behind them without hiding it from later depth tests
+        let shell_pipeline = create_pipeline(&device, &pipeline_layout, &module, "fs_shell", false, &[
+            blended.clone(), blended, untouched(OBJECT_FORMAT), untouched(DEPTH_OUTPUT_FORMAT),
+        ]);
+        let sky_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("sky"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &module,
+                entry_point: Some("vs_sky"),
+                compilation_options: Default::default(),
+                buffers: &[],
+            },
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: Some(depth_state(false, wgpu::CompareFunction::Always)),
+            multisample: wgpu::MultisampleState::default(),
+            fragment: Some(wgpu::FragmentState {
+                module: &module,
+                entry_point: Some("fs_sky"),
+                compilation_options: Default::default(),
+                targets: &[opaque(HDR_FORMAT), untouched(HDR_FORMAT), untouched(OBJECT_FORMAT), untouched(DEPTH_OUTPUT_FORMAT)],
+            }),
+            multiview: None,
+            cache: None,
+        });
+
+        let uniforms = device.create_buffer(&wgpu::BufferDescriptor {
+            label: None,
+            size: std::mem::size_of::<FrameUniforms>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let empty_nebula = create_texture(&device, 1, 1, 1, HDR_FORMAT, wgpu::TextureUsages::TEXTURE_BINDING);
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+        let bind_group = create_bind_group(&device, &layout, &uniforms, &empty_nebula, &sampler);
+
+        Ok(GpuBackend {
+            device,
+            queue,
+            adapter_name: adapter.get_info().name,
+            layout,
+            body_pipeline,
+            shell_pipeline,
+            sky_pipeline,
+            uniforms,
+            sampler,
+            bind_group,
+            targets: None,
+            body_mesh: None,
+            belt_mesh: None,
+            nebula_fingerprint: None,
+            instances: Vec::new(),
+            instance_buffer: None,
+            lights: Vec::new(),
+        })
+    }
+
+    // Bakes each layer's six faces into one layer of a texture array, in Skybox order
+    fn upload_nebula(&mut self, nebula: &Nebula) {
+        let layers: Vec<_> = nebula.layers().take(MAX_NEBULA_LAYERS).collect();
+        let resolution = layers.first().map_or(1, |(skybox, _)| skybox.faces()[0].width);
+        let texture = create_texture(
+            &self.device,
+            resolution,
+            resolution,
+            // One spare layer: the GL backend takes square textures with a multiple of six
+            // layers for cube maps, which can't be viewed as the array the sky shader reads
+            layers.len() * 6 + 1,
+            HDR_FORMAT,
+            wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+        );
+        for (layer, (skybox, _)) in layers.iter().enumerate() {
+            for (face, texture_face) in skybox.faces().iter().enumerate() {
+                let texels: Vec<[u16; 4]> = texture_face.pixels().iter().map(|color| {
+                    let rgb = color.to_vec3();
+                    [f32_to_half(rgb.x), f32_to_half(rgb.y), f32_to_half(rgb.z), f32_to_half(1.0)]
+                }).collect();
+                self.queue.write_texture(
+                    wgpu::TexelCopyTextureInfo {
+                        texture: &texture,
+                        mip_level: 0,
+                        origin: wgpu::Origin3d { x: 0, y: 0, z: (layer * 6 + face) as u32 },
+                        aspect: wgpu::TextureAspect::All,
+                    },
+                    bytemuck::cast_slice(&texels),
+                    wgpu::TexelCopyBufferLayout { offset: 0, bytes_per_row: Some(resolution as u32 * 8), rows_per_image: None },
+                    wgpu::Extent3d { width: resolution as u32, height: resolution as u32, depth_or_array_layers: 1 },
+                );
+            }
+        }
+        self.bind_group = create_bind_group(&self.device, &self.layout, &self.uniforms, &texture, &self.sampler);
+        self.nebula_fingerprint = Some(nebula.fingerprint());
+    }
+
+    fn push_instance(&mut self, model: Mat4, surface: GpuSurface, object: u32, lit_by_viewer: bool) {
+        let colors = surface.colors.map(|color| {
+            let rgb = color.to_vec3();
+            [rgb.x, rgb.y, rgb.z, 1.0]
+        });
+        let seed = if object == NO_OBJECT { 0 } else { object + 1 };
+        self.instances.push(Instance {
+            model: model.into(),
+            colors,
+            kind: [style_index(surface.style), object, lit_by_viewer as u32, seed],
+        });
+    }
+
+    fn draw(&mut self, renderer: &mut Renderer, scene: &Scene, shaders: &ShaderRegistry, camera: &Camera, time: f32) -> Result<(), String> {
+        let start = Instant::now();
+        let (view_matrix, projection_matrix, viewport_matrix) = renderer.frame_matrices(camera);
+        let view_projection = projection_matrix * view_matrix;
+        let framebuffer = &renderer.framebuffer;
+        let (viewport, scissor) = (framebuffer.viewport(), framebuffer.scissor());
+        if scissor.width == 0 || scissor.height == 0 {
+            return Ok(());
+        }
+
+        if self.targets.as_ref().is_none_or(|targets| (targets.width, targets.height) != (framebuffer.width, framebuffer.height)) {
+            self.targets = Some(Targets::new(&self.device, framebuffer.width, framebuffer.height));
+        }
+        if self.nebula_fingerprint != Some(scene.nebula.fingerprint()) {
+            self.upload_nebula(&scene.nebula);
+        }
+        if self.body_mesh.as_ref().is_none_or(|(generation, _)| *generation != renderer.mesh_generation()) {
+            self.body_mesh = Some((renderer.mesh_generation(), MeshBuffers::new(&self.device, renderer.mesh())));
+        }
+        if let Some(belt) = &scene.asteroid_belt {
+            if self.belt_mesh.as_ref().is_none_or(|(generation, _)| *generation != belt.mesh_generation) {
+                self.belt_mesh = Some((belt.mesh_generation, MeshBuffers::new(&self.device, &belt.mesh)));
+            }
+        }
+
+        // Bodies, then shells, then the belt, each a run of instances
+        self.instances.clear();
+        for (index, object) in scene.objects.iter().enumerate() {
+            let Some(shader) = shaders.get(&object.shader) else {
+                continue;
+            };
+            let model = create_model_matrix(scene.position(index, time), object.scale, scene.rotation(index, time));
+            self.push_instance(model, shader.gpu_surface(), index as u32, object.light.is_some());
+        }
+        let body_count = self.instances.len() as u32;
+        for (index, object) in scene.objects.iter().enumerate() {
+            for shell in &object.shells {
+                let Some(shader) = shaders.get(&shell.shader) else {
+                    continue;
+                };
+                let rotation = Vec3::new(0.0, time * shell.rotation_speed, object.axial_tilt);
+                let model = create_model_matrix(scene.position(index, time), object.scale * shell.scale, rotation);
+                self.push_instance(model, shader.gpu_surface(), index as u32, false);
+            }
+        }
+        let shell_end = self.instances.len() as u32;
+        let mut belt_matrices = Vec::new();
+        if let Some(belt) = &scene.asteroid_belt {
+            let surface = shaders.get(&belt.shader).map_or_else(GpuSurface::default, |shader| shader.gpu_surface());
+            belt.model_matrices(time, &mut belt_matrices);
+            for matrix in belt_matrices {
+                self.push_instance(matrix, surface, NO_OBJECT, false);
+            }
+        }
+        let instance_end = self.instances.len() as u32;
+        if self.instance_buffer.as_ref().is_none_or(|(_, capacity)| *capacity < self.instances.len()) {
+            let capacity = self.instances.len().next_power_of_two();
+            let buffer = self.device.create_buffer(&wgpu::BufferDescriptor {
+                label: None,
+                size: (capacity * std::mem::size_of::<Instance>()) as u64,
+                usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            });
+            self.instance_buffer = Some((buffer, capacity));
+        }
+        if let Some((buffer, _)) = &self.instance_buffer {
+            self.queue.write_buffer(buffer, 0, bytemuck::cast_slice(&self.instances));
+        }
+
+        self.lights.clear();
+        scene.collect_lights(time, &mut self.lights);
+        if self.lights.is_empty() {
+            self.lights.push(Light::white(camera.eye));
+        }
+        let mut frame = FrameUniforms {
+            view_projection: view_projection.into(),
+            inverse_view_projection: view_projection.try_inverse().unwrap_or_else(Mat4::identity).into(),
+            eye: [camera.eye.x, camera.eye.y, camera.eye.z, time],
+            light_positions: [[0.0; 4]; MAX_LIGHTS],
+            light_radiance: [[0.0; 4]; MAX_LIGHTS],
+            counts: [0; 4],
+            layer_distances: [0.0; MAX_NEBULA_LAYERS],
+        };
+        for (index, light) in self.lights.iter().take(MAX_LIGHTS).enumerate() {
+            let radiance = light.radiance().to_vec3();
+            frame.light_positions[index] = [light.position.x, light.position.y, light.position.z, 1.0];
+            frame.light_radiance[index] = [radiance.x, radiance.y, radiance.z, 0.0];
+        }
+        let mut layer_count = 0;
+        for (index, (_, distance)) in scene.nebula.layers().take(MAX_NEBULA_LAYERS).enumerate() {
+            frame.layer_distances[index] = distance;
+            layer_count += 1;
+        }
+        frame.counts[0] = self.lights.len().min(MAX_LIGHTS) as u32;
+        frame.counts[1] = layer_count;
+        self.queue.write_buffer(&self.uniforms, 0, bytemuck::bytes_of(&frame));
+
+        let targets = self.targets.as_ref().expect("targets are created above");
+        let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+        {
+            let clear = |color: wgpu::Color, view| Some(wgpu::RenderPassColorAttachment {
+                view,
+                resolve_target: None,
+                ops: wgpu::Operations { load: wgpu::LoadOp::Clear(color), store: wgpu::StoreOp::Store },
+            });
+            let empty = wgpu::Color { r: EMPTY_DEPTH.to_bits() as f64, g: 0.0, b: 0.0, a: 0.0 };
+            let no_object = wgpu::Color { r: NO_OBJECT as f64, g: 0.0, b: 0.0, a: 0.0 };
+            let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("scene"),
+                color_attachments: &[
+                    clear(wgpu::Color::TRANSPARENT, &targets.hdr.view),
+                    clear(wgpu::Color::TRANSPARENT, &targets.emission.view),
+                    clear(no_object, &targets.object.view),
+                    clear(empty, &targets.depth_output.view),
+                ],
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                    view: &targets.depth,
+                    depth_ops: Some(wgpu::Operations { load: wgpu::LoadOp::Clear(0.0), store: wgpu::StoreOp::Store }),
+                    stencil_ops: None,
+                }),
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+            pass.set_viewport(viewport.x as f32, viewport.y as f32, viewport.width as f32, viewport.height as f32, 0.0, 1.0);
+            pass.set_scissor_rect(scissor.x as u32, scissor.y as u32, scissor.width as u32, scissor.height as u32);
+            pass.set_bind_group(0, &self.bind_group, &[]);
+
+            // The sky is at infinity, which the top-down orthographic map can't show
+            if layer_count > 0 && !camera.map_view {
+                pass.set_pipeline(&self.sky_pipeline);
+                pass.draw(0..3, 0..1);
+            }
+
+            if let Some((instances, _)) = &self.instance_buffer {
+                pass.set_vertex_buffer(1, instances.slice(..));
+                let (_, body) = self.body_mesh.as_ref().expect("body mesh is uploaded above");
+                pass.set_pipeline(&self.body_pipeline);
+                pass.set_vertex_buffer(0, body.vertices.slice(..));
+                pass.set_index_buffer(body.indices.slice(..), wgpu::IndexFormat::Uint32);
+                pass.draw_indexed(0..body.index_count, 0, 0..body_count);
+
+                if let Some((_, belt)) = self.belt_mesh.as_ref().filter(|_| instance_end > shell_end) {
+                    pass.set_vertex_buffer(0, belt.vertices.slice(..));
+                    pass.set_index_buffer(belt.indices.slice(..), wgpu::IndexFormat::Uint32);
+                    pass.draw_indexed(0..belt.index_count, 0, shell_end..instance_end);
+                }
+
+                // Shells go last so they blend over the bodies and the belt behind them
+                pass.set_pipeline(&self.shell_pipeline);
+                pass.set_vertex_buffer(0, body.vertices.slice(..));
+                pass.set_index_buffer(body.indices.slice(..), wgpu::IndexFormat::Uint32);
+                pass.draw_indexed(0..body.index_count, 0, body_count..shell_end);
+            }
+        }
+        for target in [&targets.hdr, &targets.emission, &targets.object, &targets.depth_output] {
+            target.copy_out(&mut encoder, scissor);
+        }
+        self.queue.submit([encoder.finish()]);
+
+        let framebuffer = &mut renderer.framebuffer;
+        framebuffer.set_background_color(0x000000);
+        framebuffer.clear();
+        read_back(&self.device, targets, framebuffer, scissor)?;
+
+        // Stars stay on the CPU, where they depth test against what was read back
+        framebuffer.set_current_object(NO_OBJECT);
+        scene.star_field.draw(framebuffer, &view_projection, &viewport_matrix, time);
+
+        let body = &self.body_mesh.as_ref().expect("body mesh is uploaded above").1;
+        let belt_triangles = self.belt_mesh.as_ref().map_or(0, |(_, belt)| belt.index_count / 3 * (instance_end - shell_end));
+        let stats = RenderStats {
+            triangles: (body.index_count / 3 * shell_end + belt_triangles) as usize,
+            ..RenderStats::default()
+        };
+        renderer.stats += stats;
+        renderer.profile.push(ObjectProfile { name: format!("gpu ({})", self.adapter_name), start, stats });
+        Ok(())
+    }
+}
+
+impl RenderBackend for GpuBackend {
+    fn name(&self) -> &'static str {
+        "gpu"
+    }
+
+    fn render_frame(&mut self, renderer: &mut Renderer, scene: &Scene, shaders: &ShaderRegistry, camera: &Camera, time: f32) {
+        if renderer.wireframe || renderer.debug_view != DebugView::Final || camera.in_hyperspace() {
+            renderer.render_frame(scene, shaders, camera, time);
+            return;
+        }
+        if let Err(error) = self.draw(renderer, scene, shaders, camera, time) {
+            eprintln!("{}", error);
+            renderer.render_frame(scene, shaders, camera, time);
+        }
+    }
+}
+
+fn read_back(device: &wgpu::Device, targets: &Targets, framebuffer: &mut Framebuffer, region: Rect) -> Result<(), String> {
+    let width = framebuffer.width;
+    let hdr = &mut framebuffer.hdr;
+    targets.hdr.read(device, region, width, |index, bytes| hdr[index] = half_color(bytes))?;
+    let emission = &mut framebuffer.emission;
+    targets.emission.read(device, region, width, |index, bytes| emission[index] = half_color(bytes))?;
+    let idbuffer = &mut framebuffer.idbuffer;
+    targets.object.read(device, region, width, |index, bytes| {
+        idbuffer[index] = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+    })?;
+    let zbuffer = &mut framebuffer.zbuffer;
+    targets.depth_output.read(device, region, width, |index, bytes| {
+        let depth = f32::from_bits(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]));
+        zbuffer[index] = if depth < 0.0 { CLEAR_DEPTH } else { depth };
+    })
+}
+
+// An Rgba16Float texel, where 1.0 is full brightness
+fn half_color(bytes: &[u8]) -> Color {
+    let channel = |offset: usize| half_to_f32(u16::from_le_bytes([bytes[offset], bytes[offset + 1]]));
+    Color::from_rgb(channel(0), channel(2), channel(4))
+}
+
+// Values too small for a normal half become 0
+fn f32_to_half(value: f32) -> u16 {
+    let bits = value.to_bits();
+    let sign = ((bits >> 16) & 0x8000) as u16;
+    let exponent = ((bits >> 23) & 0xFF) as i32 - 127 + 15;
+    match exponent {
+        ..=0 => sign,
+        0x1F.. => sign | 0x7C00,
+        _ => sign | ((exponent as u16) << 10) | ((bits & 0x7F_FFFF) >> 13) as u16,
+    }
+}
+
+fn half_to_f32(bits: u16) -> f32 {
+    let sign = if bits & 0x8000 == 0 { 1.0 } else { -1.0 };
+    let exponent = (bits >> 10) & 0x1F;
+    let mantissa = (bits & 0x3FF) as f32 / 1024.0;
+    sign * match exponent {
+        0 => mantissa * 2f32.powi(-14),
+        0x1F if mantissa == 0.0 => f32::INFINITY,
+        0x1F => f32::NAN,
+        _ => (1.0 + mantissa) * 2f32.powi(exponent as i32 - 15),
+    }
+}
+
+fn style_index(style: SurfaceStyle) -> u32 {
+    // Must match the constants in gpu.wgsl
+    match style {
+        SurfaceStyle::Rocky => 0,
+        SurfaceStyle::Bands => 1,
+        SurfaceStyle::Star => 2,
+        SurfaceStyle::Ocean => 3,
+        SurfaceStyle::Lava => 4,
+        SurfaceStyle::Metal => 5,
+        SurfaceStyle::Clouds => 6,
+        SurfaceStyle::Aurora => 7,
+    }
+}
+
+fn create_buffer(device: &wgpu::Device, contents: &[u8], usage: wgpu::BufferUsages) -> wgpu::Buffer {
+    let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+        label: None,
+        size: contents.len().max(4).next_multiple_of(wgpu::COPY_BUFFER_ALIGNMENT as usize) as u64,
+        usage,
+        mapped_at_creation: true,
+    });
+    buffer.slice(..).get_mapped_range_mut()[..contents.len()].copy_from_slice(contents);
+    buffer.unmap();
+    buffer
+}
+
+fn create_texture(device: &wgpu::Device, width: usize, height: usize, layers: usize, format: wgpu::TextureFormat, usage: wgpu::TextureUsages) -> wgpu::Texture {
+    device.create_texture(&wgpu::TextureDescriptor {
+        label: None,
+        size: wgpu::Extent3d { width: width as u32, height: height as u32, depth_or_array_layers: layers as u32 },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format,
+        usage,
+        view_formats: &[],
+    })
+}
+
+fn create_bind_group(device: &wgpu::Device, layout: &wgpu::BindGroupLayout, uniforms: &wgpu::Buffer, nebula: &wgpu::Texture, sampler: &wgpu::Sampler) -> wgpu::BindGroup {
+    let view = nebula.create_view(&wgpu::TextureViewDescriptor {
+        dimension: Some(wgpu::TextureViewDimension::D2Array),
+        ..Default::default()
+    });
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: None,
+        layout,
+        entries: &[
+            wgpu::BindGroupEntry { binding: 0, resource: uniforms.as_entire_binding() },
+            wgpu::BindGroupEntry { binding: 1, resource: wgpu::BindingResource::TextureView(&view) },
+            wgpu::BindGroupEntry { binding: 2, resource: wgpu::BindingResource::Sampler(sampler) },
+        ],
+    })
+}
+
+// Reverse-Z: larger depths are nearer, and the depth buffer clears to 0
+fn depth_state(write: bool, compare: wgpu::CompareFunction) -> wgpu::DepthStencilState {
+    wgpu::DepthStencilState {
+        format: DEPTH_FORMAT,
+        depth_write_enabled: write,
+        depth_compare: compare,
+        stencil: wgpu::StencilState::default(),
+        bias: wgpu::DepthBiasState::default(),
+    }
+}
+
+// Meshes drawn per instance, with the vertex and instance layouts gpu.wgsl expects
+fn create_pipeline(
+    device: &wgpu::Device,
+    layout: &wgpu::PipelineLayout,
+    module: &wgpu::ShaderModule,
+    fragment_entry: &str,
+    write_depth: bool,
+    targets: &[Option<wgpu::ColorTargetState>],
+) -> wgpu::RenderPipeline {
+    let vertex_attributes = wgpu::vertex_attr_array![0 => Float32x3, 1 => Float32x3];
+    let instance_attributes = wgpu::vertex_attr_array![
+        2 => Float32x4, 3 => Float32x4, 4 => Float32x4, 5 => Float32x4,
+        6 => Float32x4, 7 => Float32x4, 8 => Float32x4, 9 => Float32x4,
+        10 => Uint32x4,
+    ];
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some(fragment_entry),
+        layout: Some(layout),
+        vertex: wgpu::VertexState {
+            module,
+            entry_point: Some("vs_main"),
+            compilation_options: Default::default(),
+            buffers: &[
+                wgpu::VertexBufferLayout {
+                    array_stride: std::mem::size_of::<GpuVertex>() as u64,
+                    step_mode: wgpu::VertexStepMode::Vertex,
+                    attributes: &vertex_attributes,
+                },
+                wgpu::VertexBufferLayout {
+                    array_stride: std::mem::size_of::<Instance>() as u64,
+                    step_mode: wgpu::VertexStepMode::Instance,
+                    attributes: &instance_attributes,
+                },
+            ],
+        },
+        primitive: wgpu::PrimitiveState {
+            cull_mode: Some(wgpu::Face::Back),
+            ..Default::default()
+        },
+        depth_stencil: Some(depth_state(write_depth, wgpu::CompareFunction::Greater)),
+        multisample: wgpu::MultisampleState::default(),
+        fragment: Some(wgpu::FragmentState {
+            module,
+            entry_point: Some(fragment_entry),
+            compilation_options: Default::default(),
+            targets,
+        }),
+        multiview: None,
+        cache: None,
+    })
+}
@@ -0,0 +1,312 @@
+// WGSL versions of the procedural surfaces for the GPU backend. Each shader in shaders.rs
+// picks one of these styles and fills in its colors (see backend::GpuSurface).
+
+struct Frame {
+    view_projection: mat4x4<f32>,
+    inverse_view_projection: mat4x4<f32>,
+    // xyz is the camera position, w the time in seconds
+    eye: vec4<f32>,
+    light_positions: array<vec4<f32>, 4>,
+    light_radiance: array<vec4<f32>, 4>,
+    // Lights and nebula layers
+    counts: vec4<u32>,
+    layer_distances: vec4<f32>,
+}
+
+@group(0) @binding(0) var<uniform> frame: Frame;
+// Six faces per nebula layer, in Skybox order
+@group(0) @binding(1) var nebula: texture_2d_array<f32>;
+// Bilinear and clamped to the edge of each face, like Texture::sample_bilinear
+@group(0) @binding(2) var nebula_sampler: sampler;
+
+const ROCKY: u32 = 0u;
+const BANDS: u32 = 1u;
+const STAR: u32 = 2u;
+const OCEAN: u32 = 3u;
+const LAVA: u32 = 4u;
+const METAL: u32 = 5u;
+const CLOUDS: u32 = 6u;
+const AURORA: u32 = 7u;
+
+struct VertexInput {
+    @location(0) position: vec3<f32>,
+    @location(1) normal: vec3<f32>,
+}
+
+struct Instance {
+    @location(2) model_0: vec4<f32>,
+    @location(3) model_1: vec4<f32>,
+    @location(4) model_2: vec4<f32>,
+    @location(5) model_3: vec4<f32>,
+    @location(6) color_0: vec4<f32>,
+    @location(7) color_1: vec4<f32>,
+    @location(8) color_2: vec4<f32>,
+    @location(9) color_3: vec4<f32>,
+    // Style, object id, lit from the viewer instead of the suns, noise seed
+    @location(10) kind: vec4<u32>,
+}
+
+struct Surface {
+    @builtin(position) position: vec4<f32>,
+    @location(0) world: vec3<f32>,
+    @location(1) normal: vec3<f32>,
+    @location(2) local: vec3<f32>,
+    @location(3) @interpolate(flat) kind: vec4<u32>,
+    @location(4) @interpolate(flat) color_0: vec3<f32>,
+    @location(5) @interpolate(flat) color_1: vec3<f32>,
+    @location(6) @interpolate(flat) color_2: vec3<f32>,
+    @location(7) @interpolate(flat) color_3: vec3<f32>,
+}
+
+struct BodyOutput {
+    @location(0) color: vec4<f32>,
+    @location(1) emission: vec4<f32>,
+    @location(2) object: u32,
+    // Bits of the depth, since not every backend renders to float formats
+    @location(3) depth: u32,
+}
+
+// Premultiplied, so clouds cover what is behind them and auroras add to it
+struct ShellOutput {
+    @location(0) color: vec4<f32>,
+    @location(1) emission: vec4<f32>,
+}
+
+@vertex
+fn vs_main(vertex: VertexInput, instance: Instance) -> Surface {
+    let model = mat4x4<f32>(instance.model_0, instance.model_1, instance.model_2, instance.model_3);
+    let world = model * vec4<f32>(vertex.position, 1.0);
+
+    var out: Surface;
+    out.position = frame.view_projection * world;
+    out.world = world.xyz;
+    // Models only rotate and scale uniformly, so they carry normals as they are
+    out.normal = (model * vec4<f32>(vertex.normal, 0.0)).xyz;
+    out.local = vertex.position;
+    out.kind = instance.kind;
+    out.color_0 = instance.color_0.rgb;
+    out.color_1 = instance.color_1.rgb;
+    out.color_2 = instance.color_2.rgb;
+    out.color_3 = instance.color_3.rgb;
+    return out;
+}
+
+fn hash(p: vec3<f32>) -> f32 {
+    var q = fract(p * vec3<f32>(0.1031, 0.1030, 0.0973));
+    q += dot(q, q.yxz + 33.33);
+    return fract((q.x + q.y) * q.z);
+}
+
+fn value_noise(p: vec3<f32>) -> f32 {
+    let cell = floor(p);
+    let f = fract(p);
+    let u = f * f * f * (f * (f * 6.0 - 15.0) + 10.0);
+    let corner = vec2<f32>(0.0, 1.0);
+    let bottom = mix(
+        mix(hash(cell + corner.xxx), hash(cell + corner.yxx), u.x),
+        mix(hash(cell + corner.xyx), hash(cell + corner.yyx), u.x),
+        u.y,
+    );
+    let top = mix(
+        mix(hash(cell + corner.xxy), hash(cell + corner.yxy), u.x),
+        mix(hash(cell + corner.xyy), hash(cell + corner.yyy), u.x),
+        u.y,
+    );
+    return mix(bottom, top, u.z);
+}
+
+// Five octaves, stretched so most of the surface spans 0 to 1
+fn fbm(p: vec3<f32>) -> f32 {
+    var sum = 0.0;
+    var amplitude = 0.5;
+    var q = p;
+    for (var octave = 0; octave < 5; octave++) {
+        sum += amplitude * value_noise(q);
+        q = q * 2.03 + vec3<f32>(1.7, 9.2, 3.1);
+        amplitude *= 0.5;
+    }
+    return clamp((sum / 0.96875 - 0.5) * 2.2 + 0.5, 0.0, 1.0);
+}
+
+// Four colors spread evenly from t = 0 to 1
+fn ramp(surface: Surface, t: f32) -> vec3<f32> {
+    let scaled = t * 3.0;
+    if scaled < 1.0 {
+        return mix(surface.color_0, surface.color_1, smoothstep(0.0, 1.0, scaled));
+    }
+    if scaled < 2.0 {
+        return mix(surface.color_1, surface.color_2, smoothstep(0.0, 1.0, scaled - 1.0));
+    }
+    return mix(surface.color_2, surface.color_3, smoothstep(0.0, 1.0, scaled - 2.0));
+}
+
+// Diffuse light from every sun, or from the viewer for the suns themselves
+fn diffuse(surface: Surface, normal: vec3<f32>) -> vec3<f32> {
+    if surface.kind.z != 0u {
+        return vec3<f32>(max(dot(normal, normalize(frame.eye.xyz - surface.world)), 0.0));
+    }
+    var light = vec3<f32>(0.0);
+    for (var index = 0u; index < frame.counts.x; index++) {
+        let direction = normalize(frame.light_positions[index].xyz - surface.world);
+        light += frame.light_radiance[index].rgb * max(dot(normal, direction), 0.0);
+    }
+    return light;
+}
+
+// Blinn-Phong highlights of every sun in its own color
+fn specular(surface: Surface, normal: vec3<f32>, shininess: f32) -> vec3<f32> {
+    let view = normalize(frame.eye.xyz - surface.world);
+    var highlight = vec3<f32>(0.0);
+    for (var index = 0u; index < frame.counts.x; index++) {
+        let direction = normalize(frame.light_positions[index].xyz - surface.world);
+        if dot(normal, direction) <= 0.0 {
+            continue;
+        }
+        let half_vector = normalize(direction + view);
+        highlight += frame.light_radiance[index].rgb * pow(max(dot(normal, half_vector), 0.0), shininess);
+    }
+    return highlight;
+}
+
+fn noise_position(surface: Surface) -> vec3<f32> {
+    return surface.local + f32(surface.kind.w) * vec3<f32>(17.1, 31.7, 5.3);
+}
+
+@fragment
+fn fs_body(surface: Surface) -> BodyOutput {
+    let normal = normalize(surface.normal);
+    let p = noise_position(surface);
+    let latitude = clamp(normalize(surface.local).y, -1.0, 1.0);
+    var color = vec3<f32>(0.0);
+    var emission = vec3<f32>(0.0);
+
+    switch surface.kind.x {
+        case BANDS: {
+            let warp = fbm(p * 4.0) - 0.5;
+            let band = 0.5 + 0.5 * sin((latitude + warp * 0.3) * 18.0);
+            let storm = smoothstep(0.72, 0.8, fbm(p * 3.0 + vec3<f32>(5.0, 0.0, 0.0)));
+            let base = mix(mix(surface.color_0, surface.color_1, band), surface.color_2, storm * 0.8);
+            color = base * diffuse(surface, normal);
+        }
+        case STAR: {
+            let granules = smoothstep(0.45, 0.8, fbm(p * 16.0 + vec3<f32>(0.0, 0.0, frame.eye.w * 0.05)));
+            let base = mix(surface.color_0, surface.color_1, granules);
+            // Brightest where the surface faces the viewer, dimming towards the limb
+            let facing = clamp(dot(normal, normalize(frame.eye.xyz - surface.world)), 0.0, 1.0);
+            emission = base * (1.0 + facing * facing * 2.0) * facing;
+        }
+        case OCEAN: {
+            let depth = fbm(p * 5.0);
+            color = mix(surface.color_0, surface.color_1, depth) * diffuse(surface, normal)
+                + surface.color_3 * specular(surface, normal, 60.0);
+        }
+        case LAVA: {
+            let crust = mix(surface.color_0, surface.color_1, fbm(p * 6.0));
+            let vein = 1.0 - smoothstep(0.0, 0.05, abs(fbm(p * 4.0 + vec3<f32>(11.0, 3.0, 7.0)) - 0.5));
+            color = crust * diffuse(surface, normal) * (1.0 - vein);
+            emission = mix(surface.color_2, surface.color_3, vein) * vein * 1.5;
+        }
+        case METAL: {
+            let direction = normalize(surface.local);
+            let longitude = atan2(direction.z, direction.x);
+            let seams = max(
+                1.0 - smoothstep(0.0, 0.04, abs(fract(latitude * 10.0) - 0.5) * 2.0 - 0.9),
+                1.0 - smoothstep(0.0, 0.04, abs(fract(longitude * 6.0) - 0.5) * 2.0 - 0.9),
+            );
+            let trench = 1.0 - smoothstep(0.015, 0.025, abs(latitude));
+            let along = dot(direction, normalize(vec3<f32>(0.4, 0.5, 0.77)));
+            let dish = smoothstep(0.955, 0.96, along);
+            var base = mix(surface.color_0, surface.color_1, max(seams * 0.5, trench));
+            base = mix(base, surface.color_2, dish);
+            color = base * diffuse(surface, normal) + specular(surface, normal, 30.0) * 0.3;
+        }
+        default: {
+            color = ramp(surface, fbm(p * 6.0)) * diffuse(surface, normal);
+        }
+    }
+
+    var out: BodyOutput;
+    out.color = vec4<f32>(color + emission, 1.0);
+    out.emission = vec4<f32>(emission, 1.0);
+    out.object = surface.kind.y;
+    out.depth = bitcast<u32>(surface.position.z);
+    return out;
+}
+
+@fragment
+fn fs_shell(surface: Surface) -> ShellOutput {
+    let normal = normalize(surface.normal);
+    let p = noise_position(surface);
+    let direction = normalize(surface.local);
+
+    var out: ShellOutput;
+    if surface.kind.x == AURORA {
+        let longitude = atan2(direction.z, direction.x);
+        let pole = smoothstep(0.55, 0.85, abs(direction.y));
+        let curtain = fbm(vec3<f32>(longitude * 3.0, frame.eye.w * 0.3, direction.y * 2.0));
+        let glow = mix(surface.color_0, surface.color_2, curtain) * pole * smoothstep(0.4, 0.7, curtain);
+        out.color = vec4<f32>(glow, 0.0);
+        out.emission = vec4<f32>(glow, 0.0);
+        return out;
+    }
+
+    let cover = smoothstep(0.5, 0.68, fbm(p * 5.0)) * 0.85;
+    out.color = vec4<f32>(surface.color_0 * diffuse(surface, normal) * cover, cover);
+    out.emission = vec4<f32>(0.0, 0.0, 0.0, cover);
+    return out;
+}
+
+struct Sky {
+    @builtin(position) position: vec4<f32>,
+    @location(0) ndc: vec2<f32>,
+}
+
+// One triangle covering the viewport
+@vertex
+fn vs_sky(@builtin(vertex_index) index: u32) -> Sky {
+    let ndc = vec2<f32>(f32(index & 1u) * 4.0 - 1.0, f32(index >> 1u) * 4.0 - 1.0);
+    var out: Sky;
+    out.position = vec4<f32>(ndc, 0.0, 1.0);
+    out.ndc = ndc;
+    return out;
+}
+
+// Skybox::sample, picking the face the direction points through
+fn sample_layer(layer: u32, direction: vec3<f32>) -> vec3<f32> {
+    let a = abs(direction);
+    var axis = 2u;
+    var major = direction.z;
+    var u = direction.x;
+    var v = direction.y;
+    if a.x >= a.y && a.x >= a.z {
+        axis = 0u;
+        major = direction.x;
+        u = direction.y;
+        v = direction.z;
+    } else if a.y >= a.z {
+        axis = 1u;
+        major = direction.y;
+        u = direction.x;
+        v = direction.z;
+    }
+    if major == 0.0 {
+        return vec3<f32>(0.0);
+    }
+
+    let face = axis * 2u + select(1u, 0u, major > 0.0);
+    let uv = (vec2<f32>(u, v) / abs(major) + 1.0) * 0.5;
+    return textureSampleLevel(nebula, nebula_sampler, uv, layer * 6u + face, 0.0).rgb;
+}
+
+@fragment
+fn fs_sky(sky: Sky) -> @location(0) vec4<f32> {
+    // Same view ray as Nebula::draw, through the far plane
+    let far = frame.inverse_view_projection * vec4<f32>(sky.ndc, 0.0, 1.0);
+    let direction = normalize((far.xyz - frame.eye.xyz * far.w) * sign(far.w));
+
+    var color = vec3<f32>(0.0);
+    for (var layer = 0u; layer < frame.counts.y; layer++) {
+        color += sample_layer(layer, direction * frame.layer_distances[layer] + frame.eye.xyz);
+    }
+    return vec4<f32>(color, 1.0);
+}
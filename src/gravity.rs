@@ -0,0 +1,93 @@
+use nalgebra_glm::Vec3;
+
+pub const G: f32 = 1.0;
+// Keeps close passes from producing infinite accelerations
+const SOFTENING: f32 = 0.05;
+const MAX_STEP: f32 = 0.01;
+// Large time jumps take coarser steps instead of stalling the frame
+const MAX_SUBSTEPS: usize = 2000;
+
+#[derive(Debug, Clone, Copy)]
+pub struct Body {
+    pub position: Vec3,
+    pub velocity: Vec3,
+    pub mass: f32,
+}
+
+// Acceleration at `point` towards every (position, mass) pair
+pub fn attraction(point: Vec3, bodies: impl Iterator<Item = (Vec3, f32)>) -> Vec3 {
+    bodies.fold(Vec3::zeros(), |sum, (position, mass)| {
+        let offset = position - point;
+        let distance_squared = offset.magnitude_squared() + SOFTENING * SOFTENING;
+        sum + offset * (G * mass / (distance_squared * distance_squared.sqrt()))
+    })
+}
+
+// Direct-summation n-body integrator. Leapfrog (kick-drift-kick) is symplectic and
+// time-reversible, so orbits stay bounded and running time backward retraces recent motion.
+#[derive(Clone)]
+pub struct Gravity {
+    pub bodies: Vec<Body>,
+    accelerations: Vec<Vec3>,
+}
+
+impl Gravity {
+    pub fn new(mut bodies: Vec<Body>) -> Self {
+        // Cancel the net momentum so the whole system doesn't drift off
+        let total_mass: f32 = bodies.iter().map(|body| body.mass).sum();
+        if total_mass > 0.0 {
+            let momentum = bodies.iter().fold(Vec3::zeros(), |sum, body| sum + body.velocity * body.mass);
+            for body in bodies.iter_mut() {
+                body.velocity -= momentum / total_mass;
+            }
+        }
+
+        let mut gravity = Gravity {
+            accelerations: vec![Vec3::zeros(); bodies.len()],
+            bodies,
+        };
+        gravity.update_accelerations();
+        gravity
+    }
+
+    pub fn step(&mut self, delta_time: f32) {
+        if delta_time == 0.0 {
+            return;
+        }
+        let substeps = ((delta_time.abs() / MAX_STEP).ceil() as usize).clamp(1, MAX_SUBSTEPS);
+        let dt = delta_time / substeps as f32;
+
+        for _ in 0..substeps {
+            for (body, acceleration) in self.bodies.iter_mut().zip(&self.accelerations) {
+                body.velocity += acceleration * (dt * 0.5);
+                body.position += body.velocity * dt;
+            }
+            self.update_accelerations();
+            for (body, acceleration) in self.bodies.iter_mut().zip(&self.accelerations) {
+                body.velocity += acceleration * (dt * 0.5);
+            }
+        }
+    }
+
+    pub fn kick(&mut self, index: usize, delta_velocity: Vec3) {
+        if let Some(body) = self.bodies.get_mut(index) {
+            body.velocity += delta_velocity;
+        }
+    }
+
+    fn update_accelerations(&mut self) {
+        for acceleration in self.accelerations.iter_mut() {
+            *acceleration = Vec3::zeros();
+        }
+
+        for i in 0..self.bodies.len() {
+            for j in i + 1..self.bodies.len() {
+                let offset = self.bodies[j].position - self.bodies[i].position;
+                let distance_squared = offset.magnitude_squared() + SOFTENING * SOFTENING;
+                let pull = offset * (G / (distance_squared * distance_squared.sqrt()));
+                self.accelerations[i] += pull * self.bodies[j].mass;
+                self.accelerations[j] -= pull * self.bodies[i].mass;
+            }
+        }
+    }
+}
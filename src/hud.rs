@@ -0,0 +1,76 @@
+use crate::framebuffer::Framebuffer;
+use crate::text::{draw_text, text_width, GLYPH_SIZE};
+
+const MESSAGE_DURATION: f32 = 2.5;
+const MESSAGE_SCALE: usize = 2;
+
+pub struct Hud {
+    message: Option<(String, f32)>,
+}
+
+impl Hud {
+    pub fn new() -> Self {
+        Hud { message: None }
+    }
+
+    pub fn show(&mut self, message: &str) {
+        self.message = Some((message.to_string(), MESSAGE_DURATION));
+    }
+
+    pub fn update(&mut self, delta_time: f32) {
+        if let Some((_, remaining)) = self.message.as_mut() {
+            *remaining -= delta_time;
+            if *remaining <= 0.0 {
+                self.message = None;
+            }
+        }
+    }
+
+    pub fn draw(&self, framebuffer: &mut Framebuffer) {
+        let Some((message, _)) = &self.message else {
+            return;
+        };
+
+        let width = text_width(message, MESSAGE_SCALE);
+        let x = framebuffer.width.saturating_sub(width) / 2;
+        let y = framebuffer.height.saturating_sub(GLYPH_SIZE * MESSAGE_SCALE + 16);
+        draw_text(framebuffer, message, x, y, MESSAGE_SCALE, 0xFFFFFF);
+    }
+}
+
+// A single line of text input, drawn centred with a cursor while it is open
+pub struct Prompt {
+    label: String,
+    pub text: String,
+}
+
+impl Prompt {
+    pub fn new(label: &str) -> Self {
+        Prompt {
+            label: label.to_string(),
+            text: String::new(),
+        }
+    }
+
+    pub fn push(&mut self, c: char) {
+        self.text.push(c);
+    }
+
+    pub fn pop(&mut self) {
+        self.text.pop();
+    }
+
+    pub fn draw(&self, framebuffer: &mut Framebuffer) {
+        let line = format!("{}{}_", self.label, self.text);
+        let width = text_width(&line, MESSAGE_SCALE);
+        let x = framebuffer.width.saturating_sub(width) / 2;
+        let y = framebuffer.height.saturating_sub(GLYPH_SIZE * MESSAGE_SCALE) / 2;
+        draw_text(framebuffer, &line, x, y, MESSAGE_SCALE, 0xFFFF00);
+    }
+}
+
+impl Default for Hud {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,210 @@
+use minifb::{Key, KeyRepeat, MouseButton, MouseMode, Window};
+use nalgebra_glm::{Vec2, Vec3};
+
+// One frame of camera input from whichever devices are in use, each axis in -1..=1
+#[derive(Debug, Default, Clone, Copy)]
+pub struct CameraControls {
+    // Orbit mode: x circles left, y circles down
+    pub orbit: Vec2,
+    // Orbit mode: turns the view around the eye, x to the right and y up
+    pub aim: Vec2,
+    // Positive moves in
+    pub zoom: f32,
+    // Fly mode: forward, right and up
+    pub fly: Vec3,
+    // Fly mode: x turns right, y looks up
+    pub look: Vec2,
+    pub toggle_mode: bool,
+    pub next_planet: bool,
+}
+
+impl CameraControls {
+    // Axes add up and presses from either side count, so devices can be mixed
+    pub fn combine(&self, other: &CameraControls) -> CameraControls {
+        let clamp2 = |v: Vec2| v.map(|value| value.clamp(-1.0, 1.0));
+        CameraControls {
+            orbit: clamp2(self.orbit + other.orbit),
+            aim: clamp2(self.aim + other.aim),
+            zoom: (self.zoom + other.zoom).clamp(-1.0, 1.0),
+            fly: (self.fly + other.fly).map(|value| value.clamp(-1.0, 1.0)),
+            look: clamp2(self.look + other.look),
+            toggle_mode: self.toggle_mode || other.toggle_mode,
+            next_planet: self.next_planet || other.next_planet,
+        }
+    }
+}
+
+// What the window reported between two rendered frames. The main thread gathers it on
+// every event pump and the render thread takes it once a frame, reading it with the
+// same calls it would make on the window.
+#[derive(Debug, Default, Clone)]
+pub struct WindowInput {
+    open: bool,
+    size: (usize, usize),
+    keys_down: Vec<Key>,
+    pressed: Vec<Key>,
+    // Presses including key repeat
+    repeated: Vec<Key>,
+    mouse: Option<(f32, f32)>,
+    mouse_inside: bool,
+    // Left, middle and right: as they are now, and whether down at any point since the last frame
+    buttons: [bool; 3],
+    buttons_seen: [bool; 3],
+    scroll: Option<(f32, f32)>,
+}
+
+impl WindowInput {
+    // Presses and scrolling add up over the pumps, so short taps between frames still count
+    pub fn gather(&mut self, window: &Window) {
+        self.open = window.is_open();
+        self.size = window.get_size();
+        self.keys_down = window.get_keys();
+        self.pressed.extend(window.get_keys_pressed(KeyRepeat::No));
+        self.repeated.extend(window.get_keys_pressed(KeyRepeat::Yes));
+        self.mouse = window.get_mouse_pos(MouseMode::Pass);
+        self.mouse_inside = window.get_mouse_pos(MouseMode::Discard).is_some();
+        for (index, button) in [MouseButton::Left, MouseButton::Middle, MouseButton::Right].into_iter().enumerate() {
+            self.buttons[index] = window.get_mouse_down(button);
+            self.buttons_seen[index] |= self.buttons[index];
+        }
+        if let Some((x, y)) = window.get_scroll_wheel() {
+            let (total_x, total_y) = self.scroll.unwrap_or((0.0, 0.0));
+            self.scroll = Some((total_x + x, total_y + y));
+        }
+    }
+
+    // Hands over everything gathered so far; held keys and buttons carry on into the next frame
+    pub fn take(&mut self) -> WindowInput {
+        let next = WindowInput {
+            open: self.open,
+            size: self.size,
+            keys_down: self.keys_down.clone(),
+            mouse: self.mouse,
+            mouse_inside: self.mouse_inside,
+            buttons: self.buttons,
+            buttons_seen: self.buttons,
+            ..WindowInput::default()
+        };
+        std::mem::replace(self, next)
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.open
+    }
+
+    pub fn get_size(&self) -> (usize, usize) {
+        self.size
+    }
+
+    pub fn is_key_down(&self, key: Key) -> bool {
+        self.keys_down.contains(&key)
+    }
+
+    pub fn is_key_pressed(&self, key: Key, repeat: KeyRepeat) -> bool {
+        self.presses(repeat).contains(&key)
+    }
+
+    pub fn get_keys_pressed(&self, repeat: KeyRepeat) -> Vec<Key> {
+        self.presses(repeat).to_vec()
+    }
+
+    fn presses(&self, repeat: KeyRepeat) -> &[Key] {
+        match repeat {
+            KeyRepeat::No => &self.pressed,
+            KeyRepeat::Yes => &self.repeated,
+        }
+    }
+
+    pub fn get_mouse_pos(&self, mode: MouseMode) -> Option<(f32, f32)> {
+        let (x, y) = self.mouse?;
+        let (width, height) = (self.size.0 as f32, self.size.1 as f32);
+        match mode {
+            MouseMode::Pass => Some((x, y)),
+            MouseMode::Clamp => Some((x.clamp(0.0, width - 1.0), y.clamp(0.0, height - 1.0))),
+            MouseMode::Discard => self.mouse_inside.then_some((x, y)),
+        }
+    }
+
+    pub fn get_mouse_down(&self, button: MouseButton) -> bool {
+        match button {
+            MouseButton::Left => self.buttons_seen[0],
+            MouseButton::Middle => self.buttons_seen[1],
+            MouseButton::Right => self.buttons_seen[2],
+        }
+    }
+
+    pub fn get_scroll_wheel(&self) -> Option<(f32, f32)> {
+        self.scroll
+    }
+}
+
+// Connected controllers: the left stick orbits or flies, the right stick aims or looks,
+// the triggers zoom or climb, the right bumper cycles planets and Y/triangle switches
+// camera mode. Built without the `gamepad` feature it never reports any input.
+pub struct Gamepads {
+    #[cfg(feature = "gamepad")]
+    gilrs: Option<gilrs::Gilrs>,
+}
+
+impl Gamepads {
+    #[cfg(feature = "gamepad")]
+    pub fn new() -> Self {
+        // No controller support on this system just means keyboard only
+        let gilrs = gilrs::Gilrs::new()
+            .map_err(|error| eprintln!("Gamepads unavailable: {}", error))
+            .ok();
+        Gamepads { gilrs }
+    }
+
+    #[cfg(not(feature = "gamepad"))]
+    pub fn new() -> Self {
+        Gamepads {}
+    }
+
+    // Must run every frame, since presses are only seen in the event queue
+    #[cfg(feature = "gamepad")]
+    pub fn poll(&mut self) -> CameraControls {
+        use gilrs::{Axis, Button, EventType};
+
+        let mut controls = CameraControls::default();
+        let Some(gilrs) = self.gilrs.as_mut() else {
+            return controls;
+        };
+
+        while let Some(event) = gilrs.next_event() {
+            match event.event {
+                EventType::ButtonPressed(Button::RightTrigger, _) => controls.next_planet = true,
+                EventType::ButtonPressed(Button::North, _) => controls.toggle_mode = true,
+                _ => {}
+            }
+        }
+
+        for (_, gamepad) in gilrs.gamepads() {
+            let trigger = |button| gamepad.button_data(button).map_or(0.0, |data| data.value());
+            let left = Vec2::new(gamepad.value(Axis::LeftStickX), gamepad.value(Axis::LeftStickY));
+            let right = Vec2::new(gamepad.value(Axis::RightStickX), gamepad.value(Axis::RightStickY));
+            let triggers = trigger(Button::RightTrigger2) - trigger(Button::LeftTrigger2);
+
+            controls = controls.combine(&CameraControls {
+                orbit: -left,
+                aim: right,
+                zoom: triggers,
+                fly: Vec3::new(left.y, left.x, triggers),
+                look: right,
+                ..CameraControls::default()
+            });
+        }
+        controls
+    }
+
+    #[cfg(not(feature = "gamepad"))]
+    pub fn poll(&mut self) -> CameraControls {
+        CameraControls::default()
+    }
+}
+
+impl Default for Gamepads {
+    fn default() -> Self {
+        Self::new()
+    }
+}
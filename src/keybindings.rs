@@ -0,0 +1,306 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io::ErrorKind;
+use minifb::{Key, KeyRepeat};
+use serde::Deserialize;
+use crate::input::WindowInput;
+
+// Everything the keyboard can do; each is bound to any number of keys
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Action {
+    // Orbit camera
+    OrbitLeft,
+    OrbitRight,
+    OrbitUp,
+    OrbitDown,
+    AimLeft,
+    AimRight,
+    AimUp,
+    AimDown,
+    ZoomIn,
+    ZoomOut,
+    // Fly camera
+    FlyForward,
+    FlyBack,
+    FlyLeft,
+    FlyRight,
+    FlyUp,
+    FlyDown,
+    LookLeft,
+    LookRight,
+    LookUp,
+    LookDown,
+    ToggleCameraMode,
+    NextPlanet,
+    Follow,
+    Warp,
+    SplitView,
+    MapView,
+    FovNarrower,
+    FovWider,
+    // Simulation time
+    Pause,
+    Faster,
+    Slower,
+    Reverse,
+    StepBack,
+    StepForward,
+    GoToTime,
+    Gravity,
+    Kick,
+    Superlaser,
+    // Ship
+    BoardShip,
+    ChaseCamera,
+    Thrust,
+    Brake,
+    YawLeft,
+    YawRight,
+    PitchUp,
+    PitchDown,
+    // Shaders by registry index
+    Shader0,
+    Shader1,
+    Shader2,
+    Shader3,
+    Shader4,
+    Shader5,
+    Shader6,
+    // Overlays and tools
+    ReloadScene,
+    Record,
+    Screenshot,
+    ObjectIds,
+    Labels,
+    MiniMap,
+    PictureInPicture,
+    ToneMapping,
+    Stats,
+    FrameRate,
+    Wireframe,
+    DebugView,
+    DebugUi,
+    Menu,
+    Quit,
+}
+
+// Name in the bindings file and default keys of every action
+const ACTIONS: &[(Action, &str, &[Key])] = &[
+    (Action::OrbitLeft, "orbit_left", &[Key::Left]),
+    (Action::OrbitRight, "orbit_right", &[Key::Right]),
+    (Action::OrbitUp, "orbit_up", &[Key::W]),
+    (Action::OrbitDown, "orbit_down", &[Key::S]),
+    (Action::AimLeft, "aim_left", &[Key::A]),
+    (Action::AimRight, "aim_right", &[Key::D]),
+    (Action::AimUp, "aim_up", &[Key::Q]),
+    (Action::AimDown, "aim_down", &[Key::E]),
+    (Action::ZoomIn, "zoom_in", &[Key::Up]),
+    (Action::ZoomOut, "zoom_out", &[Key::Down]),
+    (Action::FlyForward, "fly_forward", &[Key::W]),
+    (Action::FlyBack, "fly_back", &[Key::S]),
+    (Action::FlyLeft, "fly_left", &[Key::A]),
+    (Action::FlyRight, "fly_right", &[Key::D]),
+    (Action::FlyUp, "fly_up", &[Key::Q]),
+    (Action::FlyDown, "fly_down", &[Key::E]),
+    (Action::LookLeft, "look_left", &[Key::Left]),
+    (Action::LookRight, "look_right", &[Key::Right]),
+    (Action::LookUp, "look_up", &[Key::Up]),
+    (Action::LookDown, "look_down", &[Key::Down]),
+    (Action::ToggleCameraMode, "toggle_camera_mode", &[Key::Tab]),
+    (Action::NextPlanet, "next_planet", &[Key::N]),
+    (Action::Follow, "follow", &[Key::F]),
+    (Action::Warp, "warp", &[Key::J]),
+    (Action::SplitView, "split_view", &[Key::X]),
+    (Action::MapView, "map_view", &[Key::M]),
+    (Action::FovNarrower, "fov_narrower", &[Key::LeftBracket]),
+    (Action::FovWider, "fov_wider", &[Key::RightBracket]),
+    (Action::Pause, "pause", &[Key::Space]),
+    (Action::Faster, "faster", &[Key::Equal, Key::NumPadPlus]),
+    (Action::Slower, "slower", &[Key::Minus, Key::NumPadMinus]),
+    (Action::Reverse, "reverse", &[Key::B]),
+    (Action::StepBack, "step_back", &[Key::Comma]),
+    (Action::StepForward, "step_forward", &[Key::Period]),
+    (Action::GoToTime, "go_to_time", &[Key::G]),
+    (Action::Gravity, "gravity", &[Key::P]),
+    (Action::Kick, "kick", &[Key::K]),
+    (Action::Superlaser, "superlaser", &[Key::L]),
+    (Action::BoardShip, "board_ship", &[Key::V]),
+    (Action::ChaseCamera, "chase_camera", &[Key::C]),
+    (Action::Thrust, "thrust", &[Key::W]),
+    (Action::Brake, "brake", &[Key::S]),
+    (Action::YawLeft, "yaw_left", &[Key::A]),
+    (Action::YawRight, "yaw_right", &[Key::D]),
+    (Action::PitchUp, "pitch_up", &[Key::Up]),
+    (Action::PitchDown, "pitch_down", &[Key::Down]),
+    (Action::Shader0, "shader_0", &[Key::Key0]),
+    (Action::Shader1, "shader_1", &[Key::Key1]),
+    (Action::Shader2, "shader_2", &[Key::Key2]),
+    (Action::Shader3, "shader_3", &[Key::Key3]),
+    (Action::Shader4, "shader_4", &[Key::Key4]),
+    (Action::Shader5, "shader_5", &[Key::Key5]),
+    (Action::Shader6, "shader_6", &[Key::Key6]),
+    (Action::ReloadScene, "reload_scene", &[Key::F5]),
+    (Action::Record, "record", &[Key::R]),
+    (Action::Screenshot, "screenshot", &[Key::F12]),
+    (Action::ObjectIds, "object_ids", &[Key::I]),
+    (Action::Labels, "labels", &[Key::T]),
+    (Action::MiniMap, "minimap", &[Key::F3]),
+    (Action::PictureInPicture, "picture_in_picture", &[Key::F4]),
+    (Action::ToneMapping, "tone_mapping", &[Key::F6]),
+    (Action::Stats, "stats", &[Key::F1]),
+    (Action::FrameRate, "frame_rate", &[Key::F9]),
+    (Action::Wireframe, "wireframe", &[Key::F2]),
+    (Action::DebugView, "debug_view", &[Key::F8]),
+    (Action::DebugUi, "debug_ui", &[Key::F7]),
+    (Action::Menu, "menu", &[Key::Escape, Key::F10]),
+    // Quitting goes through the menu unless a key is bound here
+    (Action::Quit, "quit", &[]),
+];
+
+pub const SHADER_ACTIONS: [Action; 7] = [
+    Action::Shader0, Action::Shader1, Action::Shader2, Action::Shader3,
+    Action::Shader4, Action::Shader5, Action::Shader6,
+];
+
+// Names accepted in the bindings file; matched ignoring case
+const KEY_NAMES: &[(&str, Key)] = &[
+    ("0", Key::Key0), ("1", Key::Key1), ("2", Key::Key2), ("3", Key::Key3), ("4", Key::Key4),
+    ("5", Key::Key5), ("6", Key::Key6), ("7", Key::Key7), ("8", Key::Key8), ("9", Key::Key9),
+    ("A", Key::A), ("B", Key::B), ("C", Key::C), ("D", Key::D), ("E", Key::E), ("F", Key::F),
+    ("G", Key::G), ("H", Key::H), ("I", Key::I), ("J", Key::J), ("K", Key::K), ("L", Key::L),
+    ("M", Key::M), ("N", Key::N), ("O", Key::O), ("P", Key::P), ("Q", Key::Q), ("R", Key::R),
+    ("S", Key::S), ("T", Key::T), ("U", Key::U), ("V", Key::V), ("W", Key::W), ("X", Key::X),
+    ("Y", Key::Y), ("Z", Key::Z),
+    ("F1", Key::F1), ("F2", Key::F2), ("F3", Key::F3), ("F4", Key::F4), ("F5", Key::F5),
+    ("F6", Key::F6), ("F7", Key::F7), ("F8", Key::F8), ("F9", Key::F9), ("F10", Key::F10),
+    ("F11", Key::F11), ("F12", Key::F12),
+    ("Up", Key::Up), ("Down", Key::Down), ("Left", Key::Left), ("Right", Key::Right),
+    ("Space", Key::Space), ("Tab", Key::Tab), ("Enter", Key::Enter), ("Escape", Key::Escape),
+    ("Backspace", Key::Backspace), ("Delete", Key::Delete), ("Insert", Key::Insert),
+    ("Home", Key::Home), ("End", Key::End), ("PageUp", Key::PageUp), ("PageDown", Key::PageDown),
+    ("Comma", Key::Comma), ("Period", Key::Period), ("Minus", Key::Minus), ("Equal", Key::Equal),
+    ("Slash", Key::Slash), ("Backslash", Key::Backslash), ("Semicolon", Key::Semicolon),
+    ("Apostrophe", Key::Apostrophe), ("Backquote", Key::Backquote),
+    ("LeftBracket", Key::LeftBracket), ("RightBracket", Key::RightBracket),
+    ("LeftShift", Key::LeftShift), ("RightShift", Key::RightShift),
+    ("LeftCtrl", Key::LeftCtrl), ("RightCtrl", Key::RightCtrl),
+    ("LeftAlt", Key::LeftAlt), ("RightAlt", Key::RightAlt),
+    ("NumPad0", Key::NumPad0), ("NumPad1", Key::NumPad1), ("NumPad2", Key::NumPad2),
+    ("NumPad3", Key::NumPad3), ("NumPad4", Key::NumPad4), ("NumPad5", Key::NumPad5),
+    ("NumPad6", Key::NumPad6), ("NumPad7", Key::NumPad7), ("NumPad8", Key::NumPad8),
+    ("NumPad9", Key::NumPad9), ("NumPadPlus", Key::NumPadPlus), ("NumPadMinus", Key::NumPadMinus),
+    ("NumPadAsterisk", Key::NumPadAsterisk), ("NumPadSlash", Key::NumPadSlash),
+    ("NumPadDot", Key::NumPadDot), ("NumPadEnter", Key::NumPadEnter),
+];
+
+impl Action {
+    pub fn name(&self) -> &'static str {
+        ACTIONS.iter().find(|(action, _, _)| action == self).map_or("", |(_, name, _)| name)
+    }
+
+    fn named(name: &str) -> Option<Action> {
+        ACTIONS.iter().find(|(_, action_name, _)| *action_name == name).map(|(action, _, _)| *action)
+    }
+
+    // Held down, these keep firing at the keyboard's repeat rate
+    fn repeats(&self) -> bool {
+        matches!(self, Action::Faster | Action::Slower | Action::StepBack | Action::StepForward | Action::FovNarrower | Action::FovWider)
+    }
+}
+
+// Every action, in the order the bindings file and the help list them
+pub fn actions() -> impl Iterator<Item = Action> {
+    ACTIONS.iter().map(|(action, _, _)| *action)
+}
+
+pub fn key_name(key: Key) -> &'static str {
+    KEY_NAMES.iter().find(|(_, named)| *named == key).map_or("?", |(name, _)| name)
+}
+
+fn parse_key(name: &str) -> Option<Key> {
+    KEY_NAMES.iter().find(|(key_name, _)| key_name.eq_ignore_ascii_case(name)).map(|(_, key)| *key)
+}
+
+// A single key or a list of them; an empty list unbinds the action
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum KeyList {
+    One(String),
+    Many(Vec<String>),
+}
+
+#[derive(Deserialize)]
+struct BindingsFile {
+    #[serde(default)]
+    bindings: HashMap<String, KeyList>,
+}
+
+pub struct KeyBindings {
+    keys: HashMap<Action, Vec<Key>>,
+}
+
+impl KeyBindings {
+    // Actions the file leaves out keep their default keys, and a missing file means
+    // the defaults for everything
+    pub fn load(path: &str) -> Result<Self, String> {
+        let mut bindings = Self::default();
+        let contents = match fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(bindings),
+            Err(e) => return Err(format!("Failed to read {}: {}", path, e)),
+        };
+        let file: BindingsFile = toml::from_str(&contents)
+            .map_err(|e| format!("Failed to parse {}: {}", path, e))?;
+
+        for (name, keys) in file.bindings {
+            let action = Action::named(&name).ok_or_else(|| {
+                let known: Vec<&str> = ACTIONS.iter().map(|(_, name, _)| *name).collect();
+                format!("Unknown action \"{}\" in {} (expected one of: {})", name, path, known.join(", "))
+            })?;
+            let names = match keys {
+                KeyList::One(key) => vec![key],
+                KeyList::Many(keys) => keys,
+            };
+            let keys = names.iter()
+                .map(|key| parse_key(key).ok_or_else(|| format!("Unknown key \"{}\" for {} in {}", key, name, path)))
+                .collect::<Result<Vec<_>, _>>()?;
+            bindings.keys.insert(action, keys);
+        }
+        Ok(bindings)
+    }
+
+    pub fn keys(&self, action: Action) -> &[Key] {
+        self.keys.get(&action).map_or(&[], |keys| keys.as_slice())
+    }
+
+    // Keys joined for display, such as "Equal/NumPadPlus"
+    pub fn describe(&self, action: Action) -> String {
+        let keys = self.keys(action);
+        if keys.is_empty() {
+            return "-".to_string();
+        }
+        keys.iter().map(|key| key_name(*key)).collect::<Vec<_>>().join("/")
+    }
+
+    pub fn is_down(&self, input: &WindowInput, action: Action) -> bool {
+        self.keys(action).iter().any(|key| input.is_key_down(*key))
+    }
+
+    // Pressed this frame, or auto-repeating for the actions that repeat
+    pub fn pressed(&self, input: &WindowInput, action: Action) -> bool {
+        let repeat = if action.repeats() { KeyRepeat::Yes } else { KeyRepeat::No };
+        self.keys(action).iter().any(|key| input.is_key_pressed(*key, repeat))
+    }
+
+    // -1, 0 or 1 from a pair of opposing actions
+    pub fn axis(&self, input: &WindowInput, positive: Action, negative: Action) -> f32 {
+        (self.is_down(input, positive) as i32 - self.is_down(input, negative) as i32) as f32
+    }
+}
+
+impl Default for KeyBindings {
+    fn default() -> Self {
+        let keys = ACTIONS.iter().map(|(action, _, keys)| (*action, keys.to_vec())).collect();
+        KeyBindings { keys }
+    }
+}
@@ -0,0 +1,120 @@
+use nalgebra_glm::{Vec3, Mat4};
+use fastnoise_lite::{FastNoiseLite, NoiseType};
+use rand::SeedableRng;
+use rand::rngs::StdRng;
+use crate::pbr::Material;
+use crate::color::Color;
+
+pub mod framebuffer;
+pub mod triangle;
+pub mod line;
+pub mod vertex;
+pub mod mesh;
+pub mod obj;
+pub mod texture;
+pub mod material;
+pub mod gltf_loader;
+pub mod color;
+pub mod noise;
+pub mod fragment;
+pub mod shaders;
+pub mod watcher;
+pub mod pbr;
+pub mod camera;
+pub mod input;
+pub mod keybindings;
+pub mod clock;
+pub mod orbit;
+pub mod gravity;
+pub mod ship;
+pub mod collision;
+pub mod frustum;
+pub mod scene;
+pub mod asteroids;
+pub mod starfield;
+pub mod skybox;
+pub mod nebula;
+pub mod billboard;
+pub mod particles;
+pub mod effects;
+pub mod recorder;
+pub mod bench;
+pub mod profiler;
+pub mod renderer;
+pub mod backend;
+#[cfg(feature = "gpu")]
+pub mod gpu;
+pub mod text;
+pub mod stats;
+pub mod pacer;
+pub mod hud;
+pub mod menu;
+pub mod debug_ui;
+pub mod minimap;
+pub mod pip;
+
+pub struct Uniforms<'a> {
+    pub model_matrix: Mat4,
+    pub view_matrix: Mat4,
+    pub projection_matrix: Mat4,
+    pub viewport_matrix: Mat4,
+    pub time: f32,
+    pub noise: &'a FastNoiseLite,
+    // Every light reaching the object; their contributions are summed
+    pub lights: &'a [Light],
+    pub camera_position: Vec3,
+    // Base surface of the object being drawn, for shaders built on pbr::shade
+    pub material: Material,
+}
+
+// Point light in world space; intensity 1.0 fully lights a surface facing it
+#[derive(Debug, Clone, Copy)]
+pub struct Light {
+    pub position: Vec3,
+    pub color: Color,
+    pub intensity: f32,
+}
+
+impl Light {
+    // White light at `position`, for bodies lit from the viewer's side
+    pub fn white(position: Vec3) -> Self {
+        Light { position, color: Color::new(255, 255, 255), intensity: 1.0 }
+    }
+
+    // Unit vector from a world-space point towards the light
+    pub fn direction(&self, position: &Vec3) -> Vec3 {
+        (self.position - position).try_normalize(f32::EPSILON).unwrap_or(Vec3::z())
+    }
+
+    // Color times intensity, so shaders can scale it by their own falloff
+    pub fn radiance(&self) -> Color {
+        self.color * self.intensity
+    }
+}
+
+// Splits one run-wide seed into independent seeds, one per `salt`, so every consumer
+// doesn't draw the same sequence (splitmix64's finalizer)
+pub fn derive_seed(seed: u64, salt: u64) -> u64 {
+    let mut z = seed ^ salt.wrapping_mul(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+// Reproducible with a seed, different every run without one
+pub fn create_rng(seed: Option<u64>, salt: u64) -> StdRng {
+    match seed {
+        Some(seed) => StdRng::seed_from_u64(derive_seed(seed, salt)),
+        None => StdRng::from_entropy(),
+    }
+}
+
+pub fn create_noise() -> FastNoiseLite {
+    create_cloud_noise()
+}
+
+fn create_cloud_noise() -> FastNoiseLite {
+    let mut noise = FastNoiseLite::with_seed(1337);
+    noise.set_noise_type(Some(NoiseType::OpenSimplex2));
+    noise
+}
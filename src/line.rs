@@ -1,13 +1,7 @@
-use crate::fragment::Fragment;
-use crate::vertex::Vertex;
-use crate::color::Color;
-
-pub fn line(a: &Vertex, b: &Vertex) -> Vec<Fragment> {
-    let mut fragments = Vec::new();
-
-    let start = a.transformed_position;
-    let end = b.transformed_position;
+use nalgebra_glm::Vec3;
 
+// Bresenham between two screen-space points; depth is interpolated along the way
+pub fn line(start: Vec3, end: Vec3, mut plot: impl FnMut(i32, i32, f32)) {
     let mut x0 = start.x as i32;
     let mut y0 = start.y as i32;
     let x1 = end.x as i32;
@@ -20,10 +14,12 @@ pub fn line(a: &Vertex, b: &Vertex) -> Vec<Fragment> {
     let sy = if y0 < y1 { 1 } else { -1 };
 
     let mut err = if dx > dy { dx / 2 } else { -dy / 2 };
+    let steps = dx.max(dy).max(1) as f32;
+    let mut step = 0;
 
     loop {
-        let z = start.z + (end.z - start.z) * (x0 - start.x as i32) as f32 / (end.x - start.x) as f32;
-        fragments.push(Fragment::new(x0 as f32, y0 as f32, Color::new(255, 255, 255), z));
+        let z = start.z + (end.z - start.z) * step as f32 / steps;
+        plot(x0, y0, z);
 
         if x0 == x1 && y0 == y1 { break; }
 
@@ -36,7 +32,6 @@ pub fn line(a: &Vertex, b: &Vertex) -> Vec<Fragment> {
             err += dx;
             y0 += sy;
         }
+        step += 1;
     }
-
-    fragments
-}
\ No newline at end of file
+}
@@ -1,291 +1,1110 @@
-use nalgebra_glm::{Vec3, Mat4, look_at, perspective};
-use minifb::{Key, Window, WindowOptions};
-use std::time::Duration;
+use nalgebra_glm::{Vec2, Vec3};
+use minifb::{Key, Window, WindowOptions, MouseButton, MouseMode};
+use clap::{CommandFactory, Parser, ValueEnum};
+use clap::error::ErrorKind;
+use std::sync::Mutex;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 use std::f32::consts::PI;
 
-mod framebuffer;
-mod triangle;
-mod vertex;
-mod obj;
-mod color;
-mod fragment;
-mod shaders;
-mod camera;
-
-use framebuffer::Framebuffer;
-use vertex::Vertex;
-use obj::Obj;
-use camera::Camera;
-use triangle::triangle;
-use shaders::{vertex_shader};
-use fastnoise_lite::{FastNoiseLite, NoiseType};
-use crate::shaders::tatooine_shader;
-use crate::shaders::kamino_shader;
-use crate::shaders::sol_shader;
-use crate::shaders::hoth_shader;
-use crate::shaders::death_star_shader;
-use crate::fragment::Fragment;
-use crate::color::Color;
-
-
-pub struct Uniforms {
-    model_matrix: Mat4,
-    view_matrix: Mat4,
-    projection_matrix: Mat4,
-    viewport_matrix: Mat4,
-    time: u32,
-    noise: FastNoiseLite
-}
+use proyecto3graficas::framebuffer::{Antialiasing, Filter, Framebuffer, Presentation, Rect};
+use proyecto3graficas::obj::Obj;
+use proyecto3graficas::gltf_loader;
+use proyecto3graficas::mesh;
+use proyecto3graficas::camera::{Camera, CameraMode, MAX_FOV, MIN_FOV};
+use proyecto3graficas::shaders::ShaderRegistry;
+use proyecto3graficas::watcher::FileWatcher;
+use proyecto3graficas::scene::Scene;
+use proyecto3graficas::recorder::Recorder;
+use proyecto3graficas::bench::{path_camera, BenchReport, BENCH_SEED, BENCH_TIMESTEP};
+use proyecto3graficas::profiler::TraceWriter;
+use proyecto3graficas::renderer::{DebugView, Renderer};
+use proyecto3graficas::backend::{create_backend, BackendKind, RenderBackend};
+use proyecto3graficas::stats::FrameStats;
+use proyecto3graficas::pacer::FramePacer;
+use proyecto3graficas::clock::{SimulationClock, STEP};
+use proyecto3graficas::hud::{Hud, Prompt};
+use proyecto3graficas::menu::{Menu, Setting};
+use proyecto3graficas::debug_ui::{DebugUi, Panel};
+use proyecto3graficas::minimap::MiniMap;
+use proyecto3graficas::pip::PictureInPicture;
+use proyecto3graficas::effects::Effects;
+use proyecto3graficas::ship::{Ship, ShipControls, SHIP_MODEL, CRASH_SPEED};
+use proyecto3graficas::collision::Colliders;
+use proyecto3graficas::color::Color;
+use proyecto3graficas::input::{CameraControls, Gamepads, WindowInput};
+use proyecto3graficas::keybindings::{Action, KeyBindings, SHADER_ACTIONS};
 
-fn create_noise() -> FastNoiseLite {
-    create_cloud_noise()
-}
+const BINDINGS_PATH: &str = "config/keybindings.toml";
+const SHADER_PARAMS_PATH: &str = "config/shaders.toml";
+// Fraction of a body's speed added by a kick in gravity mode
+const KICK_STRENGTH: f32 = 0.15;
+// How far the split view's second camera sits from the planet it follows
+const SPLIT_FOLLOW_DISTANCE: f32 = 3.0;
+// Degrees per press of the FOV keys or the menu's arrows
+const FOV_STEP: f32 = 5.0;
+// Longest the main thread waits for a frame before pumping window events anyway
+const EVENT_INTERVAL: Duration = Duration::from_millis(4);
 
-fn create_cloud_noise() -> FastNoiseLite {
-    let mut noise = FastNoiseLite::with_seed(1337);
-    noise.set_noise_type(Some(NoiseType::OpenSimplex2));
-    noise
+// Startup options; every flag has a default, so plain `cargo run` opens the usual window
+#[derive(Parser)]
+#[command(about = "Software-rendered solar system")]
+struct Args {
+    #[arg(long, default_value_t = 800, help = "Window (or headless frame) width in pixels")]
+    width: usize,
+    #[arg(long, default_value_t = 600, help = "Window (or headless frame) height in pixels")]
+    height: usize,
+    #[arg(long, default_value = "scenes/solar_system.toml", help = "Scene file, also used by F5")]
+    scene: String,
+    #[arg(long, help = "Mesh for the bodies instead of the built-in sphere (.obj, .gltf or .glb)")]
+    model: Option<String>,
+    #[arg(long, help = "Frame rate cap, 0 for unlimited [default: 60]")]
+    fps_cap: Option<u32>,
+    #[arg(long, help = "Planet the camera starts at, by name or index")]
+    start_planet: Option<String>,
+    #[arg(long, help = "Render frames to PNG files instead of opening a window")]
+    headless: bool,
+    #[arg(long, default_value_t = 300, help = "Frames to render in headless or bench mode")]
+    frames: u32,
+    #[arg(long, default_value = "frames", help = "Output directory in headless mode")]
+    out: String,
+    #[arg(long, help = "Time a scripted camera path without a window and write per-stage timings")]
+    bench: bool,
+    #[arg(long, default_value = "bench.csv", help = "CSV file for the bench summary")]
+    bench_out: String,
+    #[arg(long, help = "Seed for every random choice (stars, noise, asteroids, particles), making runs reproducible")]
+    seed: Option<u64>,
+    #[arg(long, help = "Write per-object stage timings of every frame to a chrome://tracing JSON file")]
+    trace: Option<String>,
+    // Rendering below the window resolution and upscaling trades sharpness for frame rate
+    #[arg(long, default_value_t = 1.0, help = "Internal resolution as a fraction of the window, 0.1 to 1")]
+    render_scale: f32,
+    #[arg(long, value_enum, default_value_t = FilterArg::Bilinear, help = "Filter used to upscale the render")]
+    filter: FilterArg,
+    #[arg(long, value_enum, default_value_t = BackendArg::Software, help = "What draws the scene; gpu needs a build with --features gpu")]
+    backend: BackendArg,
 }
 
-fn create_model_matrix(translation: Vec3, scale: f32, rotation: Vec3) -> Mat4 {
-    let (sin_x, cos_x) = rotation.x.sin_cos();
-    let (sin_y, cos_y) = rotation.y.sin_cos();
-    let (sin_z, cos_z) = rotation.z.sin_cos();
-
-    let rotation_matrix_x = Mat4::new(
-        1.0,  0.0,    0.0,   0.0,
-        0.0,  cos_x, -sin_x, 0.0,
-        0.0,  sin_x,  cos_x, 0.0,
-        0.0,  0.0,    0.0,   1.0,
-    );
+#[derive(Clone, Copy, ValueEnum)]
+enum FilterArg {
+    Nearest,
+    Bilinear,
+}
 
-    let rotation_matrix_y = Mat4::new(
-        cos_y,  0.0,  sin_y, 0.0,
-        0.0,    1.0,  0.0,   0.0,
-        -sin_y, 0.0,  cos_y, 0.0,
-        0.0,    0.0,  0.0,   1.0,
-    );
+impl FilterArg {
+    fn filter(self) -> Filter {
+        match self {
+            FilterArg::Nearest => Filter::Nearest,
+            FilterArg::Bilinear => Filter::Bilinear,
+        }
+    }
+}
 
-    let rotation_matrix_z = Mat4::new(
-        cos_z, -sin_z, 0.0, 0.0,
-        sin_z,  cos_z, 0.0, 0.0,
-        0.0,    0.0,  1.0, 0.0,
-        0.0,    0.0,  0.0, 1.0,
-    );
+#[derive(Clone, Copy, ValueEnum)]
+enum BackendArg {
+    Software,
+    Gpu,
+}
 
-    let rotation_matrix = rotation_matrix_z * rotation_matrix_y * rotation_matrix_x;
+impl BackendArg {
+    fn kind(self) -> BackendKind {
+        match self {
+            BackendArg::Software => BackendKind::Software,
+            BackendArg::Gpu => BackendKind::Gpu,
+        }
+    }
+}
 
-    let transform_matrix = Mat4::new(
-        scale, 0.0,   0.0,   translation.x,
-        0.0,   scale, 0.0,   translation.y,
-        0.0,   0.0,   scale, translation.z,
-        0.0,   0.0,   0.0,   1.0,
-    );
+fn main() {
+    let args = Args::parse();
+    if args.bench {
+        run_bench(&args);
+    } else if args.headless {
+        run_headless(&args);
+    } else {
+        run_window(&args);
+    }
+}
 
-    transform_matrix * rotation_matrix
+// --start-planet resolved against the loaded scene, exiting with a usage error if unknown
+fn start_planet(args: &Args, scene: &Scene) -> Option<usize> {
+    let planet = args.start_planet.as_deref()?;
+    let by_index = planet.parse::<usize>().ok().filter(|index| *index < scene.objects.len());
+    let by_name = || scene.objects.iter().position(|object| object.name.eq_ignore_ascii_case(planet));
+    match by_index.or_else(by_name) {
+        Some(index) => Some(index),
+        None => {
+            let names: Vec<&str> = scene.objects.iter().map(|object| object.name.as_str()).collect();
+            let message = format!("unknown planet \"{}\" (expected one of: {})", planet, names.join(", "));
+            Args::command().error(ErrorKind::InvalidValue, message).exit()
+        }
+    }
 }
 
+// Looks at the start planet from where the next-planet key would put the camera, or at
+// the whole system from above
+fn create_camera(target: Option<Vec3>) -> Camera {
+    let (eye, center) = match target {
+        Some(position) => (position + Vec3::new(0.0, 0.0, 5.0), position),
+        None => (Vec3::new(0.0, 0.0, 10.0), Vec3::new(0.0, 0.0, 0.0)),
+    };
+    Camera::new(eye, center, Vec3::new(0.0, 1.0, 0.0))
+}
 
-fn create_view_matrix(eye: Vec3, center: Vec3, up: Vec3) -> Mat4 {
-    look_at(&eye, &center, &up)
+fn create_renderer(model: Option<&str>, width: usize, height: usize, seed: Option<u64>) -> Renderer {
+    let mut renderer = match model {
+        Some(model) if model.ends_with(".glb") || model.ends_with(".gltf") => {
+            let gltf = gltf_loader::load(model).expect("Failed to load glTF");
+            let mut renderer = Renderer::new(width, height, gltf.mesh);
+            renderer.set_materials(gltf.materials);
+            renderer
+        }
+        Some(model) => {
+            let obj = Obj::load(model).expect("Failed to load obj");
+            let mut renderer = Renderer::new(width, height, obj.get_indexed_mesh());
+            renderer.set_materials(obj.materials().to_vec());
+            renderer
+        }
+        None => Renderer::with_lods(width, height, mesh::sphere_lods(0.5)),
+    };
+    if let Some(seed) = seed {
+        renderer.set_seed(seed);
+    }
+    renderer
 }
 
-fn create_perspective_matrix(window_width: f32, window_height: f32) -> Mat4 {
-    let fov = 45.0 * PI / 180.0;
-    let aspect_ratio = window_width / window_height;
-    let near = 0.1;
-    let far = 1000.0;
+fn run_headless(args: &Args) {
+    let shaders = load_shaders().expect("Failed to load shader parameters");
+    let scene = Scene::load(&args.scene, &shaders, args.seed).expect("Failed to load scene");
+    let mut renderer = create_renderer(args.model.as_deref(), args.width, args.height, args.seed);
+    let mut backend = open_backend(args);
+    let camera = create_camera(start_planet(args, &scene).map(|index| scene.position(index, 0.0)));
+    let mut effects = Effects::new(args.seed);
 
-    perspective(fov, aspect_ratio, near, far)
-}
+    let mut recorder = Recorder::new(60);
+    recorder.start(&args.out).expect("Failed to create output directory");
+    let mut trace = create_trace(args);
 
-fn create_viewport_matrix(width: f32, height: f32) -> Mat4 {
-    Mat4::new(
-        width / 2.0, 0.0, 0.0, width / 2.0,
-        0.0, -height / 2.0, 0.0, height / 2.0,
-        0.0, 0.0, 1.0, 0.0,
-        0.0, 0.0, 0.0, 1.0
-    )
-}
-fn render(
-    framebuffer: &mut Framebuffer,
-    uniforms: &Uniforms,
-    vertex_array: &[Vertex],
-    shader_fn: &dyn Fn(&Fragment, &Uniforms) -> Color,
-) {
-    // Vertex Shader
-    let mut transformed_vertices = Vec::with_capacity(vertex_array.len());
-    for vertex in vertex_array {
-        let transformed = vertex_shader(vertex, uniforms);
-        transformed_vertices.push(transformed);
+    for frame in 1..=args.frames {
+        let frame_start = Instant::now();
+        let time = frame as f32 * recorder.timestep;
+        effects.update(&scene, time, recorder.timestep);
+        renderer.reset_stats();
+        backend.render_frame(&mut renderer, &scene, &shaders, &camera, time);
+        renderer.draw_effects(&effects, &scene, &camera, time);
+        renderer.framebuffer.resolve();
+        trace_frame(&mut trace, &renderer, frame, frame_start);
+        if let Err(error) = recorder.capture(&renderer.framebuffer) {
+            eprintln!("{}", error);
+            return;
+        }
+    }
+    if let Some((directory, frames)) = recorder.stop() {
+        println!("Recorded {} frames to {}", frames, directory);
     }
+}
 
-    // Primitive Assembly
-    let mut triangles = Vec::new();
-    for i in (0..transformed_vertices.len()).step_by(3) {
-        if i + 2 < transformed_vertices.len() {
-            triangles.push([
-                transformed_vertices[i].clone(),
-                transformed_vertices[i + 1].clone(),
-                transformed_vertices[i + 2].clone(),
-            ]);
+// Same frames on every run: a fixed camera path, seed and simulation step, so timings from
+// different builds or machines compare directly
+fn run_bench(args: &Args) {
+    let shaders = load_shaders().expect("Failed to load shader parameters");
+    let seed = Some(args.seed.unwrap_or(BENCH_SEED));
+    let scene = Scene::load(&args.scene, &shaders, seed).expect("Failed to load scene");
+    let render_scale = args.render_scale.clamp(0.1, 1.0);
+    let (width, height) = (render_size(args.width, render_scale), render_size(args.height, render_scale));
+    let mut renderer = create_renderer(args.model.as_deref(), width, height, seed);
+    let mut backend = open_backend(args);
+    let mut effects = Effects::new(seed);
+    let mut window_buffer = Vec::new();
+
+    let mut report = BenchReport::new();
+    let mut trace = create_trace(args);
+    for frame in 0..args.frames {
+        let frame_start = Instant::now();
+        let time = frame as f32 * BENCH_TIMESTEP;
+        let camera = path_camera(frame as f32 / args.frames as f32);
+        effects.update(&scene, time, BENCH_TIMESTEP);
+        renderer.reset_stats();
+        backend.render_frame(&mut renderer, &scene, &shaders, &camera, time);
+        renderer.draw_effects(&effects, &scene, &camera, time);
+
+        // What the window would do with the frame, minus handing it to the OS
+        let blit_start = Instant::now();
+        renderer.framebuffer.resolve();
+        if (width, height) != (args.width, args.height) {
+            renderer.framebuffer.upscale(&mut window_buffer, args.width, args.height, args.filter.filter());
         }
+        report.record(&renderer.stats, blit_start.elapsed(), frame_start.elapsed());
+        trace_frame(&mut trace, &renderer, frame, frame_start);
     }
 
-    // Rasterization
-    let mut fragments = Vec::new();
-    for tri in &triangles {
-        fragments.extend(triangle(&tri[0], &tri[1], &tri[2]));
+    println!("Backend: {}", backend.name());
+    report.print();
+    match report.write_csv(&args.bench_out) {
+        Ok(()) => println!("Wrote {}", args.bench_out),
+        Err(error) => eprintln!("{}", error),
     }
+}
 
-    // Fragment Processing
-    for fragment in fragments {
-        let x = fragment.position.x as usize;
-        let y = fragment.position.y as usize;
+// --trace opened up front, so a bad path fails before any rendering
+fn create_trace(args: &Args) -> Option<TraceWriter> {
+    let path = args.trace.as_deref()?;
+    Some(TraceWriter::create(path).unwrap_or_else(|error| {
+        eprintln!("{}", error);
+        std::process::exit(1);
+    }))
+}
+
+// --backend started up front, so a missing GPU fails before any rendering
+fn open_backend(args: &Args) -> Box<dyn RenderBackend> {
+    create_backend(args.backend.kind()).unwrap_or_else(|error| {
+        eprintln!("{}", error);
+        std::process::exit(1);
+    })
+}
 
-        if x < framebuffer.width && y < framebuffer.height {
-            let shaded_color = shader_fn(&fragment, uniforms);
-            let color = shaded_color.to_hex();
-            framebuffer.set_current_color(color);
-            framebuffer.point(x, y, fragment.depth);
+// A failed write stops tracing without stopping the run
+fn trace_frame(trace: &mut Option<TraceWriter>, renderer: &Renderer, frame: u32, start: Instant) {
+    if let Some(writer) = trace.as_mut() {
+        if let Err(error) = writer.frame(frame, start, start.elapsed(), &renderer.profile) {
+            eprintln!("{}", error);
+            *trace = None;
         }
     }
 }
 
-fn calculate_orbit_position(time: f32, orbit_radius: f32, angular_velocity: f32) -> Vec3 {
-    let x = orbit_radius * (time * angular_velocity).cos();
-    let z = orbit_radius * (time * angular_velocity).sin();
-    Vec3::new(x, 0.0, z)
+// Built-in shaders with config/shaders.toml applied over their defaults, when it exists
+fn load_shaders() -> Result<ShaderRegistry, String> {
+    let mut shaders = ShaderRegistry::new();
+    if std::path::Path::new(SHADER_PARAMS_PATH).exists() {
+        shaders.load_params(SHADER_PARAMS_PATH)?;
+    }
+    Ok(shaders)
 }
 
-fn main() {
-    let window_width = 800;
-    let window_height = 600;
-    let framebuffer_width = 800;
-    let framebuffer_height = 600;
-    let frame_delay = Duration::from_millis(16);
+fn render_size(window_size: usize, render_scale: f32) -> usize {
+    ((window_size as f32 * render_scale).round() as usize).max(1)
+}
 
-    let mut framebuffer = Framebuffer::new(framebuffer_width, framebuffer_height);
+// A finished frame at window size, passed from the render thread to the main thread
+struct Frame {
+    pixels: Vec<u32>,
+    width: usize,
+    height: usize,
+}
+
+// The main thread only pumps window events and presents the latest finished frame, so the
+// window stays responsive however long the render thread takes over one
+fn run_window(args: &Args) {
     let mut window = Window::new(
         "Proyecto 3",
-        window_width,
-        window_height,
-        WindowOptions::default(),
+        args.width,
+        args.height,
+        WindowOptions {
+            resize: true,
+            ..WindowOptions::default()
+        },
     ).unwrap();
 
     window.set_position(500, 500);
+    // FramePacer owns frame timing, so minifb's own update-rate limit is turned off
+    window.set_target_fps(0);
+
+    let input = Mutex::new(WindowInput::default());
+    input.lock().unwrap().gather(&window);
+    let (frame_sender, frames) = mpsc::channel::<Frame>();
+    // Double buffering: the render thread fills one buffer while the window shows the
+    // other, and gets it back once the next frame replaces it
+    let (free_sender, free_buffers) = mpsc::channel::<Vec<u32>>();
+    free_sender.send(Vec::new()).unwrap();
+
+    thread::scope(|scope| {
+        scope.spawn(|| render_loop(args, &input, frame_sender, free_buffers));
+
+        let mut front: Option<Frame> = None;
+        loop {
+            match frames.recv_timeout(EVENT_INTERVAL) {
+                Ok(frame) => {
+                    window.update_with_buffer(&frame.pixels, frame.width, frame.height).unwrap();
+                    if let Some(previous) = front.replace(frame) {
+                        let _ = free_sender.send(previous.pixels);
+                    }
+                }
+                Err(RecvTimeoutError::Timeout) => window.update(),
+                // The render thread quit, or the window closed and it followed
+                Err(RecvTimeoutError::Disconnected) => break,
+            }
+            input.lock().unwrap().gather(&window);
+        }
+    });
+}
+
+fn render_loop(args: &Args, shared_input: &Mutex<WindowInput>, frames: Sender<Frame>, free_buffers: Receiver<Vec<u32>>) {
+    let (window_width, window_height) = (args.width, args.height);
+    let mut render_scale = args.render_scale.clamp(0.1, 1.0);
+    let filter = args.filter.filter();
 
-    let mut camera = Camera::new(
-        Vec3::new(0.0, 0.0, 10.0),
-        Vec3::new(0.0, 0.0, 0.0),
-        Vec3::new(0.0, 1.0, 0.0),
+    let mut renderer = create_renderer(
+        args.model.as_deref(),
+        render_size(window_width, render_scale),
+        render_size(window_height, render_scale),
+        args.seed,
     );
+    let mut backend = open_backend(args);
+    let mut back_buffer = Vec::new();
 
-    let obj = Obj::load("assets/models/sphere-1.obj").expect("Failed to load obj");
-    let vertex_arrays = obj.get_vertex_array();
-    let mut time = 0;
+    let mut clock = SimulationClock::new();
+    let mut last_frame = Instant::now();
+    let mut trace = create_trace(args);
+    let mut frame_index = 0;
 
-    let solar_objects: Vec<(Box<dyn Fn(&Fragment, &Uniforms) -> Color>, Vec3, f32, f32)> = vec![
-        (Box::new(sol_shader), Vec3::new(0.0, 0.0, 0.0), 1.5, 0.0),  
-        (Box::new(tatooine_shader), Vec3::new(3.0, 0.0, 0.0), 0.5, 0.01),  
-        (Box::new(hoth_shader), Vec3::new(5.0, 0.0, 0.0), 0.4, 0.012),   
-        (Box::new(kamino_shader), Vec3::new(0.0, 6.0, 0.0), 0.6, 0.014), 
-        (Box::new(death_star_shader), Vec3::new(0.0, -4.0, 0.0), 0.7, 0.016), 
-    ];
+    // Bad parameters at startup fall back to the built-in ones; later saves get another try
+    let mut shaders = load_shaders().unwrap_or_else(|error| {
+        eprintln!("{}", error);
+        ShaderRegistry::new()
+    });
+    let mut shader_watcher = FileWatcher::new(SHADER_PARAMS_PATH);
+    let mut scene = Scene::load(&args.scene, &shaders, args.seed).expect("Failed to load scene");
+    let mut effects = Effects::new(args.seed);
 
-    let mut current_planet_index = 0; 
+    let start = start_planet(args, &scene);
+    let mut camera = create_camera(start.map(|index| scene.position(index, 0.0)));
+    let mut current_planet_index = start.unwrap_or(0);
+    let mut recorder = Recorder::new(60);
+    let mut frame_stats = FrameStats::new();
+    let mut pacer = match args.fps_cap {
+        Some(0) => FramePacer::with_target(None),
+        Some(fps) => FramePacer::with_target(Some(fps)),
+        None => FramePacer::new(),
+    };
+    let mut hud = Hud::new();
+    let mut menu = Menu::new();
+    let mut debug_ui = DebugUi::new();
+    let mut antialiasing = Antialiasing::Off;
+    let mut minimap = MiniMap::new();
+    let mut pip = PictureInPicture::new();
+    let mut show_labels = true;
+    let mut right_was_down = false;
+    let mut time_prompt: Option<Prompt> = None;
+    let mut ship: Option<Ship> = None;
+    let mut piloting = false;
+    let mut chase_camera = true;
+    let mut colliders = Colliders::new();
+    let mut predicted_path = Vec::new();
+    let mut split_camera: Option<Camera> = None;
+    let mut gamepads = Gamepads::new();
+    // A broken bindings file shouldn't lock the user out of the controls
+    let bindings = KeyBindings::load(BINDINGS_PATH).unwrap_or_else(|error| {
+        eprintln!("{} (using default keys)", error);
+        KeyBindings::default()
+    });
 
-    while window.is_open() && !window.is_key_down(Key::Escape) {
-        if window.is_key_pressed(Key::N, minifb::KeyRepeat::No) {
-            current_planet_index = (current_planet_index + 1) % solar_objects.len(); 
-            camera.move_to_next_planet(&solar_objects, current_planet_index);
+    loop {
+        let input = shared_input.lock().unwrap().take();
+        // The quit key is ignored while the time prompt is taking keys
+        if !input.is_open() || (time_prompt.is_none() && bindings.pressed(&input, Action::Quit)) {
+            return;
         }
-    
-        handle_input(&window, &mut camera);
-        framebuffer.clear();
-        framebuffer.set_background_color(0x000000); 
+        let time = clock.time;
 
-        (&mut framebuffer).draw_stars(15); 
-        time += 1;
-        
-    
-        let view_matrix = create_view_matrix(camera.eye, camera.center, camera.up);
-        let projection_matrix = create_perspective_matrix(window_width as f32, window_height as f32);
-        let viewport_matrix = create_viewport_matrix(framebuffer_width as f32, framebuffer_height as f32);
+        // While the prompt is open, keys that type into it don't reach the other controls
+        let typing = time_prompt.is_some();
+        if let Some(prompt) = time_prompt.as_mut() {
+            let mut close = false;
+            for key in input.get_keys_pressed(minifb::KeyRepeat::Yes) {
+                match key {
+                    Key::Enter | Key::NumPadEnter => {
+                        match prompt.text.parse::<f32>() {
+                            Ok(target) => {
+                                clock.jump_to(target);
+                                hud.show(&format!("t={:.1}", target));
+                            }
+                            Err(_) => hud.show("Invalid time"),
+                        }
+                        close = true;
+                    }
+                    Key::Escape => close = true,
+                    Key::Backspace => prompt.pop(),
+                    key => {
+                        if let Some(c) = prompt_char(key) {
+                            prompt.push(c);
+                        }
+                    }
+                }
+            }
+            if close {
+                time_prompt = None;
+            }
+        }
+
+        if !typing && bindings.pressed(&input, Action::Menu) {
+            menu.toggle();
+        }
+        // The arrow keys belong to the menu while it is open
+        if menu.visible {
+            for key in input.get_keys_pressed(minifb::KeyRepeat::Yes) {
+                let step = match key {
+                    Key::Up => {
+                        menu.move_selection(-1);
+                        continue;
+                    }
+                    Key::Down => {
+                        menu.move_selection(1);
+                        continue;
+                    }
+                    Key::Left => -1,
+                    Key::Right | Key::Enter => 1,
+                    _ => continue,
+                };
+                match menu.selected() {
+                    Setting::RenderScale => render_scale = (render_scale + step as f32 * 0.25).clamp(0.25, 1.0),
+                    Setting::TimeScale if step > 0 => clock.faster(),
+                    Setting::TimeScale => clock.slower(),
+                    Setting::Antialiasing => antialiasing = antialiasing.next(),
+                    Setting::FieldOfView => camera.set_fov(camera.base_fov + step as f32 * FOV_STEP),
+                    // The clip planes step by octaves, the scale depth precision follows
+                    Setting::NearPlane => camera.set_clip_planes(camera.near * 2f32.powi(step as i32), camera.far),
+                    Setting::FarPlane => camera.set_clip_planes(camera.near, camera.far * 2f32.powi(step as i32)),
+                    Setting::Shader => {
+                        let names: Vec<&str> = shaders.names().collect();
+                        let object = &mut scene.objects[current_planet_index];
+                        let current = names.iter().position(|name| *name == object.shader).unwrap_or(0);
+                        object.shader = names[(current as isize + step).rem_euclid(names.len() as isize) as usize].to_string();
+                    }
+                    Setting::Quit if key == Key::Enter => return,
+                    Setting::Quit => {}
+                }
+            }
+        }
+        // Rendering at a multiple of the render scale supersamples
+        let internal_scale = render_scale * antialiasing.factor();
+
+        if shader_watcher.changed() {
+            match load_shaders() {
+                Ok(reloaded) => {
+                    shaders = reloaded;
+                    hud.show("Shader parameters reloaded");
+                }
+                Err(error) => {
+                    eprintln!("{}", error);
+                    hud.show("Shader parameters failed to load");
+                }
+            }
+        }
+
+        if bindings.pressed(&input, Action::ReloadScene) {
+            match Scene::load(&args.scene, &shaders, args.seed) {
+                Ok(reloaded) => {
+                    scene = reloaded;
+                    current_planet_index = current_planet_index.min(scene.objects.len() - 1);
+                    camera.follow_target = camera.follow_target.map(|_| current_planet_index);
+                    hud.show("Scene reloaded");
+                }
+                Err(error) => {
+                    eprintln!("{}", error);
+                    hud.show("Scene reload failed");
+                }
+            }
+        }
+
+        // Keyboard and gamepads drive the camera through the same controls
+        let gamepad_controls = gamepads.poll();
+        let controls = if menu.visible {
+            CameraControls::default()
+        } else {
+            camera_controls(&input, &bindings).combine(&gamepad_controls)
+        };
+
+        if controls.next_planet && !camera.map_view {
+            current_planet_index = (current_planet_index + 1) % scene.objects.len(); 
+            if camera.follow_target.is_some() {
+                camera.follow_target = Some(current_planet_index);
+            } else {
+                camera.move_to_next_planet(scene.position(current_planet_index, time));
+            }
+        }
+        // Right click selects the planet under the cursor
+        let right_down = input.get_mouse_down(MouseButton::Right);
+        if right_down && !right_was_down && !camera.map_view && !debug_ui.wants_mouse() {
+            // The id buffer is exact for visible pixels, the ray test catches thin silhouettes
+            let picked = input.get_mouse_pos(MouseMode::Discard).and_then(|(x, y)| {
+                let (x, y) = (x * internal_scale, y * internal_scale);
+                // In split view the click goes through the camera of the half it landed in
+                let (view_camera, view) = match &split_camera {
+                    Some(second) => {
+                        let (left, right) = split_viewports(&renderer.framebuffer);
+                        if (x as usize) < right.x { (second, left) } else { (&camera, right) }
+                    }
+                    None => (&camera, renderer.framebuffer.viewport()),
+                };
+                renderer.framebuffer.set_viewport(view.x, view.y, view.width, view.height);
+                let picked = renderer.framebuffer.object_at(x as usize, y as usize)
+                    .map(|id| id as usize)
+                    .or_else(|| renderer.pick(&scene, view_camera, time, x, y));
+                renderer.framebuffer.reset_viewport();
+                picked
+            });
+            if let Some(index) = picked {
+                current_planet_index = index;
+                hud.show(&scene.objects[index].name);
+                if camera.follow_target.is_some() {
+                    camera.follow_target = Some(current_planet_index);
+                } else {
+                    camera.move_to_next_planet(scene.position(current_planet_index, time));
+                }
+            }
+        }
+        right_was_down = right_down;
+
+        for (shader_id, action) in SHADER_ACTIONS.iter().enumerate() {
+            if !typing && bindings.pressed(&input, *action) {
+                if let Some(name) = shaders.name_at(shader_id) {
+                    scene.objects[current_planet_index].shader = name.to_string();
+                    hud.show(&format!("Shader: {}", name));
+                }
+            }
+        }
+
+        if bindings.pressed(&input, Action::Follow) && !camera.map_view {
+            camera.toggle_follow(current_planet_index);
+        }
     
-        for (shader_fn, initial_translation, scale, orbital_speed) in &solar_objects {
-            let angle = time as f32 * orbital_speed;  
-            let translation = Vec3::new(
-                initial_translation.x * angle.cos() - initial_translation.y * angle.sin(),
-                initial_translation.x * angle.sin() + initial_translation.y * angle.cos(),
-                initial_translation.z,
-            );
-        
-            let rotation = Vec3::new(0.0, time as f32 * 0.01, 0.0);  
-            let model_matrix = create_model_matrix(translation, *scale, rotation);
-        
-            let uniforms = Uniforms { 
-                model_matrix, 
-                view_matrix: view_matrix.clone(), 
-                projection_matrix: projection_matrix.clone(), 
-                viewport_matrix: viewport_matrix.clone(),
-                time,
-                noise: create_noise(),
+        // Side-by-side views: a camera following the selected planet, and the free camera
+        if bindings.pressed(&input, Action::SplitView) {
+            split_camera = match split_camera {
+                Some(_) => {
+                    hud.show("Single view");
+                    None
+                }
+                None => {
+                    // Starts off to the side of the orbital plane, where the free camera starts above it
+                    let mut second = Camera::new(Vec3::new(0.0, -SPLIT_FOLLOW_DISTANCE, 1.0), Vec3::zeros(), Vec3::z());
+                    second.follow_distance = SPLIT_FOLLOW_DISTANCE;
+                    hud.show("Split view");
+                    Some(second)
+                }
             };
-        
-            render(&mut framebuffer, &uniforms, &vertex_arrays, shader_fn);
         }
-        
-    
-        window.update_with_buffer(&framebuffer.buffer, framebuffer_width, framebuffer_height).unwrap();
-        std::thread::sleep(frame_delay);
+
+        if bindings.pressed(&input, Action::MapView) {
+            camera.transition = None;
+            camera.follow_target = None;
+            camera.toggle_map_view();
+        }
+        for (action, step) in [(Action::FovNarrower, -FOV_STEP), (Action::FovWider, FOV_STEP)] {
+            if bindings.pressed(&input, action) {
+                camera.set_fov(camera.base_fov + step);
+                hud.show(&format!("FOV: {:.0} deg", camera.base_fov));
+            }
+        }
+
+        if bindings.pressed(&input, Action::Pause) {
+            clock.toggle_pause();
+            hud.show(&clock.describe());
+        }
+        if bindings.pressed(&input, Action::Faster) {
+            clock.faster();
+            hud.show(&clock.describe());
+        }
+        if !typing && bindings.pressed(&input, Action::Slower) {
+            clock.slower();
+            hud.show(&clock.describe());
+        }
+        if bindings.pressed(&input, Action::Reverse) {
+            clock.reverse();
+            hud.show(&clock.describe());
+        }
+        if bindings.pressed(&input, Action::StepBack) {
+            clock.step(-STEP);
+            hud.show(&format!("t={:.1}", clock.time));
+        }
+        if !typing && bindings.pressed(&input, Action::StepForward) {
+            clock.step(STEP);
+            hud.show(&format!("t={:.1}", clock.time));
+        }
+        if !typing && bindings.pressed(&input, Action::GoToTime) {
+            time_prompt = Some(Prompt::new("Go to time: "));
+        }
+
+        if bindings.pressed(&input, Action::Gravity) {
+            if scene.gravity.take().is_some() {
+                hud.show("Fixed orbits");
+            } else {
+                scene.start_gravity(time);
+                hud.show("Gravity simulation");
+            }
+        }
+        // Speeds the selected body up along its path so it swings onto a wider orbit
+        if bindings.pressed(&input, Action::Kick) {
+            match scene.gravity.as_mut() {
+                Some(gravity) => {
+                    let velocity = gravity.bodies[current_planet_index].velocity;
+                    let push = velocity.try_normalize(f32::EPSILON).unwrap_or(Vec3::x()) * velocity.magnitude().max(1.0) * KICK_STRENGTH;
+                    gravity.kick(current_planet_index, push);
+                    hud.show(&format!("Kicked {}", scene.objects[current_planet_index].name));
+                }
+                None => hud.show("Kicks need gravity mode (P)"),
+            }
+        }
+
+        if bindings.pressed(&input, Action::Warp) && !camera.map_view && camera.warp.is_none() {
+            camera.follow_target = None;
+            let target = scene.position(current_planet_index, time);
+            camera.start_warp(target + Vec3::new(0.0, 0.0, 5.0), target);
+        }
+
+        if bindings.pressed(&input, Action::Superlaser) {
+            match effects.fire_superlaser(&scene, current_planet_index) {
+                Ok(()) => hud.show(&format!("Firing at {}", scene.objects[current_planet_index].name)),
+                Err(error) => hud.show(&error),
+            }
+        }
+
+        // The ship spawns just ahead of the camera the first time it is boarded
+        if bindings.pressed(&input, Action::BoardShip) && !camera.map_view {
+            if ship.is_none() {
+                let forward = camera.center - camera.eye;
+                match Ship::load(SHIP_MODEL, camera.eye + forward.normalize() * 2.0, forward) {
+                    Ok(loaded) => ship = Some(loaded),
+                    Err(error) => {
+                        eprintln!("{}", error);
+                        hud.show("Ship failed to load");
+                    }
+                }
+            }
+            if ship.is_some() {
+                piloting = !piloting;
+                camera.follow_target = None;
+                camera.up = Vec3::new(0.0, 1.0, 0.0);
+                hud.show(if piloting { "Piloting ship" } else { "Free camera" });
+            }
+        }
+        if piloting && bindings.pressed(&input, Action::ChaseCamera) {
+            chase_camera = !chase_camera;
+            camera.up = Vec3::new(0.0, 1.0, 0.0);
+            hud.show(if chase_camera { "Chase camera" } else { "Fixed camera" });
+        }
+
+        if camera.transition.is_none() && camera.warp.is_none() && !camera.map_view {
+            // While piloting the keyboard flies the ship instead of the camera
+            if !piloting {
+                handle_input(&controls, &mut camera);
+            }
+            // Dragging a debug slider leaves the camera where it is
+            if debug_ui.wants_mouse() || (piloting && chase_camera) {
+                camera.last_mouse_pos = input.get_mouse_pos(MouseMode::Pass);
+            } else {
+                handle_mouse_input(&input, &mut camera);
+            }
+        } else {
+            // Keep tracking the mouse so the first drag afterwards starts from where it is now
+            camera.last_mouse_pos = input.get_mouse_pos(MouseMode::Pass);
+        }
+        if bindings.pressed(&input, Action::Record) {
+            match recorder.toggle() {
+                Ok(None) => hud.show("Recording"),
+                Ok(Some((directory, frames))) => hud.show(&format!("Recorded {} frames to {}", frames, directory)),
+                Err(error) => eprintln!("{}", error),
+            }
+        }
+
+        // Recordings advance by a fixed step so dropped frames don't change the output
+        let now = Instant::now();
+        let delta_time = if recorder.is_recording() {
+            recorder.timestep
+        } else {
+            now.duration_since(last_frame).as_secs_f32()
+        };
+        last_frame = now;
+
+        // The camera and overlays run on wall-clock time, the bodies on simulation time
+        camera.update_transition(delta_time);
+        camera.update_warp(delta_time);
+        if let Some(ship) = ship.as_mut() {
+            let controls = if piloting && !menu.visible { ship_controls(&input, &bindings) } else { ShipControls::default() };
+            ship.update(&controls, scene.attraction(ship.position, time), delta_time);
+            if piloting && chase_camera {
+                camera.chase(ship.position, ship.forward(), ship.up());
+            }
+        }
+        let simulation_delta = clock.advance(delta_time);
+        // Steps and jumps made this frame are integrated too, not just the clock's advance
+        scene.step_gravity(clock.time - time);
+        let time = clock.time;
+
+        if let Some(index) = camera.follow_target {
+            camera.follow(scene.position(index, time));
+        }
+        if let Some(second) = split_camera.as_mut() {
+            second.follow_target = Some(current_planet_index);
+            second.follow(scene.position(current_planet_index, time));
+        }
+
+        colliders.update(&scene, time);
+        if let Some(current) = ship.as_mut() {
+            if let Some((index, normal, depth)) = colliders.first_hit(&current.bounds()) {
+                if -current.velocity.dot(&normal) > CRASH_SPEED {
+                    effects.explode(current.position, normal);
+                    hud.show(&format!("Crashed into {}", scene.objects[index].name));
+                    ship = None;
+                    piloting = false;
+                    camera.up = Vec3::new(0.0, 1.0, 0.0);
+                } else {
+                    current.bounce(normal, depth);
+                }
+            }
+        }
+
+        // Follow the window size; a minimised window reports zero and is left alone
+        let (width, height) = input.get_size();
+        if width > 0 && height > 0 {
+            renderer.resize(render_size(width, internal_scale), render_size(height, internal_scale));
+        }
+
+        // Particles only age forward, whichever way the orbits are running
+        effects.update(&scene, time, simulation_delta.abs());
+        renderer.reset_stats();
+        let views = split_camera.as_ref().map(|_| split_viewports(&renderer.framebuffer));
+        if let (Some(second), Some((left, right))) = (&split_camera, views) {
+            renderer.framebuffer.set_viewport(left.x, left.y, left.width, left.height);
+            backend.render_frame(&mut renderer, &scene, &shaders, second, time);
+            if let Some(ship) = &ship {
+                renderer.draw_ship(ship, &shaders, &scene, second, time);
+            }
+            renderer.draw_effects(&effects, &scene, second, time);
+            renderer.framebuffer.set_viewport(right.x, right.y, right.width, right.height);
+        }
+        backend.render_frame(&mut renderer, &scene, &shaders, &camera, time);
+        if let Some(ship) = &ship {
+            renderer.draw_ship(ship, &shaders, &scene, &camera, time);
+            if piloting {
+                let time_rate = if clock.paused { 0.0 } else { clock.scale };
+                ship.predict(&scene, time, time_rate, &mut predicted_path);
+                renderer.draw_path(&camera, &predicted_path, Color::new(80, 255, 160));
+            }
+        }
+        renderer.draw_effects(&effects, &scene, &camera, time);
+        renderer.framebuffer.reset_viewport();
+        // Debug views are data rather than light, so they skip tone mapping, bloom and dithering
+        let presentation = renderer.framebuffer.presentation;
+        if renderer.debug_view != DebugView::Final {
+            renderer.framebuffer.presentation = Presentation::unprocessed();
+        }
+        renderer.framebuffer.resolve();
+        renderer.framebuffer.presentation = presentation;
+        if let Some((left, _)) = views {
+            let divider = Rect { x: left.width, y: 0, width: 2, height: renderer.framebuffer.height };
+            renderer.framebuffer.fill_rect(divider, Color::new(90, 90, 120), 1.0);
+        }
+
+        // Captures are taken before overlays so stills and recordings stay clean
+        if bindings.pressed(&input, Action::Screenshot) {
+            match save_screenshot(&renderer.framebuffer) {
+                Ok(path) => hud.show(&format!("Saved {}", path)),
+                Err(error) => eprintln!("{}", error),
+            }
+        }
+
+        if let Err(error) = recorder.capture(&renderer.framebuffer) {
+            eprintln!("{}", error);
+        }
+
+        renderer.framebuffer.draw_outline(current_planet_index as u32, 2, 0xFFFF00);
+
+        if bindings.pressed(&input, Action::Labels) {
+            show_labels = !show_labels;
+        }
+        if show_labels {
+            if let (Some(second), Some((left, right))) = (&split_camera, views) {
+                renderer.framebuffer.set_viewport(left.x, left.y, left.width, left.height);
+                renderer.draw_labels(&scene, second, time, current_planet_index);
+                renderer.framebuffer.set_viewport(right.x, right.y, right.width, right.height);
+            }
+            if !camera.in_hyperspace() {
+                renderer.draw_labels(&scene, &camera, time, current_planet_index);
+            }
+            renderer.framebuffer.reset_viewport();
+        }
+
+        if bindings.pressed(&input, Action::MiniMap) {
+            minimap.visible = !minimap.visible;
+        }
+        if !camera.map_view && !camera.in_hyperspace() {
+            minimap.draw(&mut renderer.framebuffer, &scene, &camera, time, current_planet_index);
+        }
+        if bindings.pressed(&input, Action::PictureInPicture) {
+            pip.visible = !pip.visible;
+        }
+        pip.draw(&mut renderer, &scene, &shaders, time, current_planet_index);
+
+        if bindings.pressed(&input, Action::ToneMapping) {
+            let presentation = &mut renderer.framebuffer.presentation;
+            presentation.tone_mapping = presentation.tone_mapping.next();
+            hud.show(&format!("Tone mapping: {}", presentation.tone_mapping.name()));
+        }
+        if bindings.pressed(&input, Action::Wireframe) {
+            renderer.wireframe = !renderer.wireframe;
+            hud.show(if renderer.wireframe { "Wireframe" } else { "Shaded" });
+        }
+        if bindings.pressed(&input, Action::DebugView) {
+            renderer.debug_view = renderer.debug_view.next();
+            hud.show(&format!("View: {}", renderer.debug_view.name()));
+        }
+        // Shortcut to the object ID view and back
+        if bindings.pressed(&input, Action::ObjectIds) {
+            renderer.debug_view = if renderer.debug_view == DebugView::ObjectIds { DebugView::Final } else { DebugView::ObjectIds };
+            hud.show(&format!("View: {}", renderer.debug_view.name()));
+        }
+        if bindings.pressed(&input, Action::Stats) {
+            frame_stats.visible = !frame_stats.visible;
+        }
+        if bindings.pressed(&input, Action::FrameRate) {
+            match pacer.cycle() {
+                Some(fps) => hud.show(&format!("Target: {} FPS", fps)),
+                None => hud.show("Target: unlimited"),
+            }
+        }
+        if bindings.pressed(&input, Action::DebugUi) {
+            debug_ui.visible = !debug_ui.visible;
+        }
+        if debug_ui.visible {
+            let mouse = input.get_mouse_pos(MouseMode::Pass).map(|(x, y)| (x * internal_scale, y * internal_scale));
+            let mut panel = debug_ui.begin(&mut renderer.framebuffer, mouse, input.get_mouse_down(MouseButton::Left));
+            tuning_panel(&mut panel, &mut scene, &mut camera, current_planet_index, time);
+        }
+        frame_stats.update(delta_time, &renderer.stats, &renderer.profile);
+        if frame_stats.visible {
+            frame_stats.draw(&mut renderer.framebuffer, &renderer.stats);
+        }
+
+        hud.update(delta_time);
+        hud.draw(&mut renderer.framebuffer);
+        menu.draw(&mut renderer.framebuffer, &bindings, |setting| match setting {
+            Setting::RenderScale => format!("{:.0}%", render_scale * 100.0),
+            Setting::TimeScale => format!("x{:.1}", clock.scale),
+            Setting::Antialiasing => antialiasing.name().to_string(),
+            Setting::FieldOfView => format!("{:.0} deg", camera.base_fov),
+            Setting::NearPlane => format!("{}", camera.near),
+            Setting::FarPlane => format!("{}", camera.far),
+            Setting::Shader => {
+                let object = &scene.objects[current_planet_index];
+                format!("{} ({})", object.shader, object.name)
+            }
+            Setting::Quit => String::new(),
+        });
+        if let Some(prompt) = &time_prompt {
+            prompt.draw(&mut renderer.framebuffer);
+        }
+
+        let framebuffer = &renderer.framebuffer;
+        let frame = if (framebuffer.width, framebuffer.height) != (width, height) && width > 0 && height > 0 {
+            // Supersampled frames are averaged down, which needs the bilinear filter
+            let filter = if antialiasing == Antialiasing::Off { filter } else { Filter::Bilinear };
+            framebuffer.upscale(&mut back_buffer, width, height, filter);
+            Frame { pixels: back_buffer, width, height }
+        } else {
+            back_buffer.clear();
+            back_buffer.extend_from_slice(&framebuffer.buffer);
+            Frame { pixels: back_buffer, width: framebuffer.width, height: framebuffer.height }
+        };
+        // Either end going away means the window is gone
+        if frames.send(frame).is_err() {
+            return;
+        }
+        let Ok(buffer) = free_buffers.recv() else {
+            return;
+        };
+        back_buffer = buffer;
+        trace_frame(&mut trace, &renderer, frame_index, now);
+        frame_index += 1;
+        pacer.wait();
     }
 }
 
+// Sliders for tuning the scene live; they edit the loaded scene, so F5 restores the file
+fn tuning_panel(panel: &mut Panel, scene: &mut Scene, camera: &mut Camera, selected: usize, time: f32) {
+    panel.heading("Camera");
+    let mut fov = camera.base_fov;
+    if panel.slider("FOV", &mut fov, MIN_FOV, MAX_FOV) {
+        camera.set_fov(fov);
+    }
+    let (mut near, mut far) = (camera.near, camera.far);
+    let near_changed = panel.slider("Near", &mut near, 0.01, 1.0);
+    if panel.slider("Far", &mut far, 50.0, 5000.0) || near_changed {
+        camera.set_clip_planes(near, far);
+    }
 
+    panel.heading("Lights");
+    for index in scene.lights().collect::<Vec<_>>() {
+        let object = &mut scene.objects[index];
+        if let Some(light) = object.light.as_mut() {
+            panel.slider(&object.name, &mut light.intensity, 0.0, 3.0);
+        }
+    }
 
-fn handle_input(window: &Window, camera: &mut Camera) {
-    let movement_speed = 1.0;
-    let rotation_speed = PI/50.0;
-    let zoom_speed = 0.1;
-   
-    //  camera orbit controls
-    if window.is_key_down(Key::Left) {
-      camera.orbit(rotation_speed, 0.0);
+    let object = &mut scene.objects[selected];
+    panel.heading(&object.name);
+    let mut mean_motion = object.orbit.mean_motion;
+    if panel.slider("Orbital speed", &mut mean_motion, -1.0, 1.0) {
+        object.orbit.set_mean_motion(mean_motion, time);
+    }
+
+    // The noise setters also refresh FastNoiseLite's derived fractal bounds
+    let noise = &mut object.noise;
+    let mut frequency = noise.frequency;
+    if panel.slider("Noise frequency", &mut frequency, 0.001, 0.1) {
+        noise.set_frequency(Some(frequency));
     }
-    if window.is_key_down(Key::Right) {
-      camera.orbit(-rotation_speed, 0.0);
+    let mut octaves = noise.octaves;
+    if panel.slider_int("Octaves", &mut octaves, 1, 8) {
+        noise.set_fractal_octaves(Some(octaves));
     }
-    if window.is_key_down(Key::W) {
-      camera.orbit(0.0, -rotation_speed);
+    let mut lacunarity = noise.lacunarity;
+    if panel.slider("Lacunarity", &mut lacunarity, 1.0, 4.0) {
+        noise.set_fractal_lacunarity(Some(lacunarity));
     }
-    if window.is_key_down(Key::S) {
-      camera.orbit(0.0, rotation_speed);
+    let mut gain = noise.gain;
+    if panel.slider("Gain", &mut gain, 0.0, 1.0) {
+        noise.set_fractal_gain(Some(gain));
     }
+}
 
-    // Camera movement controls
-    let mut movement = Vec3::new(0.0, 0.0, 0.0);
-    if window.is_key_down(Key::A) {
-      movement.x -= movement_speed;
+// Left and right halves of the screen, with a two pixel divider between them
+fn split_viewports(framebuffer: &Framebuffer) -> (Rect, Rect) {
+    let left_width = (framebuffer.width / 2).saturating_sub(1);
+    let right_x = left_width + 2;
+    (
+        Rect { x: 0, y: 0, width: left_width, height: framebuffer.height },
+        Rect { x: right_x, y: 0, width: framebuffer.width.saturating_sub(right_x), height: framebuffer.height },
+    )
+}
+
+// Characters accepted by the time prompt
+fn prompt_char(key: Key) -> Option<char> {
+    let digits = [
+        (Key::Key0, Key::NumPad0), (Key::Key1, Key::NumPad1), (Key::Key2, Key::NumPad2), (Key::Key3, Key::NumPad3),
+        (Key::Key4, Key::NumPad4), (Key::Key5, Key::NumPad5), (Key::Key6, Key::NumPad6), (Key::Key7, Key::NumPad7),
+        (Key::Key8, Key::NumPad8), (Key::Key9, Key::NumPad9),
+    ];
+    if let Some(digit) = digits.iter().position(|&(row, pad)| key == row || key == pad) {
+        return char::from_digit(digit as u32, 10);
+    }
+    match key {
+        Key::Period | Key::NumPadDot => Some('.'),
+        Key::Minus | Key::NumPadMinus => Some('-'),
+        _ => None,
     }
-    if window.is_key_down(Key::D) {
-      movement.x += movement_speed;
+}
+
+fn save_screenshot(framebuffer: &Framebuffer) -> Result<String, String> {
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_millis())
+        .unwrap_or(0);
+
+    std::fs::create_dir_all("screenshots")
+        .map_err(|e| format!("Failed to create screenshots directory: {}", e))?;
+
+    let path = format!("screenshots/screenshot_{}.png", timestamp);
+    framebuffer.save_png(&path)?;
+    Ok(path)
+}
+
+fn ship_controls(input: &WindowInput, bindings: &KeyBindings) -> ShipControls {
+    let axis = |positive, negative| bindings.axis(input, positive, negative);
+    ShipControls {
+        thrust: axis(Action::Thrust, Action::Brake),
+        yaw: axis(Action::YawLeft, Action::YawRight),
+        pitch: axis(Action::PitchUp, Action::PitchDown),
+    }
+}
+
+fn camera_controls(input: &WindowInput, bindings: &KeyBindings) -> CameraControls {
+    let axis = |positive, negative| bindings.axis(input, positive, negative);
+    CameraControls {
+        orbit: Vec2::new(axis(Action::OrbitLeft, Action::OrbitRight), axis(Action::OrbitDown, Action::OrbitUp)),
+        aim: Vec2::new(axis(Action::AimRight, Action::AimLeft), axis(Action::AimUp, Action::AimDown)),
+        zoom: axis(Action::ZoomIn, Action::ZoomOut),
+        fly: Vec3::new(
+            axis(Action::FlyForward, Action::FlyBack),
+            axis(Action::FlyRight, Action::FlyLeft),
+            axis(Action::FlyUp, Action::FlyDown),
+        ),
+        look: Vec2::new(axis(Action::LookRight, Action::LookLeft), axis(Action::LookUp, Action::LookDown)),
+        toggle_mode: bindings.pressed(input, Action::ToggleCameraMode),
+        next_planet: bindings.pressed(input, Action::NextPlanet),
+    }
+}
+
+fn handle_input(controls: &CameraControls, camera: &mut Camera) {
+    if controls.toggle_mode {
+      camera.toggle_mode();
     }
-    if window.is_key_down(Key::Q) {
-      movement.y += movement_speed;
+
+    if camera.mode == CameraMode::Fly {
+      handle_fly_input(controls, camera);
+      return;
     }
-    if window.is_key_down(Key::E) {
-      movement.y -= movement_speed;
+
+    let movement_speed = 1.0;
+    let rotation_speed = PI/50.0;
+    let zoom_speed = 0.1;
+   
+    //  camera orbit controls
+    if controls.orbit.magnitude() > 0.0 {
+      camera.orbit(controls.orbit.x * rotation_speed, controls.orbit.y * rotation_speed);
     }
+
+    // Camera movement controls
+    let movement = Vec3::new(controls.aim.x, controls.aim.y, 0.0) * movement_speed;
     if movement.magnitude() > 0.0 {
       camera.move_center(movement);
     }
 
     // Camera zoom controls
-    if window.is_key_down(Key::Up) {
-      camera.zoom(zoom_speed);
+    if controls.zoom != 0.0 {
+      camera.zoom(controls.zoom * zoom_speed);
     }
-    if window.is_key_down(Key::Down) {
-      camera.zoom(-zoom_speed);
+}
+
+fn handle_fly_input(controls: &CameraControls, camera: &mut Camera) {
+    let rotation_speed = PI/100.0;
+
+    // Fly translation controls
+    let fly = controls.fly;
+    if fly.magnitude() > 0.0 {
+      camera.fly(fly.x, fly.y, fly.z);
     }
-}
\ No newline at end of file
+
+    // Fly look controls
+    if controls.look.magnitude() > 0.0 {
+      camera.look(controls.look.x * rotation_speed, controls.look.y * rotation_speed);
+    }
+}
+
+fn handle_mouse_input(input: &WindowInput, camera: &mut Camera) {
+    let mouse_pos = input.get_mouse_pos(MouseMode::Pass);
+
+    // Drag to orbit, middle-drag to pan
+    if let (Some((x, y)), Some((last_x, last_y))) = (mouse_pos, camera.last_mouse_pos) {
+      let delta_x = x - last_x;
+      let delta_y = y - last_y;
+
+      if input.get_mouse_down(MouseButton::Left) {
+        match camera.mode {
+          CameraMode::Orbit => camera.orbit(-delta_x * camera.mouse_sensitivity, delta_y * camera.mouse_sensitivity),
+          CameraMode::Fly => camera.look(delta_x * camera.mouse_sensitivity, -delta_y * camera.mouse_sensitivity),
+        }
+      }
+      if input.get_mouse_down(MouseButton::Middle) {
+        camera.pan(-delta_x * camera.pan_sensitivity, delta_y * camera.pan_sensitivity);
+      }
+    }
+    camera.last_mouse_pos = mouse_pos;
+
+    // Scroll wheel zoom
+    if let Some((_, scroll_y)) = input.get_scroll_wheel() {
+      camera.zoom(scroll_y * camera.scroll_sensitivity);
+    }
+}
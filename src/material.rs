@@ -0,0 +1,38 @@
+use std::path::Path;
+use nalgebra_glm::Vec3;
+use crate::texture::Texture;
+
+#[derive(Clone)]
+pub struct Material {
+    pub name: String,
+    pub diffuse: Vec3,
+    pub specular: Vec3,
+    pub shininess: f32,
+    pub diffuse_texture: Option<Texture>,
+}
+
+impl Material {
+    pub fn from_mtl(material: tobj::Material, base_dir: &Path) -> Self {
+        let to_vec3 = |c: [f32; 3]| Vec3::new(c[0], c[1], c[2]);
+
+        // A missing texture should not keep the model from loading
+        let diffuse_texture = material.diffuse_texture.and_then(|file| {
+            let path = base_dir.join(&file);
+            match Texture::load(&path.to_string_lossy()) {
+                Ok(texture) => Some(texture),
+                Err(error) => {
+                    eprintln!("{}", error);
+                    None
+                }
+            }
+        });
+
+        Material {
+            name: material.name,
+            diffuse: material.diffuse.map(to_vec3).unwrap_or(Vec3::new(0.8, 0.8, 0.8)),
+            specular: material.specular.map(to_vec3).unwrap_or(Vec3::zeros()),
+            shininess: material.shininess.unwrap_or(0.0),
+            diffuse_texture,
+        }
+    }
+}
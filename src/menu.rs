@@ -0,0 +1,144 @@
+use crate::color::Color;
+use crate::framebuffer::{Framebuffer, Rect};
+use crate::keybindings::{actions, Action, KeyBindings};
+use crate::text::{draw_text, GLYPH_SIZE, GLYPH_ADVANCE};
+
+const MARGIN: usize = 24;
+const PADDING: usize = 12;
+const LINE_SPACING: usize = 2;
+// Characters between the key list's columns
+const COLUMN_GAP: usize = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Setting {
+    RenderScale,
+    TimeScale,
+    Antialiasing,
+    FieldOfView,
+    NearPlane,
+    FarPlane,
+    Shader,
+    Quit,
+}
+
+const SETTINGS: [Setting; 8] = [
+    Setting::RenderScale,
+    Setting::TimeScale,
+    Setting::Antialiasing,
+    Setting::FieldOfView,
+    Setting::NearPlane,
+    Setting::FarPlane,
+    Setting::Shader,
+    Setting::Quit,
+];
+
+impl Setting {
+    pub fn label(self) -> &'static str {
+        match self {
+            Setting::RenderScale => "Render scale",
+            Setting::TimeScale => "Time scale",
+            Setting::Antialiasing => "Antialiasing",
+            Setting::FieldOfView => "Field of view",
+            Setting::NearPlane => "Near plane",
+            Setting::FarPlane => "Far plane",
+            Setting::Shader => "Shader",
+            Setting::Quit => "Quit",
+        }
+    }
+}
+
+// Full-screen overlay with the live settings on top, picked with the up and down arrows
+// and changed with left and right, and every key binding below them
+pub struct Menu {
+    pub visible: bool,
+    selected: usize,
+}
+
+impl Menu {
+    pub fn new() -> Self {
+        Menu { visible: false, selected: 0 }
+    }
+
+    pub fn toggle(&mut self) {
+        self.visible = !self.visible;
+    }
+
+    pub fn move_selection(&mut self, offset: isize) {
+        self.selected = (self.selected as isize + offset).rem_euclid(SETTINGS.len() as isize) as usize;
+    }
+
+    pub fn selected(&self) -> Setting {
+        SETTINGS[self.selected]
+    }
+
+    // `value` describes each setting's current state, which lives with its owner
+    pub fn draw(&self, framebuffer: &mut Framebuffer, bindings: &KeyBindings, value: impl Fn(Setting) -> String) {
+        if !self.visible || framebuffer.width < MARGIN * 4 || framebuffer.height < MARGIN * 4 {
+            return;
+        }
+
+        // Keeps the text a readable size when rendering above the window resolution
+        let scale = ((framebuffer.height + 300) / 600).max(1);
+        let line = (GLYPH_SIZE + LINE_SPACING) * scale;
+        let panel = Rect {
+            x: MARGIN,
+            y: MARGIN,
+            width: framebuffer.width - MARGIN * 2,
+            height: framebuffer.height - MARGIN * 2,
+        };
+        framebuffer.fill_rect(panel, Color::new(10, 15, 30), 0.85);
+        framebuffer.stroke_rect(panel, 0x606080);
+
+        let left = panel.x + PADDING;
+        let mut y = panel.y + PADDING;
+        draw_text(framebuffer, "Settings", left, y, scale * 2, 0xFFFFFF);
+        y += line * 3;
+
+        let label_width = SETTINGS.iter().map(|setting| setting.label().len()).max().unwrap_or(0) + 2;
+        for (index, setting) in SETTINGS.iter().enumerate() {
+            let selected = index == self.selected;
+            let value = value(*setting);
+            let value = if selected && !value.is_empty() { format!("< {} >", value) } else { value };
+            let row = format!("{} {:<width$}{}", if selected { ">" } else { " " }, setting.label(), value, width = label_width);
+            draw_text(framebuffer, &row, left, y, scale, if selected { 0xFFFF00 } else { 0xFFFFFF });
+            y += line;
+        }
+
+        y += line / 2;
+        let hint = format!(
+            "Up/Down select, Left/Right change, Enter on Quit exits, {} closes",
+            bindings.describe(Action::Menu)
+        );
+        draw_text(framebuffer, &hint, left, y, scale, 0x9090A0);
+        y += line * 2;
+
+        draw_text(framebuffer, "Keys", left, y, scale * 2, 0xFFFFFF);
+        y += line * 2;
+
+        // Bindings fill columns top to bottom, as many as fit beside each other
+        let entries: Vec<(String, String)> = actions()
+            .map(|action| (action.name().replace('_', " "), bindings.describe(action)))
+            .collect();
+        let name_width = entries.iter().map(|(name, _)| name.len()).max().unwrap_or(0) + 1;
+        let keys_width = entries.iter().map(|(_, keys)| keys.len()).max().unwrap_or(0);
+        let (name_pixels, keys_pixels) = (name_width * GLYPH_ADVANCE * scale, keys_width * GLYPH_ADVANCE * scale);
+        let column_width = name_pixels + keys_pixels + COLUMN_GAP * GLYPH_ADVANCE * scale;
+        let rows = ((panel.y + panel.height).saturating_sub(y + PADDING) / line).max(1);
+
+        for (index, (name, keys)) in entries.iter().enumerate() {
+            let x = left + (index / rows) * column_width;
+            if x + name_pixels + keys_pixels > panel.x + panel.width - PADDING {
+                break;
+            }
+            let row_y = y + (index % rows) * line;
+            draw_text(framebuffer, name, x, row_y, scale, 0xB0B0C0);
+            draw_text(framebuffer, keys, x + name_pixels, row_y, scale, 0x80E0FF);
+        }
+    }
+}
+
+impl Default for Menu {
+    fn default() -> Self {
+        Self::new()
+    }
+}
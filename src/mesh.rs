@@ -0,0 +1,204 @@
+use std::collections::HashMap;
+use std::f32::consts::PI;
+use std::sync::atomic::{AtomicU64, Ordering};
+use nalgebra_glm::{Vec2, Vec3};
+use crate::vertex::Vertex;
+
+static NEXT_GENERATION: AtomicU64 = AtomicU64::new(1);
+
+// A new id on every call, for owners of meshes to tag them with when they build or replace
+// them, so copies uploaded elsewhere can tell an old mesh from a new one
+pub fn next_generation() -> u64 {
+    NEXT_GENERATION.fetch_add(1, Ordering::Relaxed)
+}
+
+pub struct IndexedMesh {
+    pub vertices: Vec<Vertex>,
+    pub indices: Vec<u32>,
+    pub groups: Vec<MeshGroup>,
+}
+
+// A contiguous range of `indices` that shares one material
+pub struct MeshGroup {
+    pub material: Option<usize>,
+    pub start: usize,
+    pub count: usize,
+}
+
+impl IndexedMesh {
+    pub fn new() -> Self {
+        IndexedMesh { vertices: Vec::new(), indices: Vec::new(), groups: Vec::new() }
+    }
+
+    // Callers append in material order so that each material ends up in a single group
+    pub fn append(&mut self, vertices: Vec<Vertex>, indices: &[u32], material: Option<usize>) {
+        let base = self.vertices.len() as u32;
+        self.vertices.extend(vertices);
+
+        match self.groups.last_mut() {
+            Some(group) if group.material == material => group.count += indices.len(),
+            _ => self.groups.push(MeshGroup {
+                material,
+                start: self.indices.len(),
+                count: indices.len(),
+            }),
+        }
+        self.indices.extend(indices.iter().map(|index| base + index));
+    }
+}
+
+impl Default for IndexedMesh {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// One resolution of a mesh, used while the body covers at least `min_screen_radius` pixels
+pub struct LodLevel {
+    pub min_screen_radius: f32,
+    pub mesh: IndexedMesh,
+}
+
+pub fn sphere_lods(radius: f32) -> Vec<LodLevel> {
+    vec![
+        LodLevel { min_screen_radius: 40.0, mesh: uv_sphere(radius, 32, 16) },
+        LodLevel { min_screen_radius: 12.0, mesh: uv_sphere(radius, 16, 8) },
+        LodLevel { min_screen_radius: 0.0, mesh: uv_sphere(radius, 10, 6) },
+    ]
+}
+
+pub fn uv_sphere(radius: f32, segments: u32, rings: u32) -> IndexedMesh {
+    let mut vertices = Vec::new();
+    let mut indices = Vec::new();
+
+    // The seam column is duplicated so UVs wrap from 1 back to 0 cleanly
+    for ring in 0..=rings {
+        let v = ring as f32 / rings as f32;
+        let theta = v * PI;
+
+        for segment in 0..=segments {
+            let u = segment as f32 / segments as f32;
+            let phi = u * 2.0 * PI;
+
+            let normal = Vec3::new(theta.sin() * phi.cos(), theta.cos(), -theta.sin() * phi.sin());
+            vertices.push(Vertex::new(normal * radius, normal, Vec2::new(u, v)));
+        }
+    }
+
+    let columns = segments + 1;
+    for ring in 0..rings {
+        for segment in 0..segments {
+            let a = ring * columns + segment;
+            let b = a + columns;
+
+            if ring != 0 {
+                indices.extend_from_slice(&[a, b, a + 1]);
+            }
+            if ring != rings - 1 {
+                indices.extend_from_slice(&[a + 1, b, b + 1]);
+            }
+        }
+    }
+
+    let mut mesh = IndexedMesh::new();
+    mesh.append(vertices, &indices, None);
+    mesh
+}
+
+pub fn icosphere(radius: f32, subdivisions: u32) -> IndexedMesh {
+    let t = (1.0 + 5.0_f32.sqrt()) / 2.0;
+    let mut positions: Vec<Vec3> = [
+        (-1.0, t, 0.0), (1.0, t, 0.0), (-1.0, -t, 0.0), (1.0, -t, 0.0),
+        (0.0, -1.0, t), (0.0, 1.0, t), (0.0, -1.0, -t), (0.0, 1.0, -t),
+        (t, 0.0, -1.0), (t, 0.0, 1.0), (-t, 0.0, -1.0), (-t, 0.0, 1.0),
+    ].iter().map(|&(x, y, z)| Vec3::new(x, y, z).normalize()).collect();
+
+    let mut faces: Vec<[u32; 3]> = vec![
+        [0, 11, 5], [0, 5, 1], [0, 1, 7], [0, 7, 10], [0, 10, 11],
+        [1, 5, 9], [5, 11, 4], [11, 10, 2], [10, 7, 6], [7, 1, 8],
+        [3, 9, 4], [3, 4, 2], [3, 2, 6], [3, 6, 8], [3, 8, 9],
+        [4, 9, 5], [2, 4, 11], [6, 2, 10], [8, 6, 7], [9, 8, 1],
+    ];
+
+    for _ in 0..subdivisions {
+        let mut midpoints: HashMap<(u32, u32), u32> = HashMap::new();
+        let mut midpoint = |a: u32, b: u32, positions: &mut Vec<Vec3>| {
+            *midpoints.entry((a.min(b), a.max(b))).or_insert_with(|| {
+                positions.push(((positions[a as usize] + positions[b as usize]) * 0.5).normalize());
+                positions.len() as u32 - 1
+            })
+        };
+
+        let mut subdivided = Vec::with_capacity(faces.len() * 4);
+        for [a, b, c] in faces {
+            let ab = midpoint(a, b, &mut positions);
+            let bc = midpoint(b, c, &mut positions);
+            let ca = midpoint(c, a, &mut positions);
+            subdivided.extend_from_slice(&[[a, ab, ca], [b, bc, ab], [c, ca, bc], [ab, bc, ca]]);
+        }
+        faces = subdivided;
+    }
+
+    let vertices = positions.iter().map(|&normal| {
+        let u = 0.5 + normal.z.atan2(normal.x) / (2.0 * PI);
+        let v = normal.y.clamp(-1.0, 1.0).acos() / PI;
+        Vertex::new(normal * radius, normal, Vec2::new(u, v))
+    }).collect();
+    let indices: Vec<u32> = faces.into_iter().flatten().collect();
+
+    let mut mesh = IndexedMesh::new();
+    mesh.append(vertices, &indices, None);
+    mesh
+}
+
+// Flat annulus in the XZ plane facing +Y; an inner radius of zero gives a disk
+pub fn ring(inner_radius: f32, outer_radius: f32, segments: u32) -> IndexedMesh {
+    let mut vertices = Vec::new();
+    let mut indices = Vec::new();
+    let normal = Vec3::new(0.0, 1.0, 0.0);
+
+    for segment in 0..=segments {
+        let v = segment as f32 / segments as f32;
+        let (sin, cos) = (v * 2.0 * PI).sin_cos();
+        let direction = Vec3::new(cos, 0.0, -sin);
+
+        vertices.push(Vertex::new(direction * inner_radius, normal, Vec2::new(0.0, v)));
+        vertices.push(Vertex::new(direction * outer_radius, normal, Vec2::new(1.0, v)));
+    }
+
+    for segment in 0..segments {
+        let inner = segment * 2;
+        indices.extend_from_slice(&[inner, inner + 1, inner + 3, inner, inner + 3, inner + 2]);
+    }
+
+    let mut mesh = IndexedMesh::new();
+    mesh.append(vertices, &indices, None);
+    mesh
+}
+
+// Area-weighted face normals accumulated per position, so vertices split along
+// UV seams still end up with the same normal
+pub fn smooth_normals(vertices: &[Vec3], indices: &[u32]) -> Vec<Vec3> {
+    let key = |v: &Vec3| (v.x.to_bits(), v.y.to_bits(), v.z.to_bits());
+    let mut accumulated: HashMap<(u32, u32, u32), Vec3> = HashMap::new();
+
+    for face in indices.chunks_exact(3) {
+        let a = vertices[face[0] as usize];
+        let b = vertices[face[1] as usize];
+        let c = vertices[face[2] as usize];
+        let face_normal = (b - a).cross(&(c - a));
+
+        for &index in face {
+            *accumulated.entry(key(&vertices[index as usize])).or_insert(Vec3::zeros()) += face_normal;
+        }
+    }
+
+    vertices.iter().map(|v| {
+        let normal = accumulated.get(&key(v)).cloned().unwrap_or(Vec3::zeros());
+        if normal.norm() > 0.0 {
+            normal.normalize()
+        } else {
+            Vec3::new(0.0, 1.0, 0.0)
+        }
+    }).collect()
+}
@@ -0,0 +1,108 @@
+use nalgebra_glm::{Vec2, Vec3};
+use crate::camera::Camera;
+use crate::collision::BODY_RADIUS;
+use crate::color::Color;
+use crate::framebuffer::{Framebuffer, Rect};
+use crate::line::line;
+use crate::scene::Scene;
+
+const SIZE: usize = 160;
+const MARGIN: usize = 10;
+// Length of the view wedge, as a fraction of the map's half width
+const WEDGE_LENGTH: f32 = 0.35;
+
+// Top-down orthographic overview of the orbital (XY) plane in the top-right corner
+pub struct MiniMap {
+    pub visible: bool,
+}
+
+impl MiniMap {
+    pub fn new() -> Self {
+        MiniMap { visible: true }
+    }
+
+    fn rect(framebuffer: &Framebuffer) -> Option<Rect> {
+        if framebuffer.width < SIZE + MARGIN * 2 || framebuffer.height < SIZE + MARGIN * 2 {
+            return None;
+        }
+        Some(Rect { x: framebuffer.width - SIZE - MARGIN, y: MARGIN, width: SIZE, height: SIZE })
+    }
+
+    pub fn draw(&self, framebuffer: &mut Framebuffer, scene: &Scene, camera: &Camera, time: f32, selected: usize) {
+        if !self.visible {
+            return;
+        }
+        let Some(rect) = Self::rect(framebuffer) else {
+            return;
+        };
+        framebuffer.fill_rect(rect, Color::new(10, 15, 30), 0.75);
+        framebuffer.stroke_rect(rect, 0x606080);
+        // Everything inside is clipped to the square
+        framebuffer.set_viewport(rect.x, rect.y, rect.width, rect.height);
+
+        // Fit every body, plus a little border, into the square
+        let extent = (0..scene.objects.len())
+            .map(|index| {
+                let position = scene.position(index, time);
+                Vec2::new(position.x, position.y).abs().max() + scene.objects[index].scale
+            })
+            .fold(1.0, f32::max) * 1.1;
+        let half = SIZE as f32 / 2.0;
+        let pixels_per_unit = half / extent;
+        let center = Vec2::new(rect.x as f32 + half, rect.y as f32 + half);
+        let to_map = |world: Vec3| center + Vec2::new(world.x, -world.y) * pixels_per_unit;
+
+        // Camera position with a wedge covering its horizontal field of view
+        let eye = to_map(camera.eye);
+        let look = camera.center - camera.eye;
+        if let Some(heading) = Vec2::new(look.x, -look.y).try_normalize(f32::EPSILON) {
+            let aspect = framebuffer.width as f32 / framebuffer.height as f32;
+            let half_fov = ((camera.fov.to_radians() / 2.0).tan() * aspect).atan();
+            for side in [-half_fov, half_fov] {
+                let (sin, cos) = side.sin_cos();
+                let edge = Vec2::new(heading.x * cos - heading.y * sin, heading.x * sin + heading.y * cos);
+                let end = eye + edge * half * WEDGE_LENGTH;
+                line(Vec3::new(eye.x, eye.y, 0.0), Vec3::new(end.x, end.y, 0.0), |x, y, _| {
+                    if x >= 0 && y >= 0 {
+                        framebuffer.draw_pixel(x as usize, y as usize, 0x80C0FF);
+                    }
+                });
+            }
+        }
+
+        let sun = scene.sun();
+        for (index, object) in scene.objects.iter().enumerate() {
+            let position = to_map(scene.position(index, time));
+            let radius = (object.scale * BODY_RADIUS * pixels_per_unit).max(1.5);
+            let color = if index == selected {
+                0xFFFF00
+            } else if Some(index) == sun {
+                0xFFAA33
+            } else {
+                0xC0C0C0
+            };
+            fill_circle(framebuffer, position, radius, color);
+        }
+        fill_circle(framebuffer, eye, 1.5, 0x80C0FF);
+        framebuffer.reset_viewport();
+    }
+}
+
+impl Default for MiniMap {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn fill_circle(framebuffer: &mut Framebuffer, center: Vec2, radius: f32, color: u32) {
+    let reach = radius.ceil() as i32;
+    let (cx, cy) = (center.x as i32, center.y as i32);
+    for y in cy - reach..=cy + reach {
+        for x in cx - reach..=cx + reach {
+            let (dx, dy) = (x as f32 + 0.5 - center.x, y as f32 + 0.5 - center.y);
+            if dx * dx + dy * dy <= radius * radius && x >= 0 && y >= 0 {
+                framebuffer.draw_pixel(x as usize, y as usize, color);
+            }
+        }
+    }
+}
@@ -0,0 +1,128 @@
+use nalgebra_glm::{Vec3, Vec4, Mat4};
+use fastnoise_lite::{FastNoiseLite, NoiseType};
+use crate::color::Color;
+use crate::framebuffer::{Framebuffer, SKY_DEPTH};
+use crate::noise::{self, Fractal};
+use crate::scene::NebulaConfig;
+use crate::skybox::Skybox;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+// The furthest layer's distance; each nearer one sits at a fraction of it, so it shifts a
+// little more as the camera travels
+const FAR_DISTANCE: f32 = 600.0;
+const WARP_STRENGTH: f32 = 0.6;
+
+struct NebulaLayer {
+    skybox: Skybox,
+    distance: f32,
+}
+
+// Glowing gas clouds far behind the stars, baked into cube maps when the scene loads
+pub struct Nebula {
+    layers: Vec<NebulaLayer>,
+    // Hash of the config it was baked from, so kept sky can tell one nebula from another
+    fingerprint: u64,
+}
+
+impl Nebula {
+    pub fn new(config: &NebulaConfig) -> Self {
+        let palette: Vec<Color> = config.palette.iter().map(|&hex| Color::from_hex(hex)).collect();
+        let density_fractal = Fractal::new(4, 2.0, 0.5);
+        let color_fractal = Fractal::new(3, 2.0, 0.5);
+
+        let layers = (0..config.layers).map(|layer| {
+            let mut noise = FastNoiseLite::with_seed(config.seed.wrapping_add(layer as i32));
+            noise.set_noise_type(Some(NoiseType::OpenSimplex2));
+            noise.set_frequency(Some(1.0));
+
+            // Nearer layers are fainter and show larger features
+            let frequency = 2.4 / (1.0 + layer as f32 * 0.5);
+            let brightness = config.brightness / (1.0 + layer as f32);
+            let skybox = Skybox::from_fn(config.resolution, |direction| {
+                let p = direction * frequency;
+                let gas = noise::domain_warp(&noise, p, &density_fractal, WARP_STRENGTH) * 0.5 + 0.5;
+                let density = ((gas - config.coverage) / (1.0 - config.coverage).max(f32::EPSILON)).clamp(0.0, 1.0);
+                if density <= 0.0 {
+                    return Color::black();
+                }
+
+                let hue = noise::fbm(&noise, p * 0.5 + Vec3::new(31.0, 7.0, 19.0), &color_fractal) * 0.5 + 0.5;
+                palette_color(&palette, hue) * (density * density * brightness)
+            });
+
+            NebulaLayer { skybox, distance: FAR_DISTANCE / (1.0 + layer as f32) }
+        }).collect();
+
+        Nebula { layers, fingerprint: fingerprint(config) }
+    }
+
+    // Each layer's cube map with the distance it is sampled at
+    pub fn layers(&self) -> impl Iterator<Item = (&Skybox, f32)> + '_ {
+        self.layers.iter().map(|layer| (&layer.skybox, layer.distance))
+    }
+
+    pub fn fingerprint(&self) -> u64 {
+        self.fingerprint
+    }
+
+    pub fn draw(&self, framebuffer: &mut Framebuffer, view_projection: &Mat4, viewport_matrix: &Mat4, eye: Vec3) {
+        if self.layers.is_empty() {
+            return;
+        }
+        let (Some(inverse_viewport), Some(inverse_view_projection)) = (viewport_matrix.try_inverse(), view_projection.try_inverse()) else {
+            return;
+        };
+
+        // The unnormalized view ray is affine in screen coordinates, so three pixels give it
+        // everywhere without a matrix product per pixel. w keeps one sign across the screen.
+        let ray = |x: f32, y: f32| {
+            let ndc = inverse_viewport * Vec4::new(x, y, 0.0, 1.0);
+            let far = inverse_view_projection * Vec4::new(ndc.x, ndc.y, 0.0, 1.0);
+            (Vec3::new(far.x, far.y, far.z) - eye * far.w) * far.w.signum()
+        };
+        let origin = ray(0.5, 0.5);
+        let step_x = ray(1.5, 0.5) - origin;
+        let step_y = ray(0.5, 1.5) - origin;
+
+        let viewport = framebuffer.viewport();
+        for y in viewport.y..viewport.y + viewport.height {
+            for x in viewport.x..viewport.x + viewport.width {
+                // Bodies already drawn hide the sky, and clean tiles got theirs back from the last frame
+                if !framebuffer.depth_test(x, y, SKY_DEPTH) || !framebuffer.redraws(x, y) {
+                    continue;
+                }
+                let Some(direction) = (origin + step_x * x as f32 + step_y * y as f32).try_normalize(f32::EPSILON) else {
+                    continue;
+                };
+
+                // Where the ray meets each layer, seen from the center of the scene
+                let mut color = Color::black();
+                for layer in &self.layers {
+                    color = color + layer.skybox.sample(&(direction * layer.distance + eye));
+                }
+                framebuffer.add_sky(x, y, color);
+            }
+        }
+    }
+}
+
+fn fingerprint(config: &NebulaConfig) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    (config.seed, config.layers, &config.palette, config.resolution).hash(&mut hasher);
+    config.brightness.to_bits().hash(&mut hasher);
+    config.coverage.to_bits().hash(&mut hasher);
+    hasher.finish()
+}
+
+fn palette_color(palette: &[Color], t: f32) -> Color {
+    match palette {
+        [] => Color::black(),
+        [only] => *only,
+        _ => {
+            let position = t.clamp(0.0, 1.0) * (palette.len() - 1) as f32;
+            let index = (position as usize).min(palette.len() - 2);
+            palette[index].lerp(&palette[index + 1], position - index as f32)
+        }
+    }
+}
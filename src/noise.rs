@@ -0,0 +1,166 @@
+use nalgebra_glm::Vec3;
+use fastnoise_lite::FastNoiseLite;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Fractal {
+    pub octaves: u32,
+    pub lacunarity: f32,
+    pub gain: f32,
+}
+
+impl Default for Fractal {
+    fn default() -> Self {
+        Fractal {
+            octaves: 5,
+            lacunarity: 2.0,
+            gain: 0.5,
+        }
+    }
+}
+
+impl Fractal {
+    pub fn new(octaves: u32, lacunarity: f32, gain: f32) -> Self {
+        Fractal { octaves, lacunarity, gain }
+    }
+
+    // Sums a per-octave function of the noise value, normalised by the total amplitude
+    fn accumulate(&self, noise: &FastNoiseLite, p: Vec3, octave: impl Fn(f32) -> f32) -> f32 {
+        let mut sum = 0.0;
+        let mut amplitude = 1.0;
+        let mut frequency = 1.0;
+        let mut total = 0.0;
+
+        for _ in 0..self.octaves.max(1) {
+            let q = p * frequency;
+            sum += octave(noise.get_noise_3d(q.x, q.y, q.z)) * amplitude;
+            total += amplitude;
+            amplitude *= self.gain;
+            frequency *= self.lacunarity;
+        }
+
+        sum / total
+    }
+}
+
+// Fractal Brownian motion, in [-1, 1]
+pub fn fbm(noise: &FastNoiseLite, p: Vec3, fractal: &Fractal) -> f32 {
+    fractal.accumulate(noise, p, |n| n)
+}
+
+// Sharp crests where the noise crosses zero, in [0, 1]; good for mountain ranges
+pub fn ridged(noise: &FastNoiseLite, p: Vec3, fractal: &Fractal) -> f32 {
+    fractal.accumulate(noise, p, |n| {
+        let ridge = 1.0 - n.abs();
+        ridge * ridge
+    })
+}
+
+// Billowy absolute-value noise, in [0, 1]
+pub fn turbulence(noise: &FastNoiseLite, p: Vec3, fractal: &Fractal) -> f32 {
+    fractal.accumulate(noise, p, |n| n.abs())
+}
+
+// fBm sampled at a position pushed around by three other fBm lookups
+pub fn domain_warp(noise: &FastNoiseLite, p: Vec3, fractal: &Fractal, strength: f32) -> f32 {
+    let offset = Vec3::new(
+        fbm(noise, p, fractal),
+        fbm(noise, p + Vec3::new(52.0, 13.0, 97.0), fractal),
+        fbm(noise, p + Vec3::new(17.0, 83.0, 29.0), fractal),
+    );
+    fbm(noise, p + offset * strength, fractal)
+}
+
+// Cheap integer hash to [0, 1)
+pub fn hash(x: i32, y: i32, z: i32) -> f32 {
+    let mut h = (x as u32).wrapping_mul(0x8DA6_B343) ^ (y as u32).wrapping_mul(0xD816_3841) ^ (z as u32).wrapping_mul(0xCB1A_B31F);
+    h ^= h >> 13;
+    h = h.wrapping_mul(0x5BD1_E995);
+    h ^= h >> 15;
+    (h & 0x00FF_FFFF) as f32 / 0x0100_0000 as f32
+}
+
+// Impact craters scattered over a surface: space is split into cells, and some of them
+// hold a crater around a jittered point, as in cellular noise
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Craters {
+    // Cells per object-space unit
+    pub frequency: f32,
+    // Fraction of cells that hold a crater
+    pub density: f32,
+    // Crater radius range as a fraction of a cell
+    pub min_radius: f32,
+    pub max_radius: f32,
+    // Bowl depth and rim height relative to the radius
+    pub depth: f32,
+    pub rim_height: f32,
+    // How strongly the slopes tilt the normal
+    pub bump: f32,
+}
+
+impl Default for Craters {
+    fn default() -> Self {
+        Craters {
+            frequency: 6.0,
+            density: 0.5,
+            min_radius: 0.15,
+            max_radius: 0.45,
+            depth: 0.5,
+            rim_height: 0.15,
+            bump: 0.6,
+        }
+    }
+}
+
+// Offset for the finite differences behind Craters::normal, in cells
+const CRATER_EPSILON: f32 = 0.02;
+
+impl Craters {
+    // Height at p in cell units: negative inside bowls, positive on rims, zero between craters
+    pub fn height(&self, p: Vec3) -> f32 {
+        let p = p * self.frequency;
+        let cell = p.map(f32::floor);
+        let mut height = 0.0;
+
+        // A crater can spill over from any neighbouring cell
+        for dx in -1..=1 {
+            for dy in -1..=1 {
+                for dz in -1..=1 {
+                    let (x, y, z) = (cell.x as i32 + dx, cell.y as i32 + dy, cell.z as i32 + dz);
+                    if hash(x, y, z) > self.density {
+                        continue;
+                    }
+                    let center = Vec3::new(x as f32 + hash(y, z, x), y as f32 + hash(z, x, y), z as f32 + hash(x, z, y));
+                    let radius = self.min_radius + (self.max_radius - self.min_radius) * hash(z, y, x);
+                    let r = (p - center).magnitude() / radius;
+                    if r > 1.5 {
+                        continue;
+                    }
+
+                    // Parabolic bowl inside the rim and a raised lip that falls off just outside it
+                    let bowl = if r < 1.0 { (r * r - 1.0) * self.depth } else { 0.0 };
+                    let rim = self.rim_height * (-((r - 1.0) / 0.25).powi(2)).exp();
+                    height += (bowl + rim) * radius;
+                }
+            }
+        }
+        height
+    }
+
+    // Height at p and `normal` (in the same space as p) bent down the crater slopes, using
+    // forward differences of the height field projected onto the surface
+    pub fn sample(&self, p: Vec3, normal: Vec3) -> (f32, Vec3) {
+        let step = CRATER_EPSILON / self.frequency;
+        let height = self.height(p);
+        let gradient = Vec3::new(
+            self.height(p + Vec3::x() * step) - height,
+            self.height(p + Vec3::y() * step) - height,
+            self.height(p + Vec3::z() * step) - height,
+        ) / CRATER_EPSILON;
+        let tangent = gradient - normal * gradient.dot(&normal);
+        (height, (normal - tangent * self.bump).try_normalize(f32::EPSILON).unwrap_or(normal))
+    }
+}
+
@@ -1,9 +1,13 @@
 use tobj;
+use std::path::Path;
 use nalgebra_glm::{Vec2, Vec3};
 use crate::vertex::Vertex;
+use crate::material::Material;
+use crate::mesh::{IndexedMesh, smooth_normals};
 
 pub struct Obj {
     meshes: Vec<Mesh>,
+    materials: Vec<Material>,
 }
 
 struct Mesh {
@@ -11,33 +15,58 @@ struct Mesh {
     normals: Vec<Vec3>,
     texcoords: Vec<Vec2>,
     indices: Vec<u32>,
+    material: Option<usize>,
 }
 
 impl Obj {
     pub fn load(filename: &str) -> Result<Self, tobj::LoadError> {
-        let (models, _) = tobj::load_obj(filename, &tobj::LoadOptions {
+        let (models, materials) = tobj::load_obj(filename, &tobj::LoadOptions {
             single_index: true,
             triangulate: true,
             ..Default::default()
         })?;
 
+        // tobj resolves negative indices and fan-triangulates quads and n-gons for us
         let meshes = models.into_iter().map(|model| {
             let mesh = model.mesh;
+            let vertices: Vec<Vec3> = mesh.positions.chunks(3)
+                .map(|v| Vec3::new(v[0], v[1], v[2]))
+                .collect();
+            let mut normals: Vec<Vec3> = mesh.normals.chunks(3)
+                .map(|n| Vec3::new(n[0], n[1], n[2]))
+                .collect();
+            if normals.len() != vertices.len() {
+                normals = smooth_normals(&vertices, &mesh.indices);
+            }
+
             Mesh {
-                vertices: mesh.positions.chunks(3)
-                    .map(|v| Vec3::new(v[0], v[1], v[2]))
-                    .collect(),
-                normals: mesh.normals.chunks(3)
-                    .map(|n| Vec3::new(n[0], n[1], n[2]))
-                    .collect(),
+                vertices,
+                normals,
                 texcoords: mesh.texcoords.chunks(2)
                     .map(|t| Vec2::new(t[0], 1.0 - t[1]))
                     .collect(),
                 indices: mesh.indices,
+                material: mesh.material_id,
             }
         }).collect();
 
-        Ok(Obj { meshes })
+        // mtllib paths are relative to the .obj file
+        let base_dir = Path::new(filename).parent().unwrap_or(Path::new(""));
+        let materials = match materials {
+            Ok(materials) => materials.into_iter()
+                .map(|material| Material::from_mtl(material, base_dir))
+                .collect(),
+            Err(error) => {
+                eprintln!("Failed to load materials for {}: {}", filename, error);
+                Vec::new()
+            }
+        };
+
+        Ok(Obj { meshes, materials })
+    }
+
+    pub fn materials(&self) -> &[Material] {
+        &self.materials
     }
 
     pub fn get_vertex_array(&self) -> Vec<Vertex> {
@@ -59,4 +88,29 @@ impl Obj {
 
         vertices
     }
-}
\ No newline at end of file
+
+    pub fn get_indexed_mesh(&self) -> IndexedMesh {
+        let mut indexed_mesh = IndexedMesh::new();
+
+        // Sort by material so faces sharing a material form a single group
+        let mut meshes: Vec<&Mesh> = self.meshes.iter().collect();
+        meshes.sort_by_key(|mesh| mesh.material);
+
+        for mesh in meshes {
+            let vertices = mesh.vertices.iter().enumerate().map(|(i, &position)| {
+                let normal = mesh.normals.get(i)
+                    .cloned()
+                    .unwrap_or(Vec3::new(0.0, 1.0, 0.0));
+                let tex_coords = mesh.texcoords.get(i)
+                    .cloned()
+                    .unwrap_or(Vec2::new(0.0, 0.0));
+
+                Vertex::new(position, normal, tex_coords)
+            }).collect();
+
+            indexed_mesh.append(vertices, &mesh.indices, mesh.material);
+        }
+
+        indexed_mesh
+    }
+}
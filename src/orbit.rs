@@ -0,0 +1,61 @@
+use nalgebra_glm::Vec3;
+use std::f32::consts::{PI, TAU};
+
+const KEPLER_ITERATIONS: usize = 8;
+const KEPLER_TOLERANCE: f32 = 1e-6;
+
+// Keplerian elements; angles in radians. The orbit lies in the XY plane before the
+// periapsis rotation, and inclination tilts it around the X axis.
+#[derive(Debug, Clone, Copy)]
+pub struct Orbit {
+    pub semi_major_axis: f32,
+    pub eccentricity: f32,
+    pub inclination: f32,
+    pub argument_of_periapsis: f32,
+    // Mean anomaly at time zero
+    pub phase: f32,
+    // Mean anomaly gained per second
+    pub mean_motion: f32,
+}
+
+impl Orbit {
+    pub fn position(&self, time: f32) -> Vec3 {
+        let mean_anomaly = (self.phase + time * self.mean_motion + PI).rem_euclid(TAU) - PI;
+        let eccentric_anomaly = solve_kepler(mean_anomaly, self.eccentricity);
+
+        // Position in the orbital plane, with the focus at the origin and periapsis on +X
+        let a = self.semi_major_axis;
+        let b = a * (1.0 - self.eccentricity * self.eccentricity).sqrt();
+        let px = a * (eccentric_anomaly.cos() - self.eccentricity);
+        let py = b * eccentric_anomaly.sin();
+
+        let (sin_w, cos_w) = self.argument_of_periapsis.sin_cos();
+        let x = px * cos_w - py * sin_w;
+        let y = px * sin_w + py * cos_w;
+
+        let (sin_i, cos_i) = self.inclination.sin_cos();
+        Vec3::new(x, y * cos_i, y * sin_i)
+    }
+
+    // Changes the speed without the body jumping: the phase absorbs the difference at `time`
+    pub fn set_mean_motion(&mut self, mean_motion: f32, time: f32) {
+        self.phase += time * (self.mean_motion - mean_motion);
+        self.mean_motion = mean_motion;
+    }
+}
+
+// Solves M = E - e sin(E) for the eccentric anomaly E with Newton's method
+pub fn solve_kepler(mean_anomaly: f32, eccentricity: f32) -> f32 {
+    // Highly eccentric orbits converge more reliably starting from pi
+    let mut eccentric_anomaly = if eccentricity < 0.8 { mean_anomaly } else { PI.copysign(mean_anomaly) };
+
+    for _ in 0..KEPLER_ITERATIONS {
+        let error = eccentric_anomaly - eccentricity * eccentric_anomaly.sin() - mean_anomaly;
+        let delta = error / (1.0 - eccentricity * eccentric_anomaly.cos());
+        eccentric_anomaly -= delta;
+        if delta.abs() < KEPLER_TOLERANCE {
+            break;
+        }
+    }
+    eccentric_anomaly
+}
@@ -0,0 +1,53 @@
+use std::time::{Duration, Instant};
+
+// Frame rate caps cycled at runtime; None renders as fast as possible
+const TARGETS: [Option<u32>; 3] = [Some(60), Some(30), None];
+
+pub struct FramePacer {
+    target: Option<u32>,
+    frame_start: Instant,
+}
+
+impl FramePacer {
+    pub fn new() -> Self {
+        Self::with_target(TARGETS[0])
+    }
+
+    pub fn with_target(target: Option<u32>) -> Self {
+        FramePacer {
+            target,
+            frame_start: Instant::now(),
+        }
+    }
+
+    pub fn target_fps(&self) -> Option<u32> {
+        self.target
+    }
+
+    // A cap that isn't one of the presets moves on to the first of them
+    pub fn cycle(&mut self) -> Option<u32> {
+        let next = TARGETS.iter()
+            .position(|target| *target == self.target)
+            .map_or(0, |index| (index + 1) % TARGETS.len());
+        self.target = TARGETS[next];
+        self.target
+    }
+
+    // Sleeps only for whatever is left of the target interval after this frame's work
+    pub fn wait(&mut self) {
+        if let Some(fps) = self.target_fps() {
+            let interval = Duration::from_secs_f32(1.0 / fps as f32);
+            let elapsed = self.frame_start.elapsed();
+            if elapsed < interval {
+                std::thread::sleep(interval - elapsed);
+            }
+        }
+        self.frame_start = Instant::now();
+    }
+}
+
+impl Default for FramePacer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,124 @@
+use rand::Rng;
+use rand::rngs::StdRng;
+use nalgebra_glm::Vec3;
+use crate::color::Color;
+use crate::create_rng;
+
+const RNG_SALT: u64 = 1;
+
+pub struct Particle {
+    pub position: Vec3,
+    pub velocity: Vec3,
+    pub age: f32,
+    pub lifetime: f32,
+    pub size: f32,
+    pub start_color: Color,
+    pub end_color: Color,
+    // Point the particle is pulled towards and the strength of the pull
+    pub attractor: Option<(Vec3, f32)>,
+}
+
+impl Particle {
+    pub fn progress(&self) -> f32 {
+        (self.age / self.lifetime).min(1.0)
+    }
+
+    pub fn color(&self) -> Color {
+        // Fade towards black so additive blending makes old particles vanish
+        let progress = self.progress();
+        self.start_color.lerp_linear(&self.end_color, progress) * (1.0 - progress)
+    }
+}
+
+pub struct Emitter {
+    pub position: Vec3,
+    pub direction: Vec3,
+    pub spread: f32,
+    pub speed: f32,
+    pub rate: f32,
+    pub lifetime: f32,
+    pub size: f32,
+    pub start_color: Color,
+    pub end_color: Color,
+    pub attractor: Option<(Vec3, f32)>,
+    // Velocity of whatever carries the emitter, which new particles start with
+    pub carrier_velocity: Vec3,
+    accumulator: f32,
+}
+
+impl Emitter {
+    pub fn new(rate: f32, lifetime: f32, size: f32, start_color: Color, end_color: Color) -> Self {
+        Emitter {
+            position: Vec3::zeros(),
+            direction: Vec3::new(0.0, 1.0, 0.0),
+            spread: 0.0,
+            speed: 0.0,
+            rate,
+            lifetime,
+            size,
+            start_color,
+            end_color,
+            attractor: None,
+            carrier_velocity: Vec3::zeros(),
+            accumulator: 0.0,
+        }
+    }
+
+    pub fn emit(&mut self, delta_time: f32, system: &mut ParticleSystem) {
+        self.accumulator += self.rate * delta_time;
+        let count = self.accumulator.floor();
+        self.accumulator -= count;
+        self.burst(count as usize, system);
+    }
+
+    pub fn burst(&self, count: usize, system: &mut ParticleSystem) {
+        let rng = &mut system.rng;
+
+        for _ in 0..count {
+            let jitter = Vec3::new(
+                rng.gen_range(-1.0..=1.0),
+                rng.gen_range(-1.0..=1.0),
+                rng.gen_range(-1.0..=1.0),
+            ) * self.spread;
+            let direction = (self.direction + jitter).try_normalize(f32::EPSILON).unwrap_or(self.direction);
+
+            system.particles.push(Particle {
+                position: self.position,
+                velocity: self.carrier_velocity + direction * self.speed * rng.gen_range(0.7..=1.3),
+                age: 0.0,
+                lifetime: self.lifetime * rng.gen_range(0.7..=1.3),
+                size: self.size,
+                start_color: self.start_color,
+                end_color: self.end_color,
+                attractor: self.attractor,
+            });
+        }
+    }
+}
+
+pub struct ParticleSystem {
+    pub particles: Vec<Particle>,
+    // Shared by every emitter feeding the system
+    rng: StdRng,
+}
+
+impl ParticleSystem {
+    pub fn new(seed: Option<u64>) -> Self {
+        ParticleSystem { particles: Vec::new(), rng: create_rng(seed, RNG_SALT) }
+    }
+
+    pub fn update(&mut self, delta_time: f32) {
+        for particle in &mut self.particles {
+            if let Some((point, strength)) = particle.attractor {
+                let pull = point - particle.position;
+                if let Some(direction) = pull.try_normalize(f32::EPSILON) {
+                    particle.velocity += direction * strength * delta_time;
+                }
+            }
+            particle.position += particle.velocity * delta_time;
+            particle.age += delta_time;
+        }
+
+        self.particles.retain(|particle| particle.age < particle.lifetime);
+    }
+}
@@ -0,0 +1,89 @@
+use std::f32::consts::PI;
+use nalgebra_glm::Vec3;
+use crate::color::Color;
+use crate::fragment::Fragment;
+use crate::shaders::environment;
+use crate::Uniforms;
+
+// Light reaching the surface from a sun of intensity 1, scaled so a white Lambertian surface facing it is white
+const SUN_RADIANCE: f32 = PI;
+// Stands in for light bounced off everything else, so night sides aren't pure black
+const AMBIENT: f32 = 0.03;
+// Reflectance of non-metals seen head-on
+const DIELECTRIC_F0: f32 = 0.04;
+
+// Surface description that PBR shaders produce per fragment; the scene file sets each
+// object's base values and shaders vary them across the surface. The emissive color
+// isn't part of shade(), shaders hand it to the renderer through PlanetShader::emission
+#[derive(Debug, Clone, Copy)]
+pub struct Material {
+    pub albedo: Color,
+    pub metallic: f32,
+    pub roughness: f32,
+    pub emissive: Color,
+}
+
+impl Default for Material {
+    fn default() -> Self {
+        Material {
+            albedo: Color::new(255, 255, 255),
+            metallic: 0.0,
+            roughness: 0.8,
+            emissive: Color::black(),
+        }
+    }
+}
+
+// Cook-Torrance lighting summed over the suns (GGX distribution, Smith geometry, Schlick Fresnel),
+// plus the surroundings reflected off metals and an ambient floor
+pub fn shade(material: &Material, fragment: &Fragment, uniforms: &Uniforms) -> Color {
+    let albedo = material.albedo.to_vec3();
+    let metallic = material.metallic.clamp(0.0, 1.0);
+    // Perfectly smooth surfaces would turn the sun into a single invisible point
+    let roughness = material.roughness.clamp(0.05, 1.0);
+
+    let normal = fragment.normal;
+    let view = (uniforms.camera_position - fragment.world_position).try_normalize(f32::EPSILON).unwrap_or(normal);
+    let n_dot_v = normal.dot(&view).max(1e-4);
+
+    let f0 = Vec3::repeat(DIELECTRIC_F0).lerp(&albedo, metallic);
+    let fresnel = |cos: f32| f0 + (Vec3::repeat(1.0) - f0) * (1.0 - cos).powi(5);
+
+    let mut color = albedo * (AMBIENT * (1.0 - metallic));
+
+    for source in uniforms.lights {
+        let light = source.direction(&fragment.world_position);
+        let n_dot_l = normal.dot(&light).max(0.0);
+        if n_dot_l <= 0.0 {
+            continue;
+        }
+        let half = (light + view).try_normalize(f32::EPSILON).unwrap_or(normal);
+        let n_dot_h = normal.dot(&half).max(0.0);
+        let v_dot_h = view.dot(&half).max(0.0);
+
+        let alpha = roughness * roughness;
+        let alpha_squared = alpha * alpha;
+        let denominator = n_dot_h * n_dot_h * (alpha_squared - 1.0) + 1.0;
+        let distribution = alpha_squared / (PI * denominator * denominator);
+
+        let k = (roughness + 1.0) * (roughness + 1.0) / 8.0;
+        let geometry = n_dot_v / (n_dot_v * (1.0 - k) + k) * n_dot_l / (n_dot_l * (1.0 - k) + k);
+
+        let f = fresnel(v_dot_h);
+        let specular = f * (distribution * geometry / (4.0 * n_dot_v * n_dot_l).max(1e-4));
+        let diffuse = (Vec3::repeat(1.0) - f).component_mul(&albedo) * ((1.0 - metallic) / PI);
+
+        let radiance = source.radiance().to_vec3() * (SUN_RADIANCE * n_dot_l);
+        color += (diffuse + specular).component_mul(&radiance);
+    }
+
+    // Rough surfaces blur their reflections away, which a single sample approximates by fading it
+    if metallic > 0.0 {
+        let reflected = normal * (2.0 * normal.dot(&view)) - view;
+        let glossiness = (1.0 - roughness) * (1.0 - roughness);
+        let surroundings = environment(&reflected, &fragment.world_position, uniforms).to_vec3();
+        color += surroundings.component_mul(&fresnel(n_dot_v)) * (metallic * glossiness);
+    }
+
+    Color::from_vec3(color)
+}
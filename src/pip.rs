@@ -0,0 +1,77 @@
+use nalgebra_glm::Vec3;
+use crate::camera::Camera;
+use crate::framebuffer::{Framebuffer, Rect};
+use crate::renderer::Renderer;
+use crate::scene::Scene;
+use crate::shaders::ShaderRegistry;
+use crate::text::draw_text;
+
+const WIDTH: usize = 200;
+const HEIGHT: usize = 150;
+const MARGIN: usize = 10;
+// Camera distance in multiples of the planet's scale; fills most of the inset
+const CLOSEUP_DISTANCE: f32 = 1.8;
+
+// Closeup of the selected planet in the bottom-right corner, rendered at full
+// resolution into its own buffer rather than scaled down from the main view
+pub struct PictureInPicture {
+    pub visible: bool,
+    target: Framebuffer,
+}
+
+impl PictureInPicture {
+    pub fn new() -> Self {
+        PictureInPicture { visible: false, target: Framebuffer::new(WIDTH, HEIGHT) }
+    }
+
+    fn rect(width: usize, height: usize) -> Option<Rect> {
+        if width < WIDTH + MARGIN * 2 || height < HEIGHT + MARGIN * 2 {
+            return None;
+        }
+        Some(Rect { x: width - WIDTH - MARGIN, y: height - HEIGHT - MARGIN, width: WIDTH, height: HEIGHT })
+    }
+
+    // Looks at the planet from partway round towards the sun, so the inset shows its day side and terminator
+    fn camera(scene: &Scene, index: usize, time: f32) -> Camera {
+        let position = scene.position(index, time);
+        let to_sun = scene.sun()
+            .filter(|&sun| sun != index)
+            .and_then(|sun| (scene.position(sun, time) - position).try_normalize(f32::EPSILON))
+            .unwrap_or(Vec3::x());
+        let side = to_sun.cross(&Vec3::z()).try_normalize(f32::EPSILON).unwrap_or(Vec3::y());
+        let direction = (to_sun + side + Vec3::z() * 0.4).normalize();
+
+        let eye = position + direction * scene.objects[index].scale * CLOSEUP_DISTANCE;
+        Camera::new(eye, position, Vec3::z())
+    }
+
+    pub fn draw(&mut self, renderer: &mut Renderer, scene: &Scene, shaders: &ShaderRegistry, time: f32, selected: usize) {
+        if !self.visible || selected >= scene.objects.len() {
+            return;
+        }
+        let Some(rect) = Self::rect(renderer.framebuffer.width, renderer.framebuffer.height) else {
+            return;
+        };
+
+        // The inset's own pass would otherwise replace the main view's numbers in the stats overlay
+        let stats = renderer.stats;
+        let camera = Self::camera(scene, selected, time);
+        self.target.presentation = renderer.framebuffer.presentation;
+        renderer.render_to(&mut self.target, |renderer| {
+            renderer.render_frame(scene, shaders, &camera, time);
+            renderer.framebuffer.resolve();
+            draw_text(&mut renderer.framebuffer, &scene.objects[selected].name, 4, 4, 1, 0xFFFF00);
+        });
+        renderer.stats = stats;
+        renderer.framebuffer.blit(&self.target, rect.x, rect.y);
+
+        let border = Rect { x: rect.x - 1, y: rect.y - 1, width: rect.width + 2, height: rect.height + 2 };
+        renderer.framebuffer.stroke_rect(border, 0x606080);
+    }
+}
+
+impl Default for PictureInPicture {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,99 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+use crate::renderer::RenderStats;
+
+pub const STAGES: [&str; 5] = ["vertex", "assembly", "raster", "fragment", "write"];
+
+// What reading the clock costs, measured once; short sampled spans subtract it so the
+// timer doesn't show up as the work it times
+pub fn timer_overhead() -> Duration {
+    static OVERHEAD: OnceLock<Duration> = OnceLock::new();
+    *OVERHEAD.get_or_init(|| {
+        const SAMPLES: u32 = 1000;
+        let start = Instant::now();
+        for _ in 0..SAMPLES {
+            std::hint::black_box(Instant::now());
+        }
+        start.elapsed() / SAMPLES
+    })
+}
+
+// Everything one draw (a body, a cloud shell, the belt, the ship) cost this frame
+pub struct ObjectProfile {
+    pub name: String,
+    pub start: Instant,
+    pub stats: RenderStats,
+}
+
+// Streams events in chrome://tracing's JSON array format, one frame at a time
+pub struct TraceWriter {
+    path: String,
+    out: BufWriter<File>,
+    origin: Instant,
+    first: bool,
+}
+
+impl TraceWriter {
+    pub fn create(path: &str) -> Result<Self, String> {
+        let file = File::create(path).map_err(|e| format!("Failed to create {}: {}", path, e))?;
+        let mut out = BufWriter::new(file);
+        out.write_all(b"[\n").map_err(|e| format!("Failed to write {}: {}", path, e))?;
+        Ok(TraceWriter { path: path.to_string(), out, origin: Instant::now(), first: true })
+    }
+
+    // The stages of a draw interleave triangle by triangle, so each object's span holds its
+    // per-stage totals laid end to end rather than when they actually ran
+    pub fn frame(&mut self, frame: u32, start: Instant, duration: Duration, objects: &[ObjectProfile]) -> Result<(), String> {
+        self.event(&format!("frame {}", frame), "frame", start, duration, "")?;
+        for object in objects {
+            let stage_times = object.stats.stage_times();
+            let total: Duration = stage_times.iter().sum();
+            let args = format!(
+                "\"triangles\":{},\"shaded_fragments\":{}",
+                object.stats.triangles, object.stats.shaded_fragments
+            );
+            self.event(&object.name, "object", object.start, total, &args)?;
+
+            let mut stage_start = object.start;
+            for (name, time) in STAGES.iter().zip(stage_times) {
+                self.event(name, "stage", stage_start, time, "")?;
+                stage_start += time;
+            }
+        }
+        Ok(())
+    }
+
+    fn event(&mut self, name: &str, category: &str, start: Instant, duration: Duration, args: &str) -> Result<(), String> {
+        let separator = if self.first { "" } else { ",\n" };
+        self.first = false;
+        let timestamp = start.saturating_duration_since(self.origin).as_secs_f64() * 1e6;
+        write!(
+            self.out,
+            "{}{{\"name\":\"{}\",\"cat\":\"{}\",\"ph\":\"X\",\"ts\":{:.3},\"dur\":{:.3},\"pid\":1,\"tid\":1,\"args\":{{{}}}}}",
+            separator,
+            escape(name),
+            category,
+            timestamp,
+            duration.as_secs_f64() * 1e6,
+            args
+        ).map_err(|e| format!("Failed to write {}: {}", self.path, e))
+    }
+}
+
+// Closing the array on drop covers every way out of the render loop; chrome://tracing
+// reads the file without the closing bracket too, should this fail
+impl Drop for TraceWriter {
+    fn drop(&mut self) {
+        let _ = self.out.write_all(b"\n]\n").and_then(|_| self.out.flush());
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.chars().flat_map(|c| match c {
+        '"' | '\\' => vec!['\\', c],
+        c if c.is_control() => Vec::new(),
+        c => vec![c],
+    }).collect()
+}
@@ -0,0 +1,60 @@
+use std::fs;
+use std::time::{SystemTime, UNIX_EPOCH};
+use crate::framebuffer::Framebuffer;
+
+pub struct Recorder {
+    pub timestep: f32,
+    directory: Option<String>,
+    frame_index: usize,
+}
+
+impl Recorder {
+    pub fn new(frames_per_second: u32) -> Self {
+        Recorder {
+            timestep: 1.0 / frames_per_second as f32,
+            directory: None,
+            frame_index: 0,
+        }
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.directory.is_some()
+    }
+
+    // Starts a recording in a new timestamped directory, or stops the current one and
+    // returns its directory and frame count
+    pub fn toggle(&mut self) -> Result<Option<(String, usize)>, String> {
+        if self.is_recording() {
+            return Ok(self.stop());
+        }
+
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_millis())
+            .unwrap_or(0);
+        let directory = format!("recordings/recording_{}", timestamp);
+        self.start(&directory).map(|()| None)
+    }
+
+    pub fn start(&mut self, directory: &str) -> Result<(), String> {
+        fs::create_dir_all(directory).map_err(|e| format!("Failed to create {}: {}", directory, e))?;
+        self.directory = Some(directory.to_string());
+        self.frame_index = 0;
+        Ok(())
+    }
+
+    pub fn stop(&mut self) -> Option<(String, usize)> {
+        self.directory.take().map(|directory| (directory, self.frame_index))
+    }
+
+    pub fn capture(&mut self, framebuffer: &Framebuffer) -> Result<(), String> {
+        let Some(directory) = &self.directory else {
+            return Ok(());
+        };
+
+        let path = format!("{}/frame_{:05}.png", directory, self.frame_index);
+        framebuffer.save_png(&path)?;
+        self.frame_index += 1;
+        Ok(())
+    }
+}
@@ -0,0 +1,1167 @@
+use nalgebra_glm::{Vec3, Vec4, Mat4, look_at, perspective, ortho};
+use fastnoise_lite::FastNoiseLite;
+use rand::rngs::StdRng;
+use std::f32::consts::PI;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::ops::AddAssign;
+use std::time::{Duration, Instant};
+use crate::framebuffer::{object_id_color, Framebuffer, NO_OBJECT};
+use crate::mesh::{self, IndexedMesh, LodLevel};
+use crate::frustum::Frustum;
+use crate::vertex::Vertex;
+use crate::fragment::Fragment;
+use crate::camera::Camera;
+use crate::triangle::triangle;
+use crate::shaders::{MaterialShader, PlanetShader, ShaderRegistry};
+use crate::material::Material;
+use crate::pbr;
+use crate::particles::ParticleSystem;
+use crate::effects::{Beam, Coma, Effects};
+use crate::billboard::{Billboard, BlendMode, Sprite, draw_sprite};
+use crate::texture::Texture;
+use crate::color::Color;
+use crate::scene::Scene;
+use crate::ship::Ship;
+use crate::line::line;
+use crate::text::{draw_text, text_width, GLYPH_SIZE};
+use crate::profiler::{timer_overhead, ObjectProfile};
+use crate::{Light, Uniforms, create_noise, create_rng, derive_seed};
+
+const MAP_VIEW_HALF_HEIGHT: f32 = 8.0;
+const RNG_SALT: u64 = 4;
+const WIREFRAME_COLOR: Color = Color::new(220, 220, 220);
+// Cloud and other transparent shells, to tell them from the body under them
+const WIREFRAME_SHELL_COLOR: Color = Color::new(80, 200, 255);
+// View distance that fades to black in the depth view, about the span of the inner system
+const DEPTH_VIEW_RANGE: f32 = 40.0;
+// Displaced vertices (solar surface, waves) can reach slightly past the undisplaced mesh
+const BOUNDS_MARGIN: f32 = 1.1;
+
+// Timing every triangle and fragment would cost more than the work itself, so one in
+// this many is timed and the total extrapolated from them
+const TIMING_INTERVAL: usize = 32;
+
+// Lens flare elements as (position along the sun-to-center axis, size in pixels, color)
+const FLARE_ELEMENTS: [(f32, f32, u32); 6] = [
+    (0.0, 90.0, 0x553311),
+    (0.4, 14.0, 0x224466),
+    (0.7, 28.0, 0x332211),
+    (1.1, 10.0, 0x446622),
+    (1.5, 40.0, 0x221133),
+    (2.0, 18.0, 0x553322),
+];
+
+// What the fragment stage writes: the shaded scene, or one of the values feeding it
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DebugView {
+    Final,
+    Depth,
+    Normals,
+    Uvs,
+    ObjectIds,
+}
+
+impl DebugView {
+    pub fn next(self) -> Self {
+        match self {
+            DebugView::Final => DebugView::Depth,
+            DebugView::Depth => DebugView::Normals,
+            DebugView::Normals => DebugView::Uvs,
+            DebugView::Uvs => DebugView::ObjectIds,
+            DebugView::ObjectIds => DebugView::Final,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            DebugView::Final => "final color",
+            DebugView::Depth => "depth",
+            DebugView::Normals => "normals",
+            DebugView::Uvs => "UVs",
+            DebugView::ObjectIds => "object IDs",
+        }
+    }
+
+    // Replaces the shader's output: distance from the camera (white when close), the
+    // world-space normal or the texture coordinates mapped to RGB, or the object's id color
+    fn fragment_color(self, fragment: &Fragment, uniforms: &Uniforms, object: u32) -> Option<Color> {
+        let color = match self {
+            DebugView::Final => return None,
+            DebugView::Depth => {
+                let distance = (fragment.world_position - uniforms.camera_position).magnitude();
+                let gray = (1.0 - distance / DEPTH_VIEW_RANGE).clamp(0.0, 1.0);
+                Vec3::new(gray, gray, gray)
+            }
+            DebugView::Normals => fragment.normal.normalize().add_scalar(1.0) * 0.5,
+            DebugView::Uvs => Vec3::new(fragment.tex_coords.x.fract(), fragment.tex_coords.y.fract(), 0.0),
+            DebugView::ObjectIds => return Some(Color::from_hex(object_id_color(object))),
+        };
+        Some(Color::from_vec3(color))
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct RenderStats {
+    pub triangles: usize,
+    pub fragments: usize,
+    pub vertex_cache_hits: usize,
+    pub shaded_fragments: usize,
+    pub culled_objects: usize,
+    pub occluded_objects: usize,
+    // Tiles re-blitted from the previous frame instead of cleared and redrawn
+    pub clean_tiles: usize,
+    pub vertex_time: Duration,
+    pub assembly_time: Duration,
+    pub raster_time: Duration,
+    pub fragment_time: Duration,
+    // Depth-tested color writes and blending, after the fragment shader
+    pub write_time: Duration,
+}
+
+impl AddAssign for RenderStats {
+    fn add_assign(&mut self, other: RenderStats) {
+        self.triangles += other.triangles;
+        self.fragments += other.fragments;
+        self.vertex_cache_hits += other.vertex_cache_hits;
+        self.shaded_fragments += other.shaded_fragments;
+        self.culled_objects += other.culled_objects;
+        self.occluded_objects += other.occluded_objects;
+        self.clean_tiles += other.clean_tiles;
+        self.vertex_time += other.vertex_time;
+        self.assembly_time += other.assembly_time;
+        self.raster_time += other.raster_time;
+        self.fragment_time += other.fragment_time;
+        self.write_time += other.write_time;
+    }
+}
+
+impl RenderStats {
+    // In the order of profiler::STAGES
+    pub fn stage_times(&self) -> [Duration; 5] {
+        [self.vertex_time, self.assembly_time, self.raster_time, self.fragment_time, self.write_time]
+    }
+}
+
+struct CachedVertices {
+    key: u64,
+    vertices: Vec<Vertex>,
+}
+
+pub struct Renderer {
+    pub framebuffer: Framebuffer,
+    pub stats: RenderStats,
+    // This frame's draws with what each of them cost, in draw order
+    pub profile: Vec<ObjectProfile>,
+    // Meshes are drawn as their triangle edges instead of shaded
+    pub wireframe: bool,
+    pub debug_view: DebugView,
+    lods: Vec<LodLevel>,
+    // From mesh::next_generation(), whenever `lods` is set
+    mesh_generation: u64,
+    mesh_radius: f32,
+    vertex_cache: Vec<CachedVertices>,
+    materials: Vec<MaterialShader>,
+    instance_matrices: Vec<Mat4>,
+    instance_vertices: Vec<Vertex>,
+    draw_order: Vec<usize>,
+    lights: Vec<Light>,
+    particle_texture: Texture,
+    // Shared by everything that has no per-object noise: clouds, the belt and hyperspace
+    noise: FastNoiseLite,
+    // Hyperspace and warp streaks
+    rng: StdRng,
+}
+
+impl Renderer {
+    pub fn new(width: usize, height: usize, mesh: IndexedMesh) -> Self {
+        Self::with_lods(width, height, vec![LodLevel { min_screen_radius: 0.0, mesh }])
+    }
+
+    // Levels are ordered from most to least detailed
+    pub fn with_lods(width: usize, height: usize, lods: Vec<LodLevel>) -> Self {
+        let mesh_radius = lods[0].mesh.vertices.iter()
+            .map(|vertex| vertex.position.magnitude())
+            .fold(0.0, f32::max);
+
+        Renderer {
+            framebuffer: Framebuffer::new(width, height),
+            stats: RenderStats::default(),
+            profile: Vec::new(),
+            wireframe: false,
+            debug_view: DebugView::Final,
+            lods,
+            mesh_generation: mesh::next_generation(),
+            mesh_radius,
+            vertex_cache: Vec::new(),
+            materials: Vec::new(),
+            instance_matrices: Vec::new(),
+            instance_vertices: Vec::new(),
+            draw_order: Vec::new(),
+            lights: Vec::new(),
+            particle_texture: Texture::soft_circle(32),
+            noise: create_noise(),
+            rng: create_rng(None, RNG_SALT),
+        }
+    }
+
+    // Makes the shared noise and the streaks follow the run's seed
+    pub fn set_seed(&mut self, seed: u64) {
+        self.noise = create_noise();
+        self.noise.seed = derive_seed(seed, self.noise.seed as u64) as i32;
+        self.rng = create_rng(Some(seed), RNG_SALT);
+    }
+
+    // Projection and viewport are rebuilt from the framebuffer size every frame, so a new buffer is enough
+    pub fn resize(&mut self, width: usize, height: usize) {
+        if width != self.framebuffer.width || height != self.framebuffer.height {
+            let mut framebuffer = Framebuffer::new(width, height);
+            framebuffer.presentation = self.framebuffer.presentation;
+            self.framebuffer = framebuffer;
+        }
+    }
+
+    // Stats and the profile add up every pass until this is called, normally once a frame
+    pub fn reset_stats(&mut self) {
+        self.stats = RenderStats::default();
+        self.profile.clear();
+    }
+
+    // Runs a pass against an offscreen buffer instead of the screen; everything that reads
+    // `self.framebuffer` (projection, depth tests, overlays) follows the swap
+    pub fn render_to<R>(&mut self, target: &mut Framebuffer, pass: impl FnOnce(&mut Renderer) -> R) -> R {
+        std::mem::swap(&mut self.framebuffer, target);
+        let result = pass(self);
+        std::mem::swap(&mut self.framebuffer, target);
+        result
+    }
+
+    // The most detailed level of the body mesh
+    pub fn mesh(&self) -> &IndexedMesh {
+        &self.lods[0].mesh
+    }
+
+    pub fn mesh_generation(&self) -> u64 {
+        self.mesh_generation
+    }
+
+    pub fn set_materials(&mut self, materials: Vec<Material>) {
+        self.materials = materials.into_iter().map(MaterialShader::new).collect();
+    }
+
+    // View, projection and viewport matrices for the camera in the current viewport
+    pub fn frame_matrices(&self, camera: &Camera) -> (Mat4, Mat4, Mat4) {
+        let viewport = self.framebuffer.viewport();
+        let width = viewport.width as f32;
+        let height = viewport.height as f32;
+
+        let view_matrix = create_view_matrix(camera.eye, camera.center, camera.up);
+        let projection_matrix = if camera.map_view {
+            create_orthographic_matrix(width, height, MAP_VIEW_HALF_HEIGHT)
+        } else {
+            create_perspective_matrix(width, height, camera.fov, camera.near, camera.far)
+        };
+        let viewport_matrix = create_viewport_matrix(viewport.x as f32, viewport.y as f32, width, height);
+
+        (view_matrix, projection_matrix, viewport_matrix)
+    }
+
+    fn screen_radius(&self, camera: &Camera, position: Vec3, radius: f32) -> f32 {
+        let half_height = self.framebuffer.viewport().height as f32 / 2.0;
+        if camera.map_view {
+            return radius * half_height / MAP_VIEW_HALF_HEIGHT;
+        }
+
+        let distance = (position - camera.eye).magnitude();
+        if distance <= radius {
+            return f32::INFINITY;
+        }
+        radius / distance * half_height / (camera.fov.to_radians() / 2.0).tan()
+    }
+
+    // Screen rectangle (min_x, min_y, max_x, max_y) and nearest depth of a bounding sphere,
+    // from the corners of its enclosing cube; None when part of it is behind the camera
+    fn screen_bounds(&self, view_projection: &Mat4, viewport_matrix: &Mat4, center: Vec3, radius: f32) -> Option<(f32, f32, f32, f32, f32)> {
+        let mut bounds = (f32::INFINITY, f32::INFINITY, f32::NEG_INFINITY, f32::NEG_INFINITY, f32::NEG_INFINITY);
+        for corner in 0..8 {
+            let offset = Vec3::new(
+                if corner & 1 == 0 { -radius } else { radius },
+                if corner & 2 == 0 { -radius } else { radius },
+                if corner & 4 == 0 { -radius } else { radius },
+            );
+            let point = center + offset;
+            let clip = view_projection * Vec4::new(point.x, point.y, point.z, 1.0);
+            if clip.w <= 0.0 {
+                return None;
+            }
+
+            let screen = viewport_matrix * Vec4::new(clip.x / clip.w, clip.y / clip.w, clip.z / clip.w, 1.0);
+            bounds.0 = bounds.0.min(screen.x);
+            bounds.1 = bounds.1.min(screen.y);
+            bounds.2 = bounds.2.max(screen.x);
+            bounds.3 = bounds.3.max(screen.y);
+            bounds.4 = bounds.4.max(screen.z);
+        }
+        Some(bounds)
+    }
+
+    fn select_lod(&self, screen_radius: f32) -> usize {
+        self.lods.iter()
+            .position(|level| screen_radius >= level.min_screen_radius)
+            .unwrap_or(self.lods.len() - 1)
+    }
+
+    pub fn project_to_screen(&self, camera: &Camera, world_position: Vec3) -> Option<Vec3> {
+        let (view_matrix, projection_matrix, viewport_matrix) = self.frame_matrices(camera);
+        let clip = projection_matrix * view_matrix * Vec4::new(world_position.x, world_position.y, world_position.z, 1.0);
+        if clip.w <= 0.0 {
+            return None;
+        }
+
+        let ndc = Vec4::new(clip.x / clip.w, clip.y / clip.w, clip.z / clip.w, 1.0);
+        let screen = viewport_matrix * ndc;
+        Some(Vec3::new(screen.x, screen.y, screen.z))
+    }
+
+    pub fn pick(&self, scene: &Scene, camera: &Camera, time: f32, screen_x: f32, screen_y: f32) -> Option<usize> {
+        let (view_matrix, projection_matrix, viewport_matrix) = self.frame_matrices(camera);
+        let inverse_viewport = viewport_matrix.try_inverse()?;
+        let inverse_view_projection = (projection_matrix * view_matrix).try_inverse()?;
+
+        let ndc = inverse_viewport * Vec4::new(screen_x, screen_y, 0.0, 1.0);
+        let unproject = |z: f32| {
+            let world = inverse_view_projection * Vec4::new(ndc.x, ndc.y, z, 1.0);
+            Vec3::new(world.x / world.w, world.y / world.w, world.z / world.w)
+        };
+        let origin = unproject(1.0);
+        let direction = (unproject(0.0) - origin).normalize();
+
+        let mut closest: Option<(usize, f32)> = None;
+        for (index, object) in scene.objects.iter().enumerate() {
+            let radius = object.scale * self.mesh_radius;
+            let to_center = scene.position(index, time) - origin;
+
+            // Ray-sphere intersection against the body's bounding sphere
+            let projection = to_center.dot(&direction);
+            let distance_squared = to_center.magnitude_squared() - projection * projection;
+            if distance_squared > radius * radius {
+                continue;
+            }
+
+            let hit = projection - (radius * radius - distance_squared).sqrt();
+            if hit > 0.0 && !matches!(closest, Some((_, nearest)) if nearest <= hit) {
+                closest = Some((index, hit));
+            }
+        }
+
+        closest.map(|(index, _)| index)
+    }
+
+    pub fn draw_labels(&mut self, scene: &Scene, camera: &Camera, time: f32, selected: usize) {
+        let camera_up = {
+            let forward = (camera.center - camera.eye).normalize();
+            let right = forward.cross(&camera.up).normalize();
+            right.cross(&forward).normalize()
+        };
+        let viewport = self.framebuffer.viewport();
+
+        for (index, object) in scene.objects.iter().enumerate() {
+            // Anchor just outside the silhouette so the body itself never hides its label
+            let top = scene.position(index, time) + camera_up * object.scale * self.mesh_radius * 1.25;
+            let Some(anchor) = self.project_to_screen(camera, top) else {
+                continue;
+            };
+
+            let width = text_width(&object.name, 1) as f32;
+            let x = anchor.x - width / 2.0;
+            let y = anchor.y - GLYPH_SIZE as f32 - 4.0;
+            if x < viewport.x as f32 || y < viewport.y as f32
+                || x + width >= (viewport.x + viewport.width) as f32 || anchor.y >= (viewport.y + viewport.height) as f32 {
+                continue;
+            }
+
+            // Hide labels whose anchor sits behind something already drawn
+            let anchor_index = anchor.y as usize * self.framebuffer.width + anchor.x as usize;
+            if self.framebuffer.zbuffer[anchor_index] > anchor.z {
+                continue;
+            }
+
+            let color = if index == selected { 0xFFFF00 } else { 0xAAAAAA };
+            draw_text(&mut self.framebuffer, &object.name, x as usize, y as usize, 1, color);
+        }
+    }
+
+    // Polyline through world-space points that fades out towards its end
+    pub fn draw_path(&mut self, camera: &Camera, points: &[Vec3], color: Color) {
+        let (width, height) = (self.framebuffer.width as f32, self.framebuffer.height as f32);
+        let segments = points.len().saturating_sub(1).max(1) as f32;
+
+        for (i, pair) in points.windows(2).enumerate() {
+            let (Some(start), Some(end)) = (self.project_to_screen(camera, pair[0]), self.project_to_screen(camera, pair[1])) else {
+                continue;
+            };
+            // Segments reaching far off screen would rasterize thousands of invisible pixels
+            let on_screen = |p: &Vec3| p.x.abs() < width * 4.0 && p.y.abs() < height * 4.0;
+            if !on_screen(&start) || !on_screen(&end) {
+                continue;
+            }
+
+            let alpha = 1.0 - i as f32 / segments;
+            let framebuffer = &mut self.framebuffer;
+            line(start, end, |x, y, depth| {
+                if x >= 0 && y >= 0 {
+                    framebuffer.blend_color(x as usize, y as usize, depth, color, alpha);
+                }
+            });
+        }
+    }
+
+    pub fn draw_billboard(&mut self, camera: &Camera, billboard: &Billboard) {
+        if let Some((center, half_size)) = self.billboard_extent(camera, billboard) {
+            draw_sprite(&mut self.framebuffer, &billboard.sprite, center, half_size);
+        }
+    }
+
+    // Screen center and half size of a billboard, or None when it is behind the camera
+    fn billboard_extent(&self, camera: &Camera, billboard: &Billboard) -> Option<(Vec3, f32)> {
+        let center = self.project_to_screen(camera, billboard.position)?;
+        Some((center, self.screen_radius(camera, billboard.position, billboard.size)))
+    }
+
+    // Comas sit just in front of their nucleus so its near side doesn't hide the glow
+    pub fn draw_comas<'a>(&mut self, comas: impl Iterator<Item = &'a Coma>, camera: &Camera) {
+        for coma in comas {
+            let towards_camera = (camera.eye - coma.position).try_normalize(f32::EPSILON).unwrap_or(Vec3::zeros());
+            let billboard = Billboard {
+                position: coma.position + towards_camera * coma.radius,
+                size: coma.size,
+                sprite: Sprite {
+                    texture: Some(&self.particle_texture),
+                    color: coma.color,
+                    alpha: coma.brightness,
+                    blend: BlendMode::Additive,
+                },
+            };
+            if let Some((center, half_size)) = self.billboard_extent(camera, &billboard) {
+                draw_sprite(&mut self.framebuffer, &billboard.sprite, center, half_size);
+            }
+        }
+    }
+
+    pub fn draw_particles(&mut self, particles: &ParticleSystem, camera: &Camera) {
+        for particle in &particles.particles {
+            let Some(center) = self.project_to_screen(camera, particle.position) else {
+                continue;
+            };
+            let half_size = self.screen_radius(camera, particle.position, particle.size).clamp(1.0, 32.0);
+
+            let sprite = Sprite {
+                texture: Some(&self.particle_texture),
+                color: particle.color(),
+                alpha: 1.0,
+                blend: BlendMode::Additive,
+            };
+            draw_sprite(&mut self.framebuffer, &sprite, center, half_size);
+        }
+    }
+
+    pub fn draw_beam(&mut self, camera: &Camera, beam: &Beam) {
+        let (Some(from), Some(to)) = (self.project_to_screen(camera, beam.from), self.project_to_screen(camera, beam.to)) else {
+            return;
+        };
+        let half_width = self.screen_radius(camera, (beam.from + beam.to) * 0.5, beam.width).max(1.5);
+        // A wider faint halo around the beam's hot core
+        let glow = half_width * 3.0;
+
+        let min_x = (from.x.min(to.x) - glow).max(0.0) as usize;
+        let min_y = (from.y.min(to.y) - glow).max(0.0) as usize;
+        let max_x = (from.x.max(to.x) + glow).min(self.framebuffer.width as f32 - 1.0);
+        let max_y = (from.y.max(to.y) + glow).min(self.framebuffer.height as f32 - 1.0);
+        if max_x < 0.0 || max_y < 0.0 {
+            return;
+        }
+
+        let segment = to - from;
+        let length_squared = (segment.x * segment.x + segment.y * segment.y).max(f32::EPSILON);
+        let core = Color::new(220, 255, 220);
+
+        for y in min_y..=max_y as usize {
+            for x in min_x..=max_x as usize {
+                let px = x as f32 + 0.5 - from.x;
+                let py = y as f32 + 0.5 - from.y;
+                let t = ((px * segment.x + py * segment.y) / length_squared).clamp(0.0, 1.0);
+                let dx = px - segment.x * t;
+                let dy = py - segment.y * t;
+                let distance = (dx * dx + dy * dy).sqrt();
+                if distance > glow {
+                    continue;
+                }
+
+                let depth = from.z + segment.z * t;
+                let halo = 1.0 - distance / glow;
+                let mut color = beam.color * (halo * halo * beam.intensity);
+                if distance < half_width {
+                    color = color + core * ((1.0 - distance / half_width) * beam.intensity);
+                }
+                self.framebuffer.add_color(x, y, depth, color);
+            }
+        }
+    }
+
+    pub fn draw_lens_flare(&mut self, scene: &Scene, camera: &Camera, time: f32) {
+        let Some(sun) = scene.sun() else {
+            return;
+        };
+        let Some(position) = self.project_to_screen(camera, scene.position(sun, time)) else {
+            return;
+        };
+
+        let viewport = self.framebuffer.viewport();
+        let (left, top) = (viewport.x as f32, viewport.y as f32);
+        let (right, bottom) = (left + viewport.width as f32, top + viewport.height as f32);
+        let edge_distance = (position.x - left).min(position.y - top).min(right - position.x).min(bottom - position.y);
+        let edge_fade = (edge_distance / 60.0).clamp(0.0, 1.0);
+        if edge_fade <= 0.0 {
+            return;
+        }
+
+        // Fraction of a small grid around the sun's center not covered by another body
+        let mut samples = 0;
+        let mut visible = 0;
+        for dy in -2..=2 {
+            for dx in -2..=2 {
+                let x = position.x + dx as f32 * 3.0;
+                let y = position.y + dy as f32 * 3.0;
+                if x < 0.0 || y < 0.0 {
+                    continue;
+                }
+                samples += 1;
+                match self.framebuffer.object_at(x as usize, y as usize) {
+                    Some(id) if id as usize != sun => {}
+                    _ => visible += 1,
+                }
+            }
+        }
+        let visibility = if samples == 0 { 0.0 } else { visible as f32 / samples as f32 };
+
+        let intensity = edge_fade * visibility;
+        if intensity <= 0.0 {
+            return;
+        }
+
+        let screen_center = Vec3::new((left + right) / 2.0, (top + bottom) / 2.0, 0.0);
+        for (offset, size, color) in FLARE_ELEMENTS {
+            let sprite = Sprite {
+                texture: Some(&self.particle_texture),
+                color: Color::from_hex(color),
+                alpha: intensity,
+                blend: BlendMode::Additive,
+            };
+            // Flares sit on the lens, in front of everything
+            let mut center = position + (screen_center - position) * offset;
+            center.z = f32::INFINITY;
+            draw_sprite(&mut self.framebuffer, &sprite, center, size);
+        }
+    }
+
+    pub fn draw_effects(&mut self, effects: &Effects, scene: &Scene, camera: &Camera, time: f32) {
+        // Sprites and flares carry no depth, normal or id to show
+        if camera.in_hyperspace() || self.debug_view != DebugView::Final {
+            return;
+        }
+
+        self.draw_particles(&effects.particles, camera);
+        self.draw_comas(effects.comas(), camera);
+        if let Some(beam) = effects.superlaser_beam(scene, time) {
+            self.draw_beam(camera, &beam);
+        }
+        self.draw_lens_flare(scene, camera, time);
+    }
+
+    // Full-screen tunnel that stands in for the scene during long warps
+    fn render_hyperspace(&mut self, time: f32, intensity: f32) {
+        let viewport = self.framebuffer.viewport();
+        let width = self.framebuffer.width;
+        let center_x = viewport.x as f32 + viewport.width as f32 / 2.0;
+        let center_y = viewport.y as f32 + viewport.height as f32 / 2.0;
+        let noise = &self.noise;
+        let tunnel_color = Color::new(40, 90, 255);
+        let core_color = Color::new(200, 230, 255);
+
+        for y in viewport.y..viewport.y + viewport.height {
+            for x in viewport.x..viewport.x + viewport.width {
+                let dx = x as f32 + 0.5 - center_x;
+                let dy = y as f32 + 0.5 - center_y;
+                let radius = (dx * dx + dy * dy).sqrt() / (viewport.height as f32 / 2.0);
+
+                // Tunnel coordinates: angle around the axis, sampled on a circle in
+                // noise space so it wraps without a seam, and depth into the screen
+                let angle = dy.atan2(dx) + time * 0.5;
+                let depth = 1.0 / radius.max(0.05) + time * 6.0;
+                let swirl = noise.get_noise_3d(angle.cos() * 150.0, angle.sin() * 150.0, depth * 60.0) * 0.5 + 0.5;
+
+                // Dark at the vanishing point, bright walls towards the edges
+                let wall = (radius * 1.5).min(1.0);
+                let color = tunnel_color.lerp(&core_color, swirl * swirl) * (swirl * wall * intensity);
+                self.framebuffer.hdr[y * width + x] = color;
+            }
+        }
+
+        self.framebuffer.draw_warp_streaks(&mut self.rng, 400, 1.0);
+    }
+
+    // Every sun in the scene, or a white light at the viewer when there is none
+    fn gather_lights(&mut self, scene: &Scene, camera: &Camera, time: f32) {
+        self.lights.clear();
+        scene.collect_lights(time, &mut self.lights);
+        if self.lights.is_empty() {
+            self.lights.push(Light::white(camera.eye));
+        }
+    }
+
+    // Drawn after the bodies so it depth-tests against them, lit by the suns like they are
+    pub fn draw_ship(&mut self, ship: &Ship, shaders: &ShaderRegistry, scene: &Scene, camera: &Camera, time: f32) {
+        if camera.in_hyperspace() {
+            return;
+        }
+        let Some(shader) = shaders.get(&ship.shader) else {
+            return;
+        };
+
+        let (view_matrix, projection_matrix, viewport_matrix) = self.frame_matrices(camera);
+        self.gather_lights(scene, camera, time);
+        let uniforms = Uniforms {
+            model_matrix: ship.model_matrix(),
+            view_matrix,
+            projection_matrix,
+            viewport_matrix,
+            time,
+            noise: &self.noise,
+            lights: &self.lights,
+            camera_position: camera.eye,
+            material: pbr::Material::default(),
+        };
+
+        let start = Instant::now();
+        let vertex_time = transform_vertices(&uniforms, &ship.mesh, shader, &mut self.instance_vertices);
+        let mut stats = render(&mut self.framebuffer, &uniforms, &ship.mesh.indices, &self.instance_vertices, shader, self.wireframe, self.debug_view);
+        stats.vertex_time += vertex_time;
+        record_draw(&mut self.stats, &mut self.profile, "ship".to_string(), start, stats);
+    }
+
+    // Dirties the tiles under the screen bounds of everything render_frame() will draw, so
+    // only those are cleared and redrawn while the view holds still
+    fn mark_dirty_tiles(&mut self, scene: &Scene, camera: &Camera, time: f32, frustum: &Frustum, view_projection: &Mat4, viewport_matrix: &Mat4) {
+        if camera.warp_intensity() > 0.0 {
+            self.framebuffer.mark_all_dirty();
+            return;
+        }
+
+        let mut spheres: Vec<(Vec3, f32)> = scene.objects.iter().enumerate().map(|(index, object)| {
+            let shell_scale = object.shells.iter().map(|shell| shell.scale).fold(1.0, f32::max);
+            (scene.position(index, time), object.scale * shell_scale * self.mesh_radius * BOUNDS_MARGIN)
+        }).collect();
+        if let Some(belt) = &scene.asteroid_belt {
+            belt.model_matrices(time, &mut self.instance_matrices);
+            spheres.extend(self.instance_matrices.iter().map(|matrix| {
+                let translation = Vec3::new(matrix[(0, 3)], matrix[(1, 3)], matrix[(2, 3)]);
+                let scale = Vec3::new(matrix[(0, 0)], matrix[(1, 0)], matrix[(2, 0)]).magnitude();
+                (translation, belt.mesh_radius * scale)
+            }));
+        }
+
+        for (center, radius) in spheres {
+            if !frustum.intersects_sphere(center, radius) {
+                continue;
+            }
+            match self.screen_bounds(view_projection, viewport_matrix, center, radius) {
+                Some((min_x, min_y, max_x, max_y, _)) => self.framebuffer.mark_dirty(min_x, min_y, max_x, max_y),
+                // Partly behind the camera, where its projection can't be bounded
+                None => self.framebuffer.mark_all_dirty(),
+            }
+        }
+    }
+
+    pub fn render_frame(&mut self, scene: &Scene, shaders: &ShaderRegistry, camera: &Camera, time: f32) {
+        if camera.in_hyperspace() {
+            self.framebuffer.clear();
+            self.render_hyperspace(time, camera.hyperspace_intensity());
+            return;
+        }
+
+        let (view_matrix, projection_matrix, viewport_matrix) = self.frame_matrices(camera);
+        let view_projection = projection_matrix * view_matrix;
+        let frustum = Frustum::from_matrix(&view_projection);
+        let draw_nebula = self.debug_view == DebugView::Final && !camera.map_view;
+        self.framebuffer.set_background_color(0x000000); 
+        if draw_nebula {
+            let sky = sky_key(&view_projection, &viewport_matrix, camera.eye, [scene.nebula.fingerprint(), scene.star_field.fingerprint()]);
+            self.framebuffer.begin_dirty_tracking(sky);
+            self.mark_dirty_tiles(scene, camera, time, &frustum, &view_projection, &viewport_matrix);
+            self.framebuffer.clear_dirty();
+            self.stats.clean_tiles += self.framebuffer.clean_tile_count();
+        } else {
+            self.framebuffer.clear();
+        }
+
+        self.gather_lights(scene, camera, time);
+        // Suns light themselves from the viewer's side so they never show a dark half
+        let viewer_light = [Light::white(camera.eye)];
+
+        // One set of uniforms per frame; each body only swaps in its own model matrix, noise and light
+        let mut uniforms = Uniforms {
+            model_matrix: Mat4::identity(),
+            view_matrix,
+            projection_matrix,
+            viewport_matrix,
+            time,
+            noise: &self.noise,
+            lights: &self.lights,
+            camera_position: camera.eye,
+            material: pbr::Material::default(),
+        };
+    
+        // Front to back, so nearer bodies fill the coarse depth buffer before farther ones are tested
+        let mut draw_order = std::mem::take(&mut self.draw_order);
+        draw_order.clear();
+        draw_order.extend(0..scene.objects.len());
+        draw_order.sort_by(|&a, &b| {
+            let distance_a = (scene.position(a, time) - camera.eye).magnitude_squared();
+            let distance_b = (scene.position(b, time) - camera.eye).magnitude_squared();
+            distance_a.total_cmp(&distance_b)
+        });
+
+        for &index in &draw_order {
+            let object = &scene.objects[index];
+            let translation = scene.position(index, time);
+            let bounding_radius = object.scale * self.mesh_radius * BOUNDS_MARGIN;
+            if !frustum.intersects_sphere(translation, bounding_radius) {
+                self.stats.culled_objects += 1;
+                continue;
+            }
+
+            let bounds = self.screen_bounds(&view_projection, &viewport_matrix, translation, bounding_radius);
+            if let Some((min_x, min_y, max_x, max_y, nearest)) = bounds {
+                if self.framebuffer.is_occluded(min_x, min_y, max_x, max_y, nearest) {
+                    self.stats.occluded_objects += 1;
+                    continue;
+                }
+            }
+        
+            uniforms.model_matrix = create_model_matrix(translation, object.scale, scene.rotation(index, time));
+            uniforms.noise = &object.noise;
+            uniforms.material = object.material;
+
+            uniforms.lights = if object.light.is_some() { &viewer_light } else { &self.lights };
+        
+            if let Some(shader) = shaders.get(&object.shader) {
+                let start = Instant::now();
+                let mut object_stats = RenderStats::default();
+                let screen_radius = self.screen_radius(camera, translation, object.scale * self.mesh_radius);
+                let lod = self.select_lod(screen_radius);
+                let mesh = &self.lods[lod].mesh;
+
+                if self.vertex_cache.len() <= index {
+                    self.vertex_cache.resize_with(index + 1, || CachedVertices { key: 0, vertices: Vec::new() });
+                }
+
+                // Reuse last frame's vertices when nothing feeding the vertex stage changed
+                let key = vertex_cache_key(&uniforms, &object.shader, lod, shader.animated_vertices());
+                let cached = &mut self.vertex_cache[index];
+                if cached.key == key && !cached.vertices.is_empty() {
+                    self.stats.vertex_cache_hits += 1;
+                } else {
+                    object_stats.vertex_time += transform_vertices(&uniforms, mesh, shader, &mut cached.vertices);
+                    cached.key = key;
+                }
+
+                self.framebuffer.set_current_object(index as u32);
+                for group in &mesh.groups {
+                    // Faces with a material of their own ignore the planet shader
+                    let group_shader = group.material
+                        .and_then(|material| self.materials.get(material))
+                        .map_or(shader, |material| material as &dyn PlanetShader);
+                    let indices = &mesh.indices[group.start..group.start + group.count];
+                    object_stats += render(&mut self.framebuffer, &uniforms, indices, &cached.vertices, group_shader, self.wireframe, self.debug_view);
+                }
+                record_draw(&mut self.stats, &mut self.profile, object.name.clone(), start, object_stats);
+
+                match bounds {
+                    Some((min_x, min_y, max_x, max_y, _)) => self.framebuffer.update_depth_tiles(min_x, min_y, max_x, max_y),
+                    None => self.framebuffer.update_depth_tiles(0.0, 0.0, f32::INFINITY, f32::INFINITY),
+                }
+            }
+        }
+        self.draw_order = draw_order;
+
+        self.framebuffer.set_current_object(NO_OBJECT);
+
+        // The sky only fills what the opaque bodies left uncovered, and the shells blend over it.
+        // It is at infinity, which the top-down orthographic map can't show; stars drop out
+        // there on their own.
+        if self.debug_view == DebugView::Final {
+            if draw_nebula {
+                scene.nebula.draw(&mut self.framebuffer, &view_projection, &viewport_matrix, camera.eye);
+            }
+            scene.star_field.draw(&mut self.framebuffer, &view_projection, &viewport_matrix, time);
+        }
+
+        // Cloud and other transparent shells go after every opaque body so they blend over what is behind them
+        for (index, object) in scene.objects.iter().enumerate() {
+            for shell in &object.shells {
+                let Some(shader) = shaders.get(&shell.shader) else {
+                    continue;
+                };
+
+                let translation = scene.position(index, time);
+                let scale = object.scale * shell.scale;
+                let bounding_radius = scale * self.mesh_radius * BOUNDS_MARGIN;
+                if !frustum.intersects_sphere(translation, bounding_radius) {
+                    self.stats.culled_objects += 1;
+                    continue;
+                }
+                if let Some((min_x, min_y, max_x, max_y, nearest)) = self.screen_bounds(&view_projection, &viewport_matrix, translation, bounding_radius) {
+                    if self.framebuffer.is_occluded(min_x, min_y, max_x, max_y, nearest) {
+                        self.stats.occluded_objects += 1;
+                        continue;
+                    }
+                }
+                // Shells share the planet's tilt but drift at their own speed
+                let rotation = Vec3::new(0.0, time * shell.rotation_speed, object.axial_tilt);
+                uniforms.model_matrix = create_model_matrix(translation, scale, rotation);
+                uniforms.noise = &self.noise;
+                uniforms.material = pbr::Material::default();
+                uniforms.lights = &self.lights;
+
+                let start = Instant::now();
+                let lod = self.select_lod(self.screen_radius(camera, translation, scale * self.mesh_radius));
+                let mesh = &self.lods[lod].mesh;
+                let vertex_time = transform_vertices(&uniforms, mesh, shader, &mut self.instance_vertices);
+                let mut stats = render(&mut self.framebuffer, &uniforms, &mesh.indices, &self.instance_vertices, shader, self.wireframe, self.debug_view);
+                stats.vertex_time += vertex_time;
+                record_draw(&mut self.stats, &mut self.profile, format!("{} {}", object.name, shell.shader), start, stats);
+            }
+        }
+
+        if let Some(belt) = &scene.asteroid_belt {
+            if let Some(shader) = shaders.get(&belt.shader) {
+                belt.model_matrices(time, &mut self.instance_matrices);
+                let total = self.instance_matrices.len();
+                self.instance_matrices.retain(|matrix| {
+                    let translation = Vec3::new(matrix[(0, 3)], matrix[(1, 3)], matrix[(2, 3)]);
+                    let scale = Vec3::new(matrix[(0, 0)], matrix[(1, 0)], matrix[(2, 0)]).magnitude();
+                    frustum.intersects_sphere(translation, belt.mesh_radius * scale)
+                });
+                self.stats.culled_objects += total - self.instance_matrices.len();
+
+                uniforms.noise = &self.noise;
+                uniforms.material = pbr::Material::default();
+                uniforms.lights = &self.lights;
+                let start = Instant::now();
+                let stats = render_instances(
+                    &mut self.framebuffer,
+                    &mut uniforms,
+                    &belt.mesh,
+                    shader,
+                    &self.instance_matrices,
+                    &mut self.instance_vertices,
+                    self.wireframe,
+                    self.debug_view,
+                );
+                record_draw(&mut self.stats, &mut self.profile, "asteroid belt".to_string(), start, stats);
+            }
+        }
+
+        let warp_intensity = camera.warp_intensity();
+        if warp_intensity > 0.0 && self.debug_view == DebugView::Final {
+            self.framebuffer.draw_warp_streaks(&mut self.rng, 200, warp_intensity);
+        }
+    }
+}
+
+pub fn create_model_matrix(translation: Vec3, scale: f32, rotation: Vec3) -> Mat4 {
+    let (sin_x, cos_x) = rotation.x.sin_cos();
+    let (sin_y, cos_y) = rotation.y.sin_cos();
+    let (sin_z, cos_z) = rotation.z.sin_cos();
+
+    let rotation_matrix_x = Mat4::new(
+        1.0,  0.0,    0.0,   0.0,
+        0.0,  cos_x, -sin_x, 0.0,
+        0.0,  sin_x,  cos_x, 0.0,
+        0.0,  0.0,    0.0,   1.0,
+    );
+
+    let rotation_matrix_y = Mat4::new(
+        cos_y,  0.0,  sin_y, 0.0,
+        0.0,    1.0,  0.0,   0.0,
+        -sin_y, 0.0,  cos_y, 0.0,
+        0.0,    0.0,  0.0,   1.0,
+    );
+
+    let rotation_matrix_z = Mat4::new(
+        cos_z, -sin_z, 0.0, 0.0,
+        sin_z,  cos_z, 0.0, 0.0,
+        0.0,    0.0,  1.0, 0.0,
+        0.0,    0.0,  0.0, 1.0,
+    );
+
+    let rotation_matrix = rotation_matrix_z * rotation_matrix_y * rotation_matrix_x;
+
+    let transform_matrix = Mat4::new(
+        scale, 0.0,   0.0,   translation.x,
+        0.0,   scale, 0.0,   translation.y,
+        0.0,   0.0,   scale, translation.z,
+        0.0,   0.0,   0.0,   1.0,
+    );
+
+    transform_matrix * rotation_matrix
+}
+
+
+pub fn create_view_matrix(eye: Vec3, center: Vec3, up: Vec3) -> Mat4 {
+    look_at(&eye, &center, &up)
+}
+
+pub fn create_perspective_matrix(window_width: f32, window_height: f32, fov_degrees: f32, near: f32, far: f32) -> Mat4 {
+    let fov = fov_degrees * PI / 180.0;
+    let aspect_ratio = window_width / window_height;
+
+    reverse_depth(perspective(aspect_ratio, fov, near, far))
+}
+
+pub fn create_orthographic_matrix(window_width: f32, window_height: f32, half_height: f32) -> Mat4 {
+    let aspect_ratio = window_width / window_height;
+    let half_width = half_height * aspect_ratio;
+    let near = 0.1;
+    let far = 1000.0;
+
+    reverse_depth(ortho(-half_width, half_width, -half_height, half_height, near, far))
+}
+
+// Remaps OpenGL's depth range, -1 at the near plane to 1 at the far one, to 1 and 0
+fn reverse_depth(projection: Mat4) -> Mat4 {
+    let remap = Mat4::new(
+        1.0, 0.0, 0.0,  0.0,
+        0.0, 1.0, 0.0,  0.0,
+        0.0, 0.0, -0.5, 0.5,
+        0.0, 0.0, 0.0,  1.0,
+    );
+    remap * projection
+}
+
+pub fn create_viewport_matrix(x: f32, y: f32, width: f32, height: f32) -> Mat4 {
+    Mat4::new(
+        width / 2.0, 0.0, 0.0, x + width / 2.0,
+        0.0, -height / 2.0, 0.0, y + height / 2.0,
+        0.0, 0.0, 1.0, 0.0,
+        0.0, 0.0, 0.0, 1.0
+    )
+}
+
+// Counter-clockwise faces in world space end up with a positive screen-space area
+fn is_back_facing(v1: &Vertex, v2: &Vertex, v3: &Vertex) -> bool {
+    let (a, b, c) = (v1.transformed_position, v2.transformed_position, v3.transformed_position);
+    (c.x - a.x) * (b.y - a.y) - (c.y - a.y) * (b.x - a.x) <= 0.0
+}
+
+fn vertex_cache_key(uniforms: &Uniforms, shader_name: &str, lod: usize, animated: bool) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    shader_name.hash(&mut hasher);
+    lod.hash(&mut hasher);
+    for matrix in [&uniforms.model_matrix, &uniforms.view_matrix, &uniforms.projection_matrix, &uniforms.viewport_matrix] {
+        for value in matrix.iter() {
+            value.to_bits().hash(&mut hasher);
+        }
+    }
+    if animated {
+        uniforms.time.to_bits().hash(&mut hasher);
+    }
+    hasher.finish()
+}
+
+// Everything the static sky's look on screen depends on
+fn sky_key(view_projection: &Mat4, viewport_matrix: &Mat4, eye: Vec3, sources: [u64; 2]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    sources.hash(&mut hasher);
+    for value in view_projection.iter().chain(viewport_matrix.iter()).chain(eye.iter()) {
+        value.to_bits().hash(&mut hasher);
+    }
+    hasher.finish()
+}
+
+// Depth-tested like filled faces, so nearer bodies still hide the edges behind them
+fn draw_edges(framebuffer: &mut Framebuffer, tri: [&Vertex; 3], color: Color) {
+    let (width, height) = (framebuffer.width as f32, framebuffer.height as f32);
+    framebuffer.set_current_color(color);
+    framebuffer.set_current_emission(Color::black());
+
+    for (a, b) in [(0, 1), (1, 2), (2, 0)] {
+        let (start, end) = (tri[a].transformed_position, tri[b].transformed_position);
+        // Edges reaching far off screen would rasterize thousands of invisible pixels
+        let on_screen = |p: &Vec3| p.x.abs() < width * 4.0 && p.y.abs() < height * 4.0;
+        if !on_screen(&start) || !on_screen(&end) {
+            continue;
+        }
+        line(start, end, |x, y, depth| {
+            if x >= 0 && y >= 0 {
+                framebuffer.point(x as usize, y as usize, depth);
+            }
+        });
+    }
+}
+
+// Adds one draw's cost to the frame totals and to the per-object profile; takes the fields
+// apart from the renderer because the frame's uniforms borrow the rest of it
+fn record_draw(totals: &mut RenderStats, profile: &mut Vec<ObjectProfile>, name: String, start: Instant, stats: RenderStats) {
+    *totals += stats;
+    profile.push(ObjectProfile { name, start, stats });
+}
+
+// Returns how long the vertex stage took
+fn transform_vertices(uniforms: &Uniforms, mesh: &IndexedMesh, shader: &dyn PlanetShader, transformed_vertices: &mut Vec<Vertex>) -> Duration {
+    // Vertex Shader
+    let start = Instant::now();
+    transformed_vertices.clear();
+    for vertex in &mesh.vertices {
+        let transformed = shader.vertex(vertex, uniforms);
+        transformed_vertices.push(transformed);
+    }
+    start.elapsed()
+}
+
+// Draws one mesh once per model matrix, reusing a single scratch buffer for the transformed vertices
+#[allow(clippy::too_many_arguments)]
+fn render_instances(
+    framebuffer: &mut Framebuffer,
+    uniforms: &mut Uniforms,
+    mesh: &IndexedMesh,
+    shader: &dyn PlanetShader,
+    model_matrices: &[Mat4],
+    transformed_vertices: &mut Vec<Vertex>,
+    wireframe: bool,
+    debug_view: DebugView,
+) -> RenderStats {
+    let mut stats = RenderStats::default();
+
+    for model_matrix in model_matrices {
+        uniforms.model_matrix = *model_matrix;
+        stats.vertex_time += transform_vertices(uniforms, mesh, shader, transformed_vertices);
+
+        let instance_stats = render(framebuffer, uniforms, &mesh.indices, transformed_vertices, shader, wireframe, debug_view);
+        stats += instance_stats;
+    }
+
+    stats
+}
+
+fn render(
+    framebuffer: &mut Framebuffer,
+    uniforms: &Uniforms,
+    indices: &[u32],
+    transformed_vertices: &[Vertex],
+    shader: &dyn PlanetShader,
+    wireframe: bool,
+    debug_view: DebugView,
+) -> RenderStats {
+    let transparent = shader.is_transparent();
+    let additive = shader.is_additive();
+    // Debug views show the surfaces under the cloud and glow shells
+    if transparent && !wireframe && debug_view != DebugView::Final {
+        return RenderStats::default();
+    }
+    let object = framebuffer.current_object();
+    let clip = framebuffer.scissor();
+
+    // Primitive Assembly, Rasterization and Fragment Processing in one pass
+    let start = Instant::now();
+    let overhead = timer_overhead();
+    // A sampled span stands for the unsampled ones around it
+    let sample = |span: Duration| span.saturating_sub(overhead) * TIMING_INTERVAL as u32;
+    let mut stats = RenderStats::default();
+    for (face_index, face) in indices.chunks_exact(3).enumerate() {
+        let assembly_start = (face_index % TIMING_INTERVAL == 0).then(Instant::now);
+        let tri = [
+            &transformed_vertices[face[0] as usize],
+            &transformed_vertices[face[1] as usize],
+            &transformed_vertices[face[2] as usize],
+        ];
+
+        // There is no clipping, so a vertex past the near or far plane would project to
+        // garbage coordinates; drop the whole triangle instead.
+        // Without depth writes the far side of a transparent shell would show through, and
+        // with no filled faces to hide it neither would a wireframe's.
+        let visible = tri.iter().all(|vertex| (0.0..=1.0).contains(&vertex.transformed_position.z))
+            && !((transparent || wireframe) && is_back_facing(tri[0], tri[1], tri[2]));
+        if let Some(assembly_start) = assembly_start {
+            stats.assembly_time += sample(assembly_start.elapsed());
+        }
+        if !visible {
+            continue;
+        }
+        stats.triangles += 1;
+        if wireframe {
+            draw_edges(framebuffer, tri, if transparent { WIREFRAME_SHELL_COLOR } else { WIREFRAME_COLOR });
+            continue;
+        }
+        triangle(tri[0], tri[1], tri[2], uniforms.lights, clip, |fragment| {
+            stats.fragments += 1;
+            let x = fragment.position.x as usize;
+            let y = fragment.position.y as usize;
+            // Early depth test: occluded fragments never reach the shader
+            if !framebuffer.depth_test(x, y, fragment.depth) {
+                return;
+            }
+            let shading_start = (stats.shaded_fragments % TIMING_INTERVAL == 0).then(Instant::now);
+            stats.shaded_fragments += 1;
+
+            // Transparent shells fold their emission into the blended color
+            let (color, emission, alpha) = if let Some(color) = debug_view.fragment_color(&fragment, uniforms, object) {
+                (color, Color::black(), 1.0)
+            } else if !transparent {
+                (shader.fragment(&fragment, uniforms), shader.emission(&fragment, uniforms), 1.0)
+            } else {
+                let alpha = shader.opacity(&fragment, uniforms);
+                if alpha > 0.0 {
+                    let color = shader.fragment(&fragment, uniforms) + shader.emission(&fragment, uniforms);
+                    (color, Color::black(), alpha)
+                } else {
+                    (Color::black(), Color::black(), 0.0)
+                }
+            };
+            let write_start = shading_start.map(|shading_start| {
+                let now = Instant::now();
+                stats.fragment_time += sample(now - shading_start);
+                now
+            });
+
+            if !transparent {
+                framebuffer.set_current_color(color);
+                framebuffer.set_current_emission(emission);
+                framebuffer.point(x, y, fragment.depth);
+            } else if alpha > 0.0 {
+                if additive {
+                    framebuffer.add_color(x, y, fragment.depth, color * alpha);
+                } else {
+                    framebuffer.blend_color(x, y, fragment.depth, color, alpha);
+                }
+            }
+
+            if let Some(write_start) = write_start {
+                stats.write_time += sample(write_start.elapsed());
+            }
+        });
+    }
+
+    // Rasterization is whatever the sampled stages leave of the draw, which they can
+    // overshoot on tiny draws
+    let elapsed = start.elapsed();
+    let sampled = stats.assembly_time + stats.fragment_time + stats.write_time;
+    if sampled > elapsed {
+        let scale = elapsed.as_secs_f64() / sampled.as_secs_f64();
+        stats.assembly_time = stats.assembly_time.mul_f64(scale);
+        stats.fragment_time = stats.fragment_time.mul_f64(scale);
+        stats.write_time = stats.write_time.mul_f64(scale);
+    }
+    stats.raster_time = elapsed.saturating_sub(stats.assembly_time + stats.fragment_time + stats.write_time);
+    stats
+}
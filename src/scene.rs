@@ -0,0 +1,528 @@
+use std::fs;
+use serde::Deserialize;
+use nalgebra_glm::Vec3;
+use fastnoise_lite::{FastNoiseLite, NoiseType, FractalType, CellularReturnType};
+use crate::shaders::ShaderRegistry;
+use crate::asteroids::AsteroidBelt;
+use crate::starfield::StarField;
+use crate::nebula::Nebula;
+use crate::orbit::Orbit;
+use crate::gravity::{attraction, Body, Gravity, G};
+use crate::pbr::Material;
+use crate::color::Color;
+use crate::{derive_seed, Light};
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct PlanetConfig {
+    pub name: String,
+    pub shader: String,
+    #[serde(alias = "orbit_radius")]
+    pub semi_major_axis: f32,
+    pub orbital_speed: f32,
+    #[serde(default)]
+    pub eccentricity: f32,
+    #[serde(default)]
+    pub inclination: f32,
+    #[serde(default)]
+    pub periapsis: f32,
+    #[serde(default)]
+    pub phase: f32,
+    pub scale: f32,
+    // Degrees between the spin axis and the orbit's up direction
+    #[serde(default)]
+    pub axial_tilt: f32,
+    // Seconds per turn; negative spins retrograde and zero doesn't spin
+    #[serde(default = "default_rotation_period")]
+    pub rotation_period: f32,
+    // Only used in gravity mode; defaults to scale cubed times DEFAULT_DENSITY
+    pub mass: Option<f32>,
+    #[serde(default)]
+    pub moons: Vec<PlanetConfig>,
+    pub clouds: Option<CloudConfig>,
+    // More transparent layers above the clouds, such as an aurora
+    #[serde(default)]
+    pub shells: Vec<CloudConfig>,
+    #[serde(default)]
+    pub noise: NoiseConfig,
+    #[serde(default)]
+    pub material: MaterialConfig,
+    // Makes the body a sun that lights the others; "sol" bodies get a white one by default
+    pub light: Option<LightConfig>,
+    // Makes the body a comet that grows dust and ion tails near the sun
+    pub tail: Option<TailConfig>,
+}
+
+const DEFAULT_DENSITY: f32 = 0.05;
+// Offset used to estimate orbital directions by finite differences
+const VELOCITY_SAMPLE: f32 = 1e-3;
+
+fn default_rotation_period() -> f32 {
+    std::f32::consts::TAU / 0.6
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct NoiseConfig {
+    pub seed: i32,
+    pub noise_type: String,
+    pub frequency: f32,
+    pub fractal: String,
+    pub octaves: i32,
+    pub lacunarity: f32,
+    pub gain: f32,
+    // Only used by cellular noise
+    pub cellular_return: String,
+}
+
+impl Default for NoiseConfig {
+    fn default() -> Self {
+        NoiseConfig {
+            seed: 1337,
+            noise_type: "open_simplex2".to_string(),
+            frequency: 0.01,
+            fractal: "none".to_string(),
+            octaves: 3,
+            lacunarity: 2.0,
+            gain: 0.5,
+            cellular_return: "distance".to_string(),
+        }
+    }
+}
+
+impl NoiseConfig {
+    pub fn build(&self) -> Result<FastNoiseLite, String> {
+        let noise_type = match self.noise_type.as_str() {
+            "open_simplex2" => NoiseType::OpenSimplex2,
+            "open_simplex2s" => NoiseType::OpenSimplex2S,
+            "cellular" => NoiseType::Cellular,
+            "perlin" => NoiseType::Perlin,
+            "value_cubic" => NoiseType::ValueCubic,
+            "value" => NoiseType::Value,
+            other => return Err(format!(
+                "Unknown noise type \"{}\" (expected one of: open_simplex2, open_simplex2s, cellular, perlin, value_cubic, value)",
+                other
+            )),
+        };
+        let fractal = match self.fractal.as_str() {
+            "none" => FractalType::None,
+            "fbm" => FractalType::FBm,
+            "ridged" => FractalType::Ridged,
+            "ping_pong" => FractalType::PingPong,
+            other => return Err(format!("Unknown fractal \"{}\" (expected one of: none, fbm, ridged, ping_pong)", other)),
+        };
+        let cellular_return = match self.cellular_return.as_str() {
+            "cell_value" => CellularReturnType::CellValue,
+            "distance" => CellularReturnType::Distance,
+            "distance2" => CellularReturnType::Distance2,
+            "distance2_add" => CellularReturnType::Distance2Add,
+            "distance2_sub" => CellularReturnType::Distance2Sub,
+            "distance2_mul" => CellularReturnType::Distance2Mul,
+            "distance2_div" => CellularReturnType::Distance2Div,
+            other => return Err(format!("Unknown cellular return \"{}\"", other)),
+        };
+
+        let mut noise = FastNoiseLite::with_seed(self.seed);
+        noise.set_noise_type(Some(noise_type));
+        noise.set_frequency(Some(self.frequency));
+        noise.set_fractal_type(Some(fractal));
+        noise.set_fractal_octaves(Some(self.octaves));
+        noise.set_fractal_lacunarity(Some(self.lacunarity));
+        noise.set_fractal_gain(Some(self.gain));
+        noise.set_cellular_return_type(Some(cellular_return));
+        Ok(noise)
+    }
+}
+
+// Base PBR parameters; the albedo comes from the shader's pattern
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct MaterialConfig {
+    pub metallic: f32,
+    pub roughness: f32,
+    // 0xRRGGBB, added on top of the lit color
+    pub emissive: u32,
+}
+
+impl Default for MaterialConfig {
+    fn default() -> Self {
+        let material = Material::default();
+        MaterialConfig {
+            metallic: material.metallic,
+            roughness: material.roughness,
+            emissive: material.emissive.to_hex(),
+        }
+    }
+}
+
+impl MaterialConfig {
+    pub fn build(&self) -> Result<Material, String> {
+        if !(0.0..=1.0).contains(&self.metallic) || !(0.0..=1.0).contains(&self.roughness) {
+            return Err(format!(
+                "material metallic and roughness must be in [0, 1], got {} and {}",
+                self.metallic, self.roughness
+            ));
+        }
+        Ok(Material {
+            metallic: self.metallic,
+            roughness: self.roughness,
+            emissive: Color::from_hex(self.emissive),
+            ..Material::default()
+        })
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct LightConfig {
+    // 0xRRGGBB
+    pub color: u32,
+    pub intensity: f32,
+}
+
+impl Default for LightConfig {
+    fn default() -> Self {
+        LightConfig { color: 0xFFFFFF, intensity: 1.0 }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct TailConfig {
+    // How far the ion tail reaches when the comet is reference_distance from the sun; it
+    // grows with the inverse square of the distance
+    pub length: f32,
+    pub reference_distance: f32,
+    // 0xRRGGBB
+    pub dust_color: u32,
+    pub ion_color: u32,
+}
+
+impl Default for TailConfig {
+    fn default() -> Self {
+        TailConfig { length: 2.5, reference_distance: 4.0, dust_color: 0xFFE6C0, ion_color: 0x7FB4FF }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct CloudConfig {
+    pub shader: String,
+    // Relative to the planet's own scale
+    #[serde(default = "default_cloud_scale")]
+    pub scale: f32,
+    pub rotation_speed: f32,
+}
+
+fn default_cloud_scale() -> f32 {
+    1.05
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct AsteroidBeltConfig {
+    pub shader: String,
+    pub count: usize,
+    pub inner_radius: f32,
+    pub outer_radius: f32,
+    pub orbital_speed: f32,
+    #[serde(default)]
+    pub thickness: f32,
+    pub min_scale: f32,
+    pub max_scale: f32,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct StarFieldConfig {
+    pub seed: u64,
+    pub count: usize,
+    pub brightness: f32,
+    // How far stars dim as they twinkle, from 0 (steady) to 1
+    pub twinkle: f32,
+    // Strength of the Milky Way's glow and how many stars crowd into it; 0 removes it
+    pub milky_way: f32,
+    // Degrees between the band and the orbital plane
+    pub milky_way_tilt: f32,
+}
+
+impl Default for StarFieldConfig {
+    fn default() -> Self {
+        StarFieldConfig { seed: 7, count: 4000, brightness: 1.0, twinkle: 0.3, milky_way: 0.6, milky_way_tilt: 60.0 }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct NebulaConfig {
+    pub seed: i32,
+    // Parallax layers, each baked into its own cube map; 0 leaves the sky clear
+    pub layers: usize,
+    // 0xRRGGBB colors the gas shifts between
+    pub palette: Vec<u32>,
+    pub brightness: f32,
+    // Share of the sky left empty, from 0 to 1
+    pub coverage: f32,
+    // Texels along each cube map face's side
+    pub resolution: usize,
+}
+
+impl Default for NebulaConfig {
+    fn default() -> Self {
+        NebulaConfig {
+            seed: 2024,
+            layers: 3,
+            palette: vec![0x1C0F3A, 0x5B2A86, 0xC8507A, 0x3FA7D6],
+            brightness: 0.6,
+            coverage: 0.45,
+            resolution: 128,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct SceneConfig {
+    planets: Vec<PlanetConfig>,
+    asteroid_belt: Option<AsteroidBeltConfig>,
+    #[serde(default)]
+    stars: StarFieldConfig,
+    #[serde(default)]
+    nebula: NebulaConfig,
+}
+
+pub struct SolarObject {
+    pub name: String,
+    pub shader: String,
+    pub orbit: Orbit,
+    pub scale: f32,
+    // Radians
+    pub axial_tilt: f32,
+    // Radians per second
+    pub rotation_speed: f32,
+    pub mass: f32,
+    pub parent: Option<usize>,
+    // Transparent layers drawn over the body, clouds first
+    pub shells: Vec<CloudConfig>,
+    pub noise: FastNoiseLite,
+    pub material: Material,
+    pub light: Option<LightConfig>,
+    pub tail: Option<TailConfig>,
+}
+
+pub struct Scene {
+    pub objects: Vec<SolarObject>,
+    pub asteroid_belt: Option<AsteroidBelt>,
+    pub star_field: StarField,
+    pub nebula: Nebula,
+    // When set, bodies follow the integrated state instead of their fixed orbits
+    pub gravity: Option<Gravity>,
+}
+
+impl Scene {
+    // With a seed, it replaces the randomness the file leaves open (the belt) and is mixed
+    // into the file's own seeds, so one number changes or reproduces the whole sky
+    pub fn load(filename: &str, shaders: &ShaderRegistry, seed: Option<u64>) -> Result<Self, String> {
+        let contents = fs::read_to_string(filename)
+            .map_err(|e| format!("Failed to read {}: {}", filename, e))?;
+        let config: SceneConfig = toml::from_str(&contents)
+            .map_err(|e| format!("Failed to parse {}: {}", filename, e))?;
+        if config.planets.is_empty() {
+            return Err(format!("{} has no [[planets]]", filename));
+        }
+
+        let mut objects = Vec::new();
+        for planet in &config.planets {
+            add_object(&mut objects, planet, None)?;
+        }
+        if let Some(seed) = seed {
+            for object in &mut objects {
+                object.noise.seed = derive_seed(seed, object.noise.seed as u64) as i32;
+            }
+        }
+
+        for object in &objects {
+            let shell_shaders = object.shells.iter().map(|shell| shell.shader.as_str());
+            for shader in std::iter::once(object.shader.as_str()).chain(shell_shaders) {
+                if shaders.get(shader).is_none() {
+                    let known: Vec<&str> = shaders.names().collect();
+                    return Err(format!(
+                        "Unknown shader \"{}\" for {} (expected one of: {})",
+                        shader, object.name, known.join(", ")
+                    ));
+                }
+            }
+        }
+
+        let asteroid_belt = match &config.asteroid_belt {
+            Some(belt) if shaders.get(&belt.shader).is_none() => {
+                return Err(format!("Unknown shader \"{}\" for the asteroid belt", belt.shader));
+            }
+            Some(belt) if belt.inner_radius > belt.outer_radius || belt.min_scale > belt.max_scale => {
+                return Err("Asteroid belt ranges must have their minimum before their maximum".to_string());
+            }
+            Some(belt) => Some(AsteroidBelt::new(belt, seed)),
+            None => None,
+        };
+
+        let stars = &config.stars;
+        if stars.brightness < 0.0 || stars.milky_way < 0.0 || !(0.0..=1.0).contains(&stars.twinkle) {
+            return Err("Star brightness and milky_way must not be negative and twinkle must be in [0, 1]".to_string());
+        }
+        let star_field = match seed {
+            Some(seed) => StarField::new(&StarFieldConfig { seed: derive_seed(seed, stars.seed), ..stars.clone() }),
+            None => StarField::new(stars),
+        };
+
+        let nebula = &config.nebula;
+        if nebula.layers > 0 && (nebula.palette.is_empty() || nebula.resolution < 2) {
+            return Err("A nebula needs at least one palette color and a resolution of 2 or more".to_string());
+        }
+        if nebula.brightness < 0.0 || !(0.0..1.0).contains(&nebula.coverage) {
+            return Err("Nebula brightness must not be negative and coverage must be in [0, 1)".to_string());
+        }
+        let nebula = match seed {
+            Some(seed) => Nebula::new(&NebulaConfig { seed: derive_seed(seed, nebula.seed as u64) as i32, ..nebula.clone() }),
+            None => Nebula::new(nebula),
+        };
+
+        Ok(Scene { objects, asteroid_belt, star_field, nebula, gravity: None })
+    }
+
+    // The first light is the primary sun, which bodies without a parent orbit
+    pub fn sun(&self) -> Option<usize> {
+        self.lights().next()
+    }
+
+    pub fn lights(&self) -> impl Iterator<Item = usize> + '_ {
+        self.objects.iter().enumerate().filter(|(_, object)| object.light.is_some()).map(|(index, _)| index)
+    }
+
+    pub fn comets(&self) -> impl Iterator<Item = usize> + '_ {
+        self.objects.iter().enumerate().filter(|(_, object)| object.tail.is_some()).map(|(index, _)| index)
+    }
+
+    // World-space lights at `time`, appended to `lights`
+    pub fn collect_lights(&self, time: f32, lights: &mut Vec<Light>) {
+        for index in self.lights() {
+            let Some(light) = &self.objects[index].light else {
+                continue;
+            };
+            lights.push(Light {
+                position: self.position(index, time),
+                color: Color::from_hex(light.color),
+                intensity: light.intensity,
+            });
+        }
+    }
+
+    // Spin around the body's own Y axis, then the tilt around Z, for create_model_matrix
+    pub fn rotation(&self, index: usize, time: f32) -> Vec3 {
+        let object = &self.objects[index];
+        Vec3::new(0.0, time * object.rotation_speed, object.axial_tilt)
+    }
+
+    pub fn position(&self, index: usize, time: f32) -> Vec3 {
+        if let Some(gravity) = &self.gravity {
+            return gravity.bodies[index].position;
+        }
+        self.orbit_position(index, time)
+    }
+
+    fn orbit_position(&self, index: usize, time: f32) -> Vec3 {
+        let object = &self.objects[index];
+        let local = object.orbit.position(time);
+
+        match object.parent {
+            Some(parent) => self.orbit_position(parent, time) + local,
+            None => local,
+        }
+    }
+
+    // Velocity at `time` along the fixed orbit, with the vis-viva speed for the bodies'
+    // masses so the integrated orbit starts out close to the Keplerian one
+    fn orbit_velocity(&self, index: usize, time: f32) -> Vec3 {
+        let object = &self.objects[index];
+        let Some(parent) = object.parent.or_else(|| self.sun().filter(|&sun| sun != index)) else {
+            return Vec3::zeros();
+        };
+        let parent_velocity = match object.parent {
+            Some(parent) => self.orbit_velocity(parent, time),
+            None => Vec3::zeros(),
+        };
+
+        let local = object.orbit.position(time);
+        let ahead = object.orbit.position(time + VELOCITY_SAMPLE);
+        let Some(direction) = (ahead - local).try_normalize(f32::EPSILON) else {
+            return parent_velocity;
+        };
+
+        let mu = G * (self.objects[parent].mass + object.mass);
+        let radius = local.magnitude();
+        let speed = (mu * (2.0 / radius - 1.0 / object.orbit.semi_major_axis)).max(0.0).sqrt();
+        parent_velocity + direction * speed
+    }
+
+    // Seeds the n-body state from where the fixed orbits are at `time`
+    pub fn start_gravity(&mut self, time: f32) {
+        let bodies = (0..self.objects.len())
+            .map(|index| Body {
+                position: self.orbit_position(index, time),
+                velocity: self.orbit_velocity(index, time),
+                mass: self.objects[index].mass,
+            })
+            .collect();
+        self.gravity = Some(Gravity::new(bodies));
+    }
+
+    // Pull of every body on a test mass at `point`, for objects that aren't part of the scene
+    pub fn attraction(&self, point: Vec3, time: f32) -> Vec3 {
+        attraction(point, (0..self.objects.len()).map(|index| (self.position(index, time), self.objects[index].mass)))
+    }
+
+    pub fn step_gravity(&mut self, delta_time: f32) {
+        if let Some(gravity) = self.gravity.as_mut() {
+            gravity.step(delta_time);
+        }
+    }
+}
+
+fn add_object(objects: &mut Vec<SolarObject>, planet: &PlanetConfig, parent: Option<usize>) -> Result<(), String> {
+    let noise = planet.noise.build().map_err(|e| format!("{}: {}", planet.name, e))?;
+    let material = planet.material.build().map_err(|e| format!("{}: {}", planet.name, e))?;
+    let light = planet.light.clone().or_else(|| (planet.shader == "sol").then(LightConfig::default));
+    if light.as_ref().is_some_and(|light| light.intensity < 0.0) {
+        return Err(format!("{}: light intensity must not be negative", planet.name));
+    }
+    if planet.tail.as_ref().is_some_and(|tail| tail.length < 0.0 || tail.reference_distance <= 0.0) {
+        return Err(format!("{}: tail length must not be negative and reference_distance must be positive", planet.name));
+    }
+    if !(0.0..1.0).contains(&planet.eccentricity) {
+        return Err(format!("{}: eccentricity must be in [0, 1), got {}", planet.name, planet.eccentricity));
+    }
+
+    objects.push(SolarObject {
+        name: planet.name.clone(),
+        shader: planet.shader.clone(),
+        orbit: Orbit {
+            semi_major_axis: planet.semi_major_axis,
+            eccentricity: planet.eccentricity,
+            inclination: planet.inclination.to_radians(),
+            argument_of_periapsis: planet.periapsis.to_radians(),
+            phase: planet.phase.to_radians(),
+            mean_motion: planet.orbital_speed,
+        },
+        scale: planet.scale,
+        axial_tilt: planet.axial_tilt.to_radians(),
+        mass: planet.mass.unwrap_or(planet.scale.powi(3) * DEFAULT_DENSITY),
+        rotation_speed: if planet.rotation_period == 0.0 { 0.0 } else { std::f32::consts::TAU / planet.rotation_period },
+        parent,
+        shells: planet.clouds.iter().chain(&planet.shells).cloned().collect(),
+        noise,
+        material,
+        light,
+        tail: planet.tail.clone(),
+    });
+
+    let index = objects.len() - 1;
+    for moon in &planet.moons {
+        add_object(objects, moon, Some(index))?;
+    }
+    Ok(())
+}
@@ -4,6 +4,13 @@ use crate::vertex::Vertex;
 use crate::Uniforms;
 use crate::fragment::Fragment;
 use crate::color::Color;
+use crate::material::Material;
+use crate::pbr;
+use crate::backend::{GpuSurface, SurfaceStyle};
+use crate::noise::{self, hash, Craters, Fractal};
+use serde::{Deserialize, Serialize};
+use serde::de::DeserializeOwned;
+use toml::{Table, Value};
 
 pub fn vertex_shader(vertex: &Vertex, uniforms: &Uniforms) -> Vertex {
     let position = Vec4::new(
@@ -13,7 +20,8 @@ pub fn vertex_shader(vertex: &Vertex, uniforms: &Uniforms) -> Vertex {
         1.0
     );
 
-    let transformed = uniforms.projection_matrix * uniforms.view_matrix * uniforms.model_matrix * position;
+    let world = uniforms.model_matrix * position;
+    let transformed = uniforms.projection_matrix * uniforms.view_matrix * world;
 
     let w = transformed.w;
     let transformed_position = Vec4::new(
@@ -36,265 +44,1520 @@ pub fn vertex_shader(vertex: &Vertex, uniforms: &Uniforms) -> Vertex {
         tex_coords: vertex.tex_coords,
         color: vertex.color,
         transformed_position: Vec3::new(screen_position.x, screen_position.y, screen_position.z),
-        transformed_normal: transformed_normal
-    }
-}
-
-pub fn fragment_shader(fragment: &Fragment, uniforms: &Uniforms, current_shader: u8) -> Color {
-  match current_shader {
-      0 => tatooine_shader(fragment, uniforms),
-      1 => death_star_shader(fragment, uniforms),
-      2 => gaseoso_shader(fragment, uniforms),
-      3 => kamino_shader(fragment, uniforms),
-      4 => sol_shader(fragment, uniforms),
-      5 => hoth_shader(fragment, uniforms),
-      6 => kashyyyk_shader(fragment, uniforms),
-      _ => Color::black(), 
-  }
-}
-
-pub fn kamino_shader(fragment: &Fragment, uniforms: &Uniforms) -> Color {
-    let zoom = 1000.0;  
-    let ox = 100.0;    
-    let oy = 100.0;
-    let x = fragment.vertex_position.x;
-    let y = fragment.vertex_position.y;
-    let t = uniforms.time as f32 * 0.8;
-
-    let noise_value = uniforms.noise.get_noise_2d(x * zoom + ox + t, y * zoom + oy);
-  
-    let detail_noise_value = uniforms.noise.get_noise_2d(x * zoom * 2.0 + ox + t, y * zoom * 2.0 + oy);
-    let storm_intensity = (detail_noise_value * 0.5) + 0.5;  
-
-    let lightning = (uniforms.time as f32).sin() * 10.0;  
-    let mut cloud_color = Color::new(144, 144, 144) * 0.5;  
-    if storm_intensity > 0.7 && lightning > 0.9 {
-        cloud_color = cloud_color * 2.0;  
-    }
-
-    let sky_color = Color::new(0, 61, 102);  
-    let stormy_sky_color = sky_color * (1.0 - storm_intensity * 0.5); 
-
-    let cloud_threshold = 0.3;
-    let noise_color = if noise_value > cloud_threshold {
-        cloud_color  
-    } else {
-        stormy_sky_color  
+        transformed_normal,
+        world_position: Vec3::new(world.x, world.y, world.z),
+    }
+}
+
+pub fn displaced_vertex_shader(vertex: &Vertex, uniforms: &Uniforms, displacement: f32) -> Vertex {
+    let mut displaced = vertex.clone();
+    displaced.position += vertex.normal.normalize() * displacement;
+    vertex_shader(&displaced, uniforms)
+}
+
+// Warm settlement lights that only show where the surface faces away from the sun
+pub fn city_lights(fragment: &Fragment, uniforms: &Uniforms, zoom: f32, night_threshold: f32, color: Color) -> Color {
+    if fragment.intensity >= night_threshold {
+        return Color::black();
+    }
+
+    let p = fragment.vertex_position * zoom;
+    let cluster = uniforms.noise.get_noise_3d(p.x, p.y, p.z);
+    let lights = uniforms.noise.get_noise_3d(p.x * 8.0, p.y * 8.0, p.z * 8.0);
+    if cluster < 0.2 || lights < 0.3 {
+        return Color::black();
+    }
+
+    let darkness = 1.0 - fragment.intensity / night_threshold;
+    color * (darkness * ((lights - 0.3) / 0.7 + 0.3).min(1.0))
+}
+
+// How much of a polar ice cap covers the fragment, with a noisy edge around cap_latitude
+pub fn ice_cap(fragment: &Fragment, uniforms: &Uniforms, cap_latitude: f32) -> f32 {
+    let p = fragment.vertex_position * 400.0;
+    let edge = uniforms.noise.get_noise_3d(p.x, p.y, p.z) * 0.12;
+    ((fragment.latitude().abs() + edge - cap_latitude) / 0.04).clamp(0.0, 1.0)
+}
+
+// Bends the fragment's normal down crater slopes and relights it, and returns how much to
+// darken the bowls and brighten the rims by
+pub fn apply_craters(fragment: &Fragment, uniforms: &Uniforms, craters: &Craters) -> (Fragment, f32) {
+    // The model matrix only rotates and scales uniformly, so its transpose takes normals back to object space
+    let rotation = mat4_to_mat3(&uniforms.model_matrix);
+    let object_normal = (rotation.transpose() * fragment.normal).try_normalize(f32::EPSILON).unwrap_or(fragment.normal);
+    let (height, bent) = craters.sample(fragment.vertex_position, object_normal);
+    let normal = (rotation * bent).try_normalize(f32::EPSILON).unwrap_or(fragment.normal);
+    (fragment.with_normal(normal, uniforms.lights), 1.0 + height * 1.5)
+}
+
+// Blinn-Phong highlights of every light as seen from the camera, in the lights' colors
+pub fn specular(fragment: &Fragment, uniforms: &Uniforms, shininess: f32) -> Color {
+    let normal = fragment.normal;
+    let mut highlight = Color::black();
+    for source in uniforms.lights {
+        let light = source.direction(&fragment.world_position);
+        if normal.dot(&light) <= 0.0 {
+            continue;
+        }
+
+        let view = (uniforms.camera_position - fragment.world_position).try_normalize(f32::EPSILON).unwrap_or(light);
+        let half = (light + view).try_normalize(f32::EPSILON).unwrap_or(normal);
+        highlight = highlight + source.radiance() * normal.dot(&half).max(0.0).powf(shininess);
+    }
+    highlight
+}
+
+// Procedural sky seen along a world-space direction from `position`: each sun's disc and glow
+// plus a sparse starfield, laid out on the faces of a cube so it needs no texture
+pub fn environment(direction: &Vec3, position: &Vec3, uniforms: &Uniforms) -> Color {
+    let mut sun = Color::black();
+    for source in uniforms.lights {
+        let alignment = direction.dot(&source.direction(position)).max(0.0);
+        let glow = Color::new(255, 240, 200).blend_multiply(&source.radiance());
+        sun = sun + glow * (alignment.powf(400.0) + alignment.powf(20.0) * 0.15);
+    }
+
+    let (major, u, v, face) = {
+        let a = direction.abs();
+        if a.x >= a.y && a.x >= a.z {
+            (a.x, direction.y, direction.z, if direction.x > 0.0 { 0 } else { 1 })
+        } else if a.y >= a.z {
+            (a.y, direction.x, direction.z, if direction.y > 0.0 { 2 } else { 3 })
+        } else {
+            (a.z, direction.x, direction.y, if direction.z > 0.0 { 4 } else { 5 })
+        }
     };
+    if major <= 0.0 {
+        return sun;
+    }
+
+    const STAR_GRID: f32 = 40.0;
+    const STAR_DENSITY: f32 = 0.06;
+    let (cell_u, cell_v) = ((u / major + 1.0) * STAR_GRID, (v / major + 1.0) * STAR_GRID);
+    let (cell_x, cell_y) = (cell_u.floor() as i32, cell_v.floor() as i32);
+    let roll = hash(cell_x, cell_y, face);
+    if roll > STAR_DENSITY {
+        return sun;
+    }
+
+    // One star per lit cell, somewhere inside it, fading towards its rim
+    let (star_u, star_v) = (hash(cell_y, face, cell_x), hash(face, cell_x, cell_y));
+    let (du, dv) = (cell_u.fract() - (0.2 + star_u * 0.6), cell_v.fract() - (0.2 + star_v * 0.6));
+    let glow = (1.0 - (du * du + dv * dv).sqrt() / 0.2).max(0.0);
+    sun + Color::new(255, 255, 255) * (glow * (0.4 + roll / STAR_DENSITY * 0.6))
+}
+
+pub trait PlanetShader {
+    fn fragment(&self, fragment: &Fragment, uniforms: &Uniforms) -> Color;
+
+    // Light the surface gives off itself, added on top of fragment() whatever the suns do and
+    // fed to bloom; by default the emissive color from the scene's material
+    fn emission(&self, _fragment: &Fragment, uniforms: &Uniforms) -> Color {
+        uniforms.material.emissive
+    }
+
+    fn vertex(&self, vertex: &Vertex, uniforms: &Uniforms) -> Vertex {
+        vertex_shader(vertex, uniforms)
+    }
+
+    // Shaders whose vertex stage depends on time must opt out of vertex caching
+    fn animated_vertices(&self) -> bool {
+        false
+    }
+
+    // Transparent shaders are drawn after opaque bodies, blended and without writing depth
+    fn is_transparent(&self) -> bool {
+        false
+    }
+
+    fn opacity(&self, _fragment: &Fragment, _uniforms: &Uniforms) -> f32 {
+        1.0
+    }
+
+    // Transparent shaders that add their light to what is behind instead of covering it, for glows
+    fn is_additive(&self) -> bool {
+        false
+    }
+
+    // Overrides constants by field name, from the shader's table in the parameters file
+    fn tune(&mut self, _params: &Table) -> Result<(), String> {
+        Err("has no tunable parameters".to_string())
+    }
+
+    // The nearest WGSL style and the colors to fill it with, for the GPU backend
+    fn gpu_surface(&self) -> GpuSurface {
+        GpuSurface::default()
+    }
+}
+
+// Lays `params` over the shader's current fields, nested tables included, so the file only
+// needs the constants it changes
+fn overlay_params<T: Serialize + DeserializeOwned>(shader: &mut T, params: &Table) -> Result<(), String> {
+    fn merge(table: &mut Table, params: &Table) {
+        for (key, value) in params {
+            match (table.get_mut(key), value) {
+                (Some(Value::Table(existing)), Value::Table(nested)) => merge(existing, nested),
+                _ => {
+                    table.insert(key.clone(), value.clone());
+                }
+            }
+        }
+    }
+
+    let Value::Table(mut table) = Value::try_from(&*shader).map_err(|e| e.to_string())? else {
+        return Err("parameters are not a table".to_string());
+    };
+    merge(&mut table, params);
+    *shader = Value::Table(table).try_into().map_err(|e: toml::de::Error| e.message().to_string())?;
+    Ok(())
+}
+
+// Vectors as [x, y, z] arrays
+mod vec3_array {
+    use nalgebra_glm::Vec3;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<S: Serializer>(vector: &Vec3, serializer: S) -> Result<S::Ok, S::Error> {
+        [vector.x, vector.y, vector.z].serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec3, D::Error> {
+        let [x, y, z] = <[f32; 3]>::deserialize(deserializer)?;
+        Ok(Vec3::new(x, y, z))
+    }
+}
+
+pub struct ShaderRegistry {
+    shaders: Vec<(&'static str, Box<dyn PlanetShader>)>,
+}
+
+impl ShaderRegistry {
+    pub fn new() -> Self {
+        let mut registry = ShaderRegistry { shaders: Vec::new() };
+        registry.register("tatooine", Box::new(TatooineShader::default()));
+        registry.register("death_star", Box::new(DeathStarShader::default()));
+        registry.register("gaseoso", Box::new(GaseosoShader::default()));
+        registry.register("kamino", Box::new(KaminoShader::default()));
+        registry.register("sol", Box::new(SolShader::default()));
+        registry.register("hoth", Box::new(HothShader::default()));
+        registry.register("kashyyyk", Box::new(KashyyykShader::default()));
+        registry.register("asteroid", Box::new(AsteroidShader::default()));
+        registry.register("clouds", Box::new(CloudShader::default()));
+        registry.register("storm_clouds", Box::new(CloudShader::storm()));
+        registry.register("ship", Box::new(ShipShader::default()));
+        registry.register("mustafar", Box::new(MustafarShader::default()));
+        registry.register("coruscant", Box::new(CoruscantShader::default()));
+        registry.register("smog", Box::new(SmogShader::default()));
+        registry.register("endor", Box::new(EndorShader::default()));
+        registry.register("bespin", Box::new(BespinShader::default()));
+        registry.register("aurora", Box::new(AuroraShader::default()));
+        registry.register("comet", Box::new(CometShader::default()));
+        registry
+    }
+
+    pub fn register(&mut self, name: &'static str, shader: Box<dyn PlanetShader>) {
+        self.shaders.retain(|(existing, _)| *existing != name);
+        self.shaders.push((name, shader));
+    }
+
+    pub fn get(&self, name: &str) -> Option<&dyn PlanetShader> {
+        self.shaders.iter()
+            .find(|(existing, _)| *existing == name)
+            .map(|(_, shader)| shader.as_ref())
+    }
+
+    pub fn get_mut(&mut self, name: &str) -> Option<&mut Box<dyn PlanetShader>> {
+        self.shaders.iter_mut()
+            .find(|(existing, _)| *existing == name)
+            .map(|(_, shader)| shader)
+    }
+
+    pub fn name_at(&self, index: usize) -> Option<&'static str> {
+        self.shaders.get(index).map(|(name, _)| *name)
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.shaders.iter().map(|(name, _)| *name)
+    }
+
+    // Applies a parameters file with one table per shader, such as [tatooine], on top of
+    // the current constants
+    pub fn load_params(&mut self, path: &str) -> Result<(), String> {
+        let contents = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read {}: {}", path, e))?;
+        let file: Table = toml::from_str(&contents)
+            .map_err(|e| format!("Failed to parse {}: {}", path, e))?;
+
+        for (name, params) in &file {
+            let Value::Table(params) = params else {
+                return Err(format!("{}: [{}] must be a table of parameters", path, name));
+            };
+            let shader = self.get_mut(name).ok_or_else(|| format!("{}: unknown shader \"{}\"", path, name))?;
+            shader.tune(params).map_err(|e| format!("{}: [{}] {}", path, name, e))?;
+        }
+        Ok(())
+    }
+}
+
+impl Default for ShaderRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct KaminoShader {
+    pub wave_amplitude: f32,
+    pub wave_frequency: f32,
+    pub wave_speed: f32,
+    pub zoom: f32,
+    pub offset_x: f32,
+    pub offset_y: f32,
+    pub ocean_color: Color,
+    pub deep_ocean_color: Color,
+    pub specular_color: Color,
+    pub calm_shininess: f32,
+    pub storm_shininess: f32,
+    pub city_light_color: Color,
+    pub city_light_zoom: f32,
+    pub night_threshold: f32,
+}
+
+impl Default for KaminoShader {
+    fn default() -> Self {
+        KaminoShader {
+            wave_amplitude: 0.008,
+            wave_frequency: 24.0,
+            wave_speed: 3.0,
+            zoom: 1000.0,
+            offset_x: 100.0,
+            offset_y: 100.0,
+            ocean_color: Color::new(0, 61, 102),
+            deep_ocean_color: Color::new(0, 30, 60),
+            specular_color: Color::new(255, 250, 230),
+            calm_shininess: 120.0,
+            storm_shininess: 12.0,
+            city_light_color: Color::new(255, 210, 140),
+            city_light_zoom: 900.0,
+            night_threshold: 0.15,
+        }
+    }
+}
+
+impl PlanetShader for KaminoShader {
+
+    fn tune(&mut self, params: &Table) -> Result<(), String> {
+        overlay_params(self, params)
+    }
+    fn gpu_surface(&self) -> GpuSurface {
+        GpuSurface::new(SurfaceStyle::Ocean, [self.deep_ocean_color, self.ocean_color, self.ocean_color, self.specular_color])
+    }
+    fn animated_vertices(&self) -> bool {
+        true
+    }
+
+    fn vertex(&self, vertex: &Vertex, uniforms: &Uniforms) -> Vertex {
+        let p = vertex.position;
+        let t = uniforms.time * self.wave_speed;
+        let waves = (p.x * self.wave_frequency + t).sin() * (p.z * self.wave_frequency * 0.7 + t * 1.3).cos();
+        displaced_vertex_shader(vertex, uniforms, waves * self.wave_amplitude)
+    }
+
+    fn fragment(&self, fragment: &Fragment, uniforms: &Uniforms) -> Color {
+        let zoom = self.zoom;
+        let ox = self.offset_x;
+        let oy = self.offset_y;
+        let p = fragment.vertex_position * zoom * 2.0;
+        let t = uniforms.time * 48.0;
+
+        let detail_noise_value = uniforms.noise.get_noise_3d(p.x + ox + t, p.y + oy, p.z);
+        let storm_intensity = (detail_noise_value * 0.5) + 0.5;
+
+        let ocean_color = self.ocean_color.lerp(&self.deep_ocean_color, storm_intensity);
+
+        // Calm water gives a tight, bright glint; choppy storm regions spread it out
+        let shininess = self.calm_shininess + (self.storm_shininess - self.calm_shininess) * storm_intensity;
+        let glint = specular(fragment, uniforms, shininess) * (1.0 - storm_intensity * 0.7);
+
+        fragment.lit(ocean_color) + self.specular_color.blend_multiply(&glint)
+    }
+
+    fn emission(&self, fragment: &Fragment, uniforms: &Uniforms) -> Color {
+        city_lights(fragment, uniforms, self.city_light_zoom, self.night_threshold, self.city_light_color) + uniforms.material.emissive
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SolShader {
+    pub displacement_amplitude: f32,
+    pub displacement_zoom: f32,
+    pub bright_color: Color,
+    pub dark_color: Color,
+    pub base_frequency: f32,
+    pub pulsate_amplitude: f32,
+    pub zoom: f32,
+}
+
+impl Default for SolShader {
+    fn default() -> Self {
+        SolShader {
+            displacement_amplitude: 0.03,
+            displacement_zoom: 400.0,
+            bright_color: Color::new(255, 255, 204),
+            dark_color: Color::new(255, 51, 0),
+            base_frequency: 0.2,
+            pulsate_amplitude: 0.5,
+            zoom: 1000.0,
+        }
+    }
+}
+
+impl PlanetShader for SolShader {
+
+    fn tune(&mut self, params: &Table) -> Result<(), String> {
+        overlay_params(self, params)
+    }
+    fn gpu_surface(&self) -> GpuSurface {
+        GpuSurface::new(SurfaceStyle::Star, [self.dark_color, self.bright_color, self.bright_color, self.bright_color])
+    }
+    fn animated_vertices(&self) -> bool {
+        true
+    }
+
+    fn vertex(&self, vertex: &Vertex, uniforms: &Uniforms) -> Vertex {
+        let p = vertex.position * self.displacement_zoom;
+        let t = uniforms.time * 30.0;
+        let noise_value = uniforms.noise.get_noise_3d(p.x + t, p.y, p.z);
+        displaced_vertex_shader(vertex, uniforms, noise_value * self.displacement_amplitude)
+    }
+
+    // The sun only gives off light; nothing it reflects would show next to it
+    fn fragment(&self, _fragment: &Fragment, _uniforms: &Uniforms) -> Color {
+        Color::black()
+    }
+
+    fn emission(&self, fragment: &Fragment, uniforms: &Uniforms) -> Color {
+        let position = fragment.vertex_position;
+
+        let t = uniforms.time * 0.6;
+        let pulsate = (t * self.base_frequency).sin() * self.pulsate_amplitude;
+
+        let zoom = self.zoom;
+        let noise_value1 = uniforms.noise.get_noise_3d(
+            position.x * zoom,
+            position.y * zoom,
+            (position.z + pulsate) * zoom
+        );
+        let noise_value2 = uniforms.noise.get_noise_3d(
+            (position.x + 1000.0) * zoom,
+            (position.y + 1000.0) * zoom,
+            (position.z + 1000.0 + pulsate) * zoom
+        );
+        let noise_value = (noise_value1 + noise_value2) * 0.5;
+
+        let base_color = self.dark_color.lerp(&self.bright_color, noise_value);
+
+        // Brightest where the surface faces the viewer, dimming towards the limb
+        let view = (uniforms.camera_position - fragment.world_position).try_normalize(f32::EPSILON).unwrap_or(fragment.normal);
+        let facing = fragment.normal.dot(&view).clamp(0.0, 1.0);
+        let falloff = facing.powf(2.0);
+
+        let brightened_color = base_color * (1.0 + falloff * 2.0);
+
+        brightened_color * facing
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct HothShader {
+    pub snow_color: Color,
+    pub ice_color: Color,
+    pub crack_color: Color,
+    pub zoom: f32,
+    pub ice_threshold: f32,
+    pub crack_threshold: f32,
+}
+
+impl Default for HothShader {
+    fn default() -> Self {
+        HothShader {
+            snow_color: Color::new(255, 255, 255),
+            ice_color: Color::new(173, 216, 230),
+            crack_color: Color::new(60, 100, 140),
+            zoom: 500.0,
+            ice_threshold: 0.3,
+            crack_threshold: -0.95,
+        }
+    }
+}
+
+impl PlanetShader for HothShader {
+
+    fn tune(&mut self, params: &Table) -> Result<(), String> {
+        overlay_params(self, params)
+    }
+    fn gpu_surface(&self) -> GpuSurface {
+        GpuSurface::new(SurfaceStyle::Rocky, [self.crack_color, self.ice_color, self.snow_color, self.snow_color])
+    }
+    fn fragment(&self, fragment: &Fragment, uniforms: &Uniforms) -> Color {
+        let position = fragment.vertex_position;
+
+        let zoom = self.zoom;
+        let t = uniforms.time * 0.6;
+
+        let noise_value = uniforms.noise.get_noise_3d(
+            position.x * zoom,
+            position.y * zoom,
+            position.z * zoom + t
+        );
+
+        // With cellular distance noise the lowest values trace the borders between ice plates
+        let base_color = if noise_value < self.crack_threshold {
+            self.crack_color
+        } else if noise_value > self.ice_threshold {
+            self.ice_color
+        } else {
+            self.snow_color
+        };
+
+        let intensity_variation = 0.9 + (noise_value * 0.1);
+
+        fragment.lit(base_color) * intensity_variation
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MustafarShader {
+    pub zoom: f32,
+    pub crust_color: Color,
+    pub ash_color: Color,
+    pub lava_color: Color,
+    pub lava_core_color: Color,
+    pub river_fractal: Fractal,
+    // Ridged noise above 1 - river_width is molten
+    pub river_width: f32,
+    pub flow_speed: f32,
+    // Multiplies the lava's emission past white so it blooms
+    pub glow: f32,
+}
+
+impl Default for MustafarShader {
+    fn default() -> Self {
+        MustafarShader {
+            zoom: 400.0,
+            crust_color: Color::new(28, 22, 20),
+            ash_color: Color::new(58, 46, 40),
+            lava_color: Color::new(255, 70, 10),
+            lava_core_color: Color::new(255, 200, 80),
+            river_fractal: Fractal::new(4, 2.1, 0.5),
+            river_width: 0.3,
+            flow_speed: 6.0,
+            glow: 2.5,
+        }
+    }
+}
+
+impl MustafarShader {
+    // How molten the fragment is, 0 on solid crust and 1 in the middle of a river
+    fn lava(&self, fragment: &Fragment, uniforms: &Uniforms) -> f32 {
+        let p = fragment.vertex_position * self.zoom;
+        let t = uniforms.time * self.flow_speed;
+        // Sliding the lookup makes the rivers creep across the crust
+        let rivers = noise::ridged(uniforms.noise, p + Vec3::new(t, t * 0.4, 0.0), &self.river_fractal);
+        ((rivers - (1.0 - self.river_width)) / self.river_width).clamp(0.0, 1.0)
+    }
+}
+
+impl PlanetShader for MustafarShader {
+
+    fn tune(&mut self, params: &Table) -> Result<(), String> {
+        overlay_params(self, params)
+    }
+    fn gpu_surface(&self) -> GpuSurface {
+        GpuSurface::new(SurfaceStyle::Lava, [self.crust_color, self.ash_color, self.lava_color, self.lava_core_color])
+    }
+    fn fragment(&self, fragment: &Fragment, uniforms: &Uniforms) -> Color {
+        let p = fragment.vertex_position * self.zoom * 3.0;
+        let basalt = noise::fbm(uniforms.noise, p, &self.river_fractal) * 0.5 + 0.5;
+        let crust = self.crust_color.lerp(&self.ash_color, basalt);
+        fragment.lit(crust) * (1.0 - self.lava(fragment, uniforms))
+    }
+
+    fn emission(&self, fragment: &Fragment, uniforms: &Uniforms) -> Color {
+        let lava = self.lava(fragment, uniforms);
+        if lava <= 0.0 {
+            return uniforms.material.emissive;
+        }
+        // Hotter towards the middle of each river, with a slow throb
+        let pulse = 0.85 + (uniforms.time * 2.0 + fragment.vertex_position.x * 20.0).sin() * 0.15;
+        let color = self.lava_color.lerp(&self.lava_core_color, lava * lava);
+        color * (lava * self.glow * pulse) + uniforms.material.emissive
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CoruscantShader {
+    // City blocks around the equator; rows keep them roughly square
+    pub blocks_around: f32,
+    // Fraction of a block taken by the street on each side
+    pub street_width: f32,
+    pub building_color: Color,
+    pub tower_color: Color,
+    pub street_color: Color,
+    pub window_color: Color,
+    pub traffic_color: Color,
+    // Blocks per second
+    pub traffic_speed: f32,
+    pub night_threshold: f32,
+}
+
+impl Default for CoruscantShader {
+    fn default() -> Self {
+        CoruscantShader {
+            blocks_around: 160.0,
+            street_width: 0.14,
+            building_color: Color::new(120, 118, 125),
+            tower_color: Color::new(190, 185, 170),
+            street_color: Color::new(45, 45, 52),
+            window_color: Color::new(255, 215, 150),
+            traffic_color: Color::new(255, 245, 220),
+            traffic_speed: 0.6,
+            night_threshold: 0.2,
+        }
+    }
+}
+
+impl CoruscantShader {
+    // Position in blocks: x along longitude, y along latitude
+    fn grid(&self, fragment: &Fragment) -> (f32, f32) {
+        let scale = self.blocks_around / std::f32::consts::TAU;
+        (fragment.longitude() * scale, fragment.latitude() * scale)
+    }
+
+    fn on_street(&self, offset: f32) -> bool {
+        offset.rem_euclid(1.0) < self.street_width
+    }
+}
+
+impl PlanetShader for CoruscantShader {
+
+    fn tune(&mut self, params: &Table) -> Result<(), String> {
+        overlay_params(self, params)
+    }
+    fn gpu_surface(&self) -> GpuSurface {
+        GpuSurface::new(SurfaceStyle::Rocky, [self.street_color, self.building_color, self.building_color, self.tower_color])
+    }
+    fn fragment(&self, fragment: &Fragment, _uniforms: &Uniforms) -> Color {
+        let (x, y) = self.grid(fragment);
+        if self.on_street(x) || self.on_street(y) {
+            return fragment.lit(self.street_color);
+        }
+
+        // Each block is one building whose height shows as how pale its roof is
+        let height = hash(x.floor() as i32, y.floor() as i32, 7);
+        let color = self.building_color.lerp(&self.tower_color, height * height);
+        fragment.lit(color)
+    }
+
+    // Lit windows and traffic streaming down the streets, only on the night side
+    fn emission(&self, fragment: &Fragment, uniforms: &Uniforms) -> Color {
+        let darkness = (1.0 - fragment.intensity / self.night_threshold).clamp(0.0, 1.0);
+        if darkness <= 0.0 {
+            return uniforms.material.emissive;
+        }
+
+        let (x, y) = self.grid(fragment);
+        let (block_x, block_y) = (x.floor() as i32, y.floor() as i32);
+        let travel = uniforms.time * self.traffic_speed;
+        let lights = if self.on_street(y) {
+            // East-west lanes; alternate streets flow in opposite directions
+            let direction = if block_y % 2 == 0 { 1.0 } else { -1.0 };
+            let car = (x * 4.0 - travel * direction * 4.0).rem_euclid(1.0);
+            if car < 0.25 && hash(block_x, block_y, 3) > 0.3 { self.traffic_color } else { Color::black() }
+        } else if self.on_street(x) {
+            let direction = if block_x % 2 == 0 { 1.0 } else { -1.0 };
+            let car = (y * 4.0 - travel * direction * 4.0).rem_euclid(1.0);
+            if car < 0.25 && hash(block_x, block_y, 5) > 0.3 { self.traffic_color } else { Color::black() }
+        } else {
+            let (window_x, window_y) = ((x * 6.0).floor() as i32, (y * 6.0).floor() as i32);
+            let lit = hash(window_x, window_y, 11);
+            if lit > 0.88 { self.window_color * ((lit - 0.88) / 0.12) } else { Color::black() }
+        };
+        lights * darkness + uniforms.material.emissive
+    }
+}
+
+// Thin haze over Coruscant, thicker towards the limb where the line of sight grazes it
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SmogShader {
+    pub color: Color,
+    pub opacity: f32,
+    pub limb_opacity: f32,
+}
+
+impl Default for SmogShader {
+    fn default() -> Self {
+        SmogShader {
+            color: Color::new(200, 170, 130),
+            opacity: 0.12,
+            limb_opacity: 0.6,
+        }
+    }
+}
+
+impl PlanetShader for SmogShader {
+
+    fn tune(&mut self, params: &Table) -> Result<(), String> {
+        overlay_params(self, params)
+    }
+    fn gpu_surface(&self) -> GpuSurface {
+        GpuSurface::new(SurfaceStyle::Clouds, [self.color; 4])
+    }
+    fn is_transparent(&self) -> bool {
+        true
+    }
 
-    noise_color * fragment.intensity
+    fn opacity(&self, fragment: &Fragment, uniforms: &Uniforms) -> f32 {
+        let view = (uniforms.camera_position - fragment.world_position).try_normalize(f32::EPSILON).unwrap_or(fragment.normal);
+        let grazing = 1.0 - fragment.normal.dot(&view).clamp(0.0, 1.0);
+        self.opacity + (self.limb_opacity - self.opacity) * grazing * grazing
+    }
+
+    fn fragment(&self, fragment: &Fragment, _uniforms: &Uniforms) -> Color {
+        fragment.lit(self.color)
+    }
 }
-pub fn sol_shader(fragment: &Fragment, uniforms: &Uniforms) -> Color {
-  let bright_color = Color::new(255, 255, 204); 
-  let dark_color = Color::new(255, 51, 0);    
-
-  let position = Vec3::new(
-      fragment.vertex_position.x,
-      fragment.vertex_position.y,
-      fragment.depth
-  );
 
-  let base_frequency = 0.2;
-  let pulsate_amplitude = 0.5;
-  let t = uniforms.time as f32 * 0.01;
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct KashyyykShader {
+    pub light_green: Color,
+    pub medium_green: Color,
+    pub dark_green: Color,
+    pub terrain_color: Color,
+    pub ice_color: Color,
+    pub zoom: f32,
+    pub vegetation_threshold: f32,
+    pub ice_cap_latitude: f32,
+}
 
-  let pulsate = (t * base_frequency).sin() * pulsate_amplitude;
+impl Default for KashyyykShader {
+    fn default() -> Self {
+        KashyyykShader {
+            light_green: Color::new(144, 238, 144),
+            medium_green: Color::new(34, 139, 34),
+            dark_green: Color::new(139, 69, 19),
+            terrain_color: Color::new(0, 100, 0),
+            ice_color: Color::new(240, 245, 250),
+            zoom: 300.0,
+            vegetation_threshold: 0.3,
+            ice_cap_latitude: 1.15,
+        }
+    }
+}
 
-  let zoom = 1000.0; 
-  let noise_value1 = uniforms.noise.get_noise_3d(
-      position.x * zoom,
-      position.y * zoom,
-      (position.z + pulsate) * zoom
-  );
-  let noise_value2 = uniforms.noise.get_noise_3d(
-      (position.x + 1000.0) * zoom,
-      (position.y + 1000.0) * zoom,
-      (position.z + 1000.0 + pulsate) * zoom
-  );
-  let noise_value = (noise_value1 + noise_value2) * 0.5;  
+impl PlanetShader for KashyyykShader {
 
-  let base_color = dark_color.lerp(&bright_color, noise_value);
+    fn tune(&mut self, params: &Table) -> Result<(), String> {
+        overlay_params(self, params)
+    }
+    fn gpu_surface(&self) -> GpuSurface {
+        GpuSurface::new(SurfaceStyle::Rocky, [self.terrain_color, self.dark_green, self.medium_green, self.light_green])
+    }
+    fn fragment(&self, fragment: &Fragment, uniforms: &Uniforms) -> Color {
+        let position = fragment.vertex_position;
+
+        let zoom = self.zoom;
+        let t = uniforms.time * 0.6;
+
+        let noise_value = uniforms.noise.get_noise_3d(
+            position.x * zoom,
+            position.y * zoom,
+            position.z * zoom + t
+        );
+
+        let vegetation_color = if noise_value > self.vegetation_threshold {
+            if noise_value > 0.7 {
+                self.dark_green.lerp(&self.medium_green, (noise_value - 0.7) * 3.0)
+            } else if noise_value > 0.5 {
+                self.medium_green.lerp(&self.light_green, (noise_value - 0.5) * 2.0)
+            } else {
+                self.light_green
+            }
+        } else {
+            self.terrain_color
+        };
+        let vegetation_color = vegetation_color.lerp(&self.ice_color, ice_cap(fragment, uniforms, self.ice_cap_latitude));
+
+        let intensity_variation = 0.9 + (noise_value * 0.1);
 
-  let distance_from_center = position.x.hypot(position.y);  
-  let radius = 0.5;  
-  let falloff = (1.0 - (distance_from_center / radius).clamp(0.0, 1.0)).powf(2.0);  
-
-  let brightened_color = base_color * (1.0 + falloff * 2.0);  
-
-  brightened_color * fragment.intensity
+        fragment.lit(vegetation_color) * intensity_variation
+    }
 }
 
-pub fn hoth_shader(fragment: &Fragment, uniforms: &Uniforms) -> Color {
-  let snow_color = Color::new(255, 255, 255); 
-  let ice_color = Color::new(173, 216, 230);  
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct EndorShader {
+    pub canopy_color: Color,
+    pub dark_canopy_color: Color,
+    pub clearing_color: Color,
+    pub lake_color: Color,
+    pub wisp_color: Color,
+    pub zoom: f32,
+    pub forest_fractal: Fractal,
+    pub lake_threshold: f32,
+    // Wisps are stretched this much along the direction they drift
+    pub wisp_stretch: f32,
+    pub wisp_coverage: f32,
+    pub wisp_speed: f32,
+}
 
-  let position = Vec3::new(
-      fragment.vertex_position.x,
-      fragment.vertex_position.y,
-      fragment.depth
-  );
+impl Default for EndorShader {
+    fn default() -> Self {
+        EndorShader {
+            canopy_color: Color::new(40, 96, 38),
+            dark_canopy_color: Color::new(18, 52, 22),
+            clearing_color: Color::new(92, 120, 60),
+            lake_color: Color::new(24, 50, 70),
+            wisp_color: Color::new(235, 240, 240),
+            zoom: 800.0,
+            forest_fractal: Fractal::new(5, 2.2, 0.55),
+            lake_threshold: -0.45,
+            wisp_stretch: 6.0,
+            wisp_coverage: 0.5,
+            wisp_speed: 8.0,
+        }
+    }
+}
 
-  let zoom = 500.0;
-  let t = uniforms.time as f32 * 0.01;  
-
-  let noise_value = uniforms.noise.get_noise_3d(
-      position.x * zoom,
-      position.y * zoom,
-      position.z * zoom + t
-  );
+impl PlanetShader for EndorShader {
 
-  let ice_threshold = 0.3; 
+    fn tune(&mut self, params: &Table) -> Result<(), String> {
+        overlay_params(self, params)
+    }
+    fn gpu_surface(&self) -> GpuSurface {
+        GpuSurface::new(SurfaceStyle::Rocky, [self.lake_color, self.dark_canopy_color, self.canopy_color, self.clearing_color])
+    }
+    fn fragment(&self, fragment: &Fragment, uniforms: &Uniforms) -> Color {
+        let p = fragment.vertex_position * self.zoom;
+
+        // Canopy everywhere except a few lakes, mottled by fine fBm into darker and lighter stands
+        let terrain = noise::fbm(uniforms.noise, p * 0.4, &self.forest_fractal);
+        let canopy = noise::fbm(uniforms.noise, p * 3.0 + Vec3::new(31.0, 7.0, 59.0), &self.forest_fractal) * 0.5 + 0.5;
+        let ground = if terrain < self.lake_threshold {
+            self.lake_color
+        } else if terrain > 0.45 {
+            self.clearing_color.lerp(&self.canopy_color, canopy)
+        } else {
+            self.dark_canopy_color.lerp(&self.canopy_color, canopy)
+        };
+
+        // Wisps are part of the surface rather than a separate shell, so they stay thin and low
+        let t = uniforms.time * self.wisp_speed;
+        let q = Vec3::new(p.x / self.wisp_stretch + t, p.y, p.z / self.wisp_stretch) * 1.5;
+        let wisp = noise::turbulence(uniforms.noise, q, &self.forest_fractal);
+        let cover = ((wisp - self.wisp_coverage) / 0.2).clamp(0.0, 0.8);
 
-  let base_color = if noise_value > ice_threshold {
-      ice_color  
-  } else {
-      snow_color 
-  };
-
-  let intensity_variation = 0.9 + (noise_value * 0.1);  
-
-  base_color * fragment.intensity * intensity_variation
+        fragment.lit(ground.lerp(&self.wisp_color, cover))
+    }
 }
-pub fn kashyyyk_shader(fragment: &Fragment, uniforms: &Uniforms) -> Color {
-  let light_green = Color::new(144, 238, 144); 
-  let medium_green = Color::new(34, 139, 34);   
-  let dark_green = Color::new(139, 69, 19);      
-  let terrain_color = Color::new(0, 100, 0);  
 
-  let position = Vec3::new(
-      fragment.vertex_position.x,
-      fragment.vertex_position.y,
-      fragment.depth
-  );
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct GaseosoShader {
+    pub zoom: f32,
+    pub offset_x: f32,
+    pub offset_y: f32,
+    pub base_color: Color,
+    pub band_color: Color,
+    pub storm_color: Color,
+    pub band_count: f32,
+    pub band_turbulence: f32,
+}
 
-  let zoom = 300.0;
-  let t = uniforms.time as f32 * 0.01; 
-
-  let noise_value = uniforms.noise.get_noise_3d(
-      position.x * zoom,
-      position.y * zoom,
-      position.z * zoom + t
-  );
+impl Default for GaseosoShader {
+    fn default() -> Self {
+        GaseosoShader {
+            zoom: 1000.0,
+            offset_x: 50.0,
+            offset_y: 50.0,
+            base_color: Color::new(128, 0, 0),
+            band_color: Color::new(255, 204, 153),
+            storm_color: Color::new(192, 57, 43),
+            band_count: 9.0,
+            band_turbulence: 0.15,
+        }
+    }
+}
 
-  let vegetation_threshold = 0.3;  
+impl PlanetShader for GaseosoShader {
 
-  let vegetation_color = if noise_value > vegetation_threshold {
-      if noise_value > 0.7 {
-          dark_green.lerp(&medium_green, (noise_value - 0.7) * 3.0)  
-      } else if noise_value > 0.5 {
-          medium_green.lerp(&light_green, (noise_value - 0.5) * 2.0)  
-      } else {
-          light_green  
-      }
-  } else {
-      terrain_color  
-  };
-
-  let intensity_variation = 0.9 + (noise_value * 0.1);  
-
-  vegetation_color * fragment.intensity * intensity_variation 
-}
-
-pub fn gaseoso_shader(fragment: &Fragment, uniforms: &Uniforms) -> Color {
-  let zoom = 1000.0; 
-  let ox = 50.0;    
-  let oy = 50.0;    
+    fn tune(&mut self, params: &Table) -> Result<(), String> {
+        overlay_params(self, params)
+    }
+    fn gpu_surface(&self) -> GpuSurface {
+        GpuSurface::new(SurfaceStyle::Bands, [self.base_color, self.band_color, self.storm_color, self.storm_color])
+    }
+    fn fragment(&self, fragment: &Fragment, uniforms: &Uniforms) -> Color {
+        let zoom = self.zoom;
+        let ox = self.offset_x;
+        let oy = self.offset_y;
 
-  let x = fragment.vertex_position.x;
-  let y = fragment.vertex_position.y;
-  let t = uniforms.time as f32 * 0.1;
+        let x = fragment.vertex_position.x;
+        let y = fragment.vertex_position.y;
+        let z = fragment.vertex_position.z;
+        let t = uniforms.time * 6.0;
 
-  let base_color = Color::new(128, 0, 0);        
-  let band_color = Color::new(255, 204, 153);       
-  let storm_color = Color::new(192, 57, 43);        
-  let noise_value = uniforms.noise.get_noise_2d(x * zoom + ox, y * zoom * 0.5 + oy + t);
-  let band_intensity = (noise_value * 0.5) + 0.5;
+        // Bands follow latitude, with noise bending their edges as they flow around the planet
+        let p = fragment.vertex_position * zoom * 0.3;
+        let noise_value = uniforms.noise.get_noise_3d(p.x + ox + t, p.y * 0.3 + oy, p.z);
+        let band = (fragment.latitude() + noise_value * self.band_turbulence) * self.band_count;
+        let band_intensity = (band.sin() * 0.5) + 0.5;
 
-  let storm_noise = uniforms.noise.get_noise_2d(x * zoom * 1.5 + ox, y * zoom * 1.5 + oy + t);
-  let storm_intensity = (storm_noise * 0.5) + 0.5;
+        let storm_noise = uniforms.noise.get_noise_3d(x * zoom * 1.5 + ox, y * zoom * 1.5 + oy + t, z * zoom * 1.5);
+        let storm_intensity = (storm_noise * 0.5) + 0.5;
 
-  let color = if band_intensity > 0.6 {
-      base_color.lerp(&band_color, band_intensity)
-  } else if storm_intensity > 0.7 {
-      storm_color 
-  } else {
-      base_color 
-  };
+        let color = if band_intensity > 0.6 {
+            self.base_color.lerp(&self.band_color, band_intensity)
+        } else if storm_intensity > 0.7 {
+            self.storm_color
+        } else {
+            self.base_color
+        };
 
-  color * fragment.intensity
+        fragment.lit(color)
+    }
 }
 
-pub fn death_star_shader(fragment: &Fragment, uniforms: &Uniforms) -> Color {
-  let position = fragment.vertex_position;
-  let x = position.x;
-  let y = position.y;
-
-  let line_spacing = 0.1;
-  let line_width = 0.02;  
-  let circle_radius = 0.16;
-  let center = Vec3::new(0.0, 0.17, 0.0); 
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BespinShader {
+    pub zoom: f32,
+    pub cream_color: Color,
+    pub tan_color: Color,
+    pub rust_color: Color,
+    pub band_count: f32,
+    pub band_turbulence: f32,
+    pub vortex_fractal: Fractal,
+    pub warp_strength: f32,
+    // Radians per second at the equator; higher latitudes lag behind, shearing the vortices
+    pub swirl_speed: f32,
+    // Centre of the persistent storm in radians, and its half-height; it is twice as wide
+    pub spot_latitude: f32,
+    pub spot_longitude: f32,
+    pub spot_radius: f32,
+    // Radians per second the storm drifts in longitude and spins around its centre
+    pub spot_drift: f32,
+    pub spot_spin: f32,
+    pub spot_color: Color,
+}
 
-  let line_color = Color::new(128, 128, 128);   
-  let circle_color = Color::new(64, 64, 64); 
-  let background_color = Color::new(102, 102, 102); 
-
-  let in_vertical_line = (x / line_spacing).fract().abs() < line_width;
-  let in_horizontal_line = (y / line_spacing).fract().abs() < line_width;
+impl Default for BespinShader {
+    fn default() -> Self {
+        BespinShader {
+            zoom: 250.0,
+            cream_color: Color::new(240, 222, 190),
+            tan_color: Color::new(205, 160, 110),
+            rust_color: Color::new(160, 90, 60),
+            band_count: 12.0,
+            band_turbulence: 0.25,
+            vortex_fractal: Fractal::new(4, 2.0, 0.5),
+            warp_strength: 90.0,
+            swirl_speed: 0.15,
+            spot_latitude: -0.35,
+            spot_longitude: 1.0,
+            spot_radius: 0.18,
+            spot_drift: 0.05,
+            spot_spin: 1.5,
+            spot_color: Color::new(190, 70, 45),
+        }
+    }
+}
 
-  let distance_from_center = ((x - center.x).powi(2) + (y - center.y).powi(2)).sqrt();
-  let in_circle = distance_from_center <= circle_radius;
+impl BespinShader {
+    // How far into the storm the fragment is (0 outside, 1 at the eye) and the swirled
+    // position to sample its streaks at
+    fn spot(&self, fragment: &Fragment, time: f32) -> (f32, Vec3) {
+        let center = self.spot_longitude + time * self.spot_drift;
+        let longitude = (fragment.longitude() - center + std::f32::consts::PI).rem_euclid(std::f32::consts::TAU) - std::f32::consts::PI;
+        let dx = longitude * self.spot_latitude.cos() / (self.spot_radius * 2.0);
+        let dy = (fragment.latitude() - self.spot_latitude) / self.spot_radius;
+        let distance = dx.hypot(dy);
+        if distance >= 1.0 {
+            return (0.0, Vec3::zeros());
+        }
 
-  let final_color = if in_circle {
-      circle_color 
-  } else if in_vertical_line || in_horizontal_line {
-      line_color 
-  } else {
-      background_color
-  };
+        // The inside turns faster than the rim, winding the streaks into a spiral
+        let angle = (1.0 - distance).powi(2) * (time * self.spot_spin + 3.0);
+        let (sin, cos) = angle.sin_cos();
+        let swirled = Vec3::new(dx * cos - dy * sin, dx * sin + dy * cos, 0.0);
+        let inside = ((1.0 - distance) / 0.3).clamp(0.0, 1.0);
+        (inside, swirled)
+    }
+}
 
-  final_color * fragment.intensity
-}
-
-pub fn tatooine_shader(fragment: &Fragment, uniforms: &Uniforms) -> Color {
-  let zoom = 1000.0;
-  let time_factor = uniforms.time as f32 * 0.01; 
-  let x = fragment.vertex_position.x;
-  let y = fragment.vertex_position.y;
+impl PlanetShader for BespinShader {
 
-  let base_rock_color = Color::new(139, 69, 19);  
-  let mountain_color = Color::new(105, 105, 105); 
-  let plain_color = Color::new(205, 133, 63);     
-  let land_color = Color::new(163, 163, 117);     
+    fn tune(&mut self, params: &Table) -> Result<(), String> {
+        overlay_params(self, params)
+    }
+    fn gpu_surface(&self) -> GpuSurface {
+        GpuSurface::new(SurfaceStyle::Bands, [self.tan_color, self.cream_color, self.rust_color, self.spot_color])
+    }
+    fn fragment(&self, fragment: &Fragment, uniforms: &Uniforms) -> Color {
+        let latitude = fragment.latitude();
 
-  let base_noise = uniforms.noise.get_noise_2d(
-      x * zoom * 0.5 + time_factor,
-      y * zoom * 0.5 + time_factor
-  );
-
-  let mountain_noise = uniforms.noise.get_noise_2d(
-      x * zoom + time_factor * 0.5,
-      y * zoom + time_factor * 0.5
-  );
+        // Differential rotation: each latitude turns at its own rate, so the warped noise shears into vortices
+        let angle = uniforms.time * self.swirl_speed * (1.0 - 0.4 * latitude.sin().powi(2));
+        let (sin, cos) = angle.sin_cos();
+        let position = fragment.vertex_position;
+        let rotated = Vec3::new(position.x * cos - position.z * sin, position.y, position.x * sin + position.z * cos);
+        let warp = noise::domain_warp(uniforms.noise, rotated * self.zoom, &self.vortex_fractal, self.warp_strength);
 
-  let continent_shift = (uniforms.time as f32 * 0.005).sin() * 0.1;
+        let band = ((latitude + warp * self.band_turbulence) * self.band_count).sin() * 0.5 + 0.5;
+        let color = if band > 0.5 {
+            self.tan_color.lerp(&self.cream_color, (band - 0.5) * 2.0)
+        } else {
+            self.rust_color.lerp(&self.tan_color, band * 2.0)
+        };
 
-  let continental_noise = uniforms.noise.get_noise_2d(
-      (x + continent_shift) * zoom * 0.8,
-      (y + continent_shift) * zoom * 0.8
-  );
+        let (inside, swirled) = self.spot(fragment, uniforms.time);
+        let color = if inside > 0.0 {
+            let streaks = uniforms.noise.get_noise_3d(swirled.x * 600.0, swirled.y * 600.0, 0.0) * 0.5 + 0.5;
+            let storm = self.spot_color.lerp(&self.tan_color, streaks * 0.5);
+            color.lerp(&storm, inside)
+        } else {
+            color
+        };
 
-  let mountain_threshold = 0.6;
-  let land_threshold = -0.3;
+        fragment.lit(color)
+    }
+}
 
-  let final_color = if base_noise > mountain_threshold {
-      mountain_color.lerp(&base_rock_color, mountain_noise)
-  } else if continental_noise < land_threshold {
-      land_color 
-  } else {
-      plain_color.lerp(&base_rock_color, continental_noise) 
-  };
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DeathStarShader {
+    pub line_spacing: f32,
+    pub line_width: f32,
+    pub circle_radius: f32,
+    #[serde(with = "vec3_array")]
+    pub dish_center: Vec3,
+    pub line_color: Color,
+    pub circle_color: Color,
+    pub background_color: Color,
+    // Added to the object's roughness along the panel seams
+    pub seam_roughness: f32,
+}
 
-  final_color * fragment.intensity
+impl Default for DeathStarShader {
+    fn default() -> Self {
+        DeathStarShader {
+            line_spacing: 0.1,
+            line_width: 0.02,
+            circle_radius: 0.16,
+            dish_center: Vec3::new(0.0, 0.17, 0.0),
+            line_color: Color::new(128, 128, 128),
+            circle_color: Color::new(64, 64, 64),
+            background_color: Color::new(102, 102, 102),
+            seam_roughness: 0.3,
+        }
+    }
 }
 
-  
+impl PlanetShader for DeathStarShader {
+
+    fn tune(&mut self, params: &Table) -> Result<(), String> {
+        overlay_params(self, params)
+    }
+    fn gpu_surface(&self) -> GpuSurface {
+        GpuSurface::new(SurfaceStyle::Metal, [self.background_color, self.line_color, self.circle_color, self.circle_color])
+    }
+    fn fragment(&self, fragment: &Fragment, uniforms: &Uniforms) -> Color {
+        let position = fragment.vertex_position;
+        let x = position.x;
+        let y = position.y;
+        let center = self.dish_center;
+
+        let in_vertical_line = (x / self.line_spacing).fract().abs() < self.line_width;
+        let in_horizontal_line = (y / self.line_spacing).fract().abs() < self.line_width;
+
+        let distance_from_center = ((x - center.x).powi(2) + (y - center.y).powi(2)).sqrt();
+        let in_circle = distance_from_center <= self.circle_radius;
+
+        let final_color = if in_circle {
+            self.circle_color
+        } else if in_vertical_line || in_horizontal_line {
+            self.line_color
+        } else {
+            self.background_color
+        };
+
+        // The scene gives the hull its metal finish; seams are rougher and the dish is a dull recess
+        let base = uniforms.material;
+        let material = pbr::Material {
+            albedo: final_color,
+            metallic: if in_circle { base.metallic * 0.5 } else { base.metallic },
+            roughness: if in_vertical_line || in_horizontal_line { base.roughness + self.seam_roughness } else { base.roughness },
+            ..base
+        };
+        pbr::shade(&material, fragment, uniforms)
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TatooineShader {
+    pub zoom: f32,
+    pub base_rock_color: Color,
+    pub mountain_color: Color,
+    pub plain_color: Color,
+    pub land_color: Color,
+    pub mountain_threshold: f32,
+    pub land_threshold: f32,
+    pub terrain_fractal: Fractal,
+    pub warp_strength: f32,
+    pub dune_frequency: f32,
+    pub ice_color: Color,
+    pub ice_cap_latitude: f32,
+    pub city_light_color: Color,
+    pub city_light_zoom: f32,
+    pub night_threshold: f32,
+    pub craters: Craters,
+}
+
+impl Default for TatooineShader {
+    fn default() -> Self {
+        TatooineShader {
+            zoom: 1000.0,
+            base_rock_color: Color::new(139, 69, 19),
+            mountain_color: Color::new(105, 105, 105),
+            plain_color: Color::new(205, 133, 63),
+            land_color: Color::new(163, 163, 117),
+            mountain_threshold: 0.6,
+            land_threshold: -0.3,
+            terrain_fractal: Fractal::default(),
+            warp_strength: 60.0,
+            dune_frequency: 0.4,
+            ice_color: Color::new(235, 225, 210),
+            ice_cap_latitude: 1.3,
+            city_light_color: Color::new(255, 180, 90),
+            city_light_zoom: 700.0,
+            night_threshold: 0.15,
+            craters: Craters { frequency: 5.0, density: 0.12, ..Craters::default() },
+        }
+    }
+}
+
+impl PlanetShader for TatooineShader {
+
+    fn tune(&mut self, params: &Table) -> Result<(), String> {
+        overlay_params(self, params)
+    }
+    fn gpu_surface(&self) -> GpuSurface {
+        GpuSurface::new(SurfaceStyle::Rocky, [self.base_rock_color, self.plain_color, self.land_color, self.mountain_color])
+    }
+    fn fragment(&self, fragment: &Fragment, uniforms: &Uniforms) -> Color {
+        let zoom = self.zoom;
+        let time_factor = uniforms.time * 0.6;
+        let p = fragment.vertex_position * zoom;
+        let fractal = &self.terrain_fractal;
+
+        let mountain_noise = noise::ridged(
+            uniforms.noise,
+            p * 0.5 + Vec3::new(time_factor, time_factor, 0.0),
+            fractal
+        );
+
+        let continent_shift = (uniforms.time * 0.3).sin() * 0.1 * zoom;
+
+        let continental_noise = noise::domain_warp(
+            uniforms.noise,
+            (p + Vec3::new(continent_shift, continent_shift, 0.0)) * 0.8,
+            fractal,
+            self.warp_strength
+        );
+
+        // Wind-blown ripples whose crests bend with low-frequency fBm
+        let dune_warp = noise::fbm(uniforms.noise, p * 2.0, fractal);
+        let dunes = ((p.x + p.z) * self.dune_frequency + dune_warp * 8.0).sin() * 0.5 + 0.5;
+
+        let final_color = if mountain_noise > self.mountain_threshold {
+            let height = (mountain_noise - self.mountain_threshold) / (1.0 - self.mountain_threshold);
+            self.base_rock_color.lerp(&self.mountain_color, height)
+        } else if continental_noise < self.land_threshold {
+            self.land_color
+        } else {
+            self.plain_color.lerp(&self.base_rock_color, continental_noise) * (0.85 + dunes * 0.15)
+        };
+        let final_color = final_color.lerp(&self.ice_color, ice_cap(fragment, uniforms, self.ice_cap_latitude));
+
+        let (cratered, shade) = apply_craters(fragment, uniforms, &self.craters);
+        cratered.lit(final_color) * shade
+    }
+
+    fn emission(&self, fragment: &Fragment, uniforms: &Uniforms) -> Color {
+        city_lights(fragment, uniforms, self.city_light_zoom, self.night_threshold, self.city_light_color) + uniforms.material.emissive
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AsteroidShader {
+    pub rock_color: Color,
+    pub dust_color: Color,
+    pub zoom: f32,
+    pub craters: Craters,
+}
+
+impl Default for AsteroidShader {
+    fn default() -> Self {
+        AsteroidShader {
+            rock_color: Color::new(110, 100, 90),
+            dust_color: Color::new(60, 55, 50),
+            zoom: 20.0,
+            craters: Craters { frequency: 3.0, density: 0.7, max_radius: 0.5, ..Craters::default() },
+        }
+    }
+}
+
+impl PlanetShader for AsteroidShader {
+
+    fn tune(&mut self, params: &Table) -> Result<(), String> {
+        overlay_params(self, params)
+    }
+    fn gpu_surface(&self) -> GpuSurface {
+        GpuSurface::new(SurfaceStyle::Rocky, [self.dust_color, self.rock_color, self.rock_color, self.rock_color])
+    }
+    fn fragment(&self, fragment: &Fragment, uniforms: &Uniforms) -> Color {
+        let position = fragment.vertex_position * self.zoom;
+        let noise_value = uniforms.noise.get_noise_3d(position.x, position.y, position.z);
+
+        let color = self.rock_color.lerp(&self.dust_color, noise_value * 0.5 + 0.5);
+        let (cratered, shade) = apply_craters(fragment, uniforms, &self.craters);
+        cratered.lit(color) * shade
+    }
+}
+
+// Dirty ice: a dark dusty crust with bright patches of exposed ice that fizz where the sun
+// warms them
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CometShader {
+    pub crust_color: Color,
+    pub ice_color: Color,
+    pub vent_color: Color,
+    pub zoom: f32,
+    pub ice_threshold: f32,
+    pub fractal: Fractal,
+    pub craters: Craters,
+}
+
+impl Default for CometShader {
+    fn default() -> Self {
+        CometShader {
+            crust_color: Color::new(48, 44, 42),
+            ice_color: Color::new(215, 230, 245),
+            vent_color: Color::new(120, 190, 255),
+            zoom: 250.0,
+            ice_threshold: 0.15,
+            fractal: Fractal::default(),
+            craters: Craters { frequency: 4.0, density: 0.5, ..Craters::default() },
+        }
+    }
+}
+
+impl CometShader {
+    fn ice(&self, fragment: &Fragment, uniforms: &Uniforms) -> f32 {
+        let value = noise::fbm(uniforms.noise, fragment.vertex_position * self.zoom, &self.fractal);
+        ((value - self.ice_threshold) / 0.15).clamp(0.0, 1.0)
+    }
+}
+
+impl PlanetShader for CometShader {
+
+    fn tune(&mut self, params: &Table) -> Result<(), String> {
+        overlay_params(self, params)
+    }
+    fn gpu_surface(&self) -> GpuSurface {
+        GpuSurface::new(SurfaceStyle::Rocky, [self.crust_color, self.crust_color, self.ice_color, self.ice_color])
+    }
+    fn fragment(&self, fragment: &Fragment, uniforms: &Uniforms) -> Color {
+        let color = self.crust_color.lerp(&self.ice_color, self.ice(fragment, uniforms));
+        let (cratered, shade) = apply_craters(fragment, uniforms, &self.craters);
+        cratered.lit(color) * shade
+    }
+
+    fn emission(&self, fragment: &Fragment, uniforms: &Uniforms) -> Color {
+        let warmth = fragment.intensity.clamp(0.0, 1.0);
+        self.vent_color * (self.ice(fragment, uniforms) * warmth * warmth * 0.6) + uniforms.material.emissive
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ShipShader {
+    pub hull_color: Color,
+    pub stripe_color: Color,
+    pub engine_color: Color,
+    pub panel_spacing: f32,
+    pub shininess: f32,
+    pub ambient: f32,
+}
+
+impl Default for ShipShader {
+    fn default() -> Self {
+        ShipShader {
+            hull_color: Color::new(190, 192, 198),
+            stripe_color: Color::new(200, 60, 40),
+            engine_color: Color::new(120, 200, 255),
+            panel_spacing: 0.12,
+            shininess: 40.0,
+            ambient: 0.3,
+        }
+    }
+}
+
+// Object-space coordinates of the ship model: nose on +Z, wings along X
+fn in_engine(position: &Vec3) -> bool {
+    position.z < -0.4 && position.x.abs() < 0.12 && position.y.abs() < 0.1
+}
+
+impl PlanetShader for ShipShader {
+
+    fn tune(&mut self, params: &Table) -> Result<(), String> {
+        overlay_params(self, params)
+    }
+    fn gpu_surface(&self) -> GpuSurface {
+        GpuSurface::new(SurfaceStyle::Metal, [self.hull_color, self.stripe_color, self.engine_color, self.engine_color])
+    }
+    fn fragment(&self, fragment: &Fragment, uniforms: &Uniforms) -> Color {
+        let position = fragment.vertex_position;
+        if in_engine(&position) {
+            return Color::black();
+        }
+
+        let wing = position.x.abs();
+        let mut color = if (0.34..0.42).contains(&wing) {
+            self.stripe_color
+        } else {
+            self.hull_color
+        };
+        if (position.z / self.panel_spacing).fract().abs() < 0.08 {
+            color = color * 0.75;
+        }
+
+        // Some ambient light keeps the hull readable on its night side
+        let ambient = Color::new(255, 255, 255) * (self.ambient - fragment.intensity).max(0.0);
+        let highlight = specular(fragment, uniforms, self.shininess) * 0.6;
+        color.blend_multiply(&(fragment.light + ambient)) + highlight
+    }
+
+    // The back plate glows like an engine regardless of lighting
+    fn emission(&self, fragment: &Fragment, _uniforms: &Uniforms) -> Color {
+        if in_engine(&fragment.vertex_position) { self.engine_color } else { Color::black() }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CloudShader {
+    pub cloud_color: Color,
+    pub zoom: f32,
+    pub drift_speed: f32,
+    pub coverage: f32,
+    pub max_opacity: f32,
+}
+
+impl Default for CloudShader {
+    fn default() -> Self {
+        CloudShader {
+            cloud_color: Color::new(255, 255, 255),
+            zoom: 300.0,
+            drift_speed: 20.0,
+            coverage: 0.1,
+            max_opacity: 0.85,
+        }
+    }
+}
+
+impl CloudShader {
+    pub fn storm() -> Self {
+        CloudShader {
+            cloud_color: Color::new(150, 150, 160),
+            zoom: 500.0,
+            drift_speed: 40.0,
+            coverage: -0.1,
+            max_opacity: 0.9,
+        }
+    }
+}
+
+impl PlanetShader for CloudShader {
+
+    fn tune(&mut self, params: &Table) -> Result<(), String> {
+        overlay_params(self, params)
+    }
+    fn gpu_surface(&self) -> GpuSurface {
+        GpuSurface::new(SurfaceStyle::Clouds, [self.cloud_color; 4])
+    }
+    fn is_transparent(&self) -> bool {
+        true
+    }
+
+    fn opacity(&self, fragment: &Fragment, uniforms: &Uniforms) -> f32 {
+        let p = fragment.vertex_position * self.zoom;
+        let t = uniforms.time * self.drift_speed;
+        let base = uniforms.noise.get_noise_3d(p.x + t, p.y, p.z);
+        let detail = uniforms.noise.get_noise_3d(p.x * 3.0 - t, p.y * 3.0, p.z * 3.0);
+        let density = base * 0.7 + detail * 0.3;
+
+        ((density - self.coverage) / 0.3).clamp(0.0, 1.0) * self.max_opacity
+    }
+
+    fn fragment(&self, fragment: &Fragment, _uniforms: &Uniforms) -> Color {
+        fragment.lit(self.cloud_color)
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AuroraShader {
+    pub low_color: Color,
+    pub high_color: Color,
+    // Radians from the equator to the middle of the ring, and the ring's half-width
+    pub latitude: f32,
+    pub width: f32,
+    // How far the ring swings towards and away from the pole
+    pub waviness: f32,
+    // Curtain rays around the ring, and how fast they shimmer
+    pub ray_count: f32,
+    pub speed: f32,
+    // Object-space distance the brightest curtains rise above the shell
+    pub height: f32,
+    pub max_opacity: f32,
+    // Scales the emission past white so the ribbons bloom
+    pub brightness: f32,
+}
+
+impl Default for AuroraShader {
+    fn default() -> Self {
+        AuroraShader {
+            low_color: Color::new(60, 255, 140),
+            high_color: Color::new(170, 70, 255),
+            latitude: 1.1,
+            width: 0.12,
+            waviness: 0.08,
+            ray_count: 90.0,
+            speed: 0.8,
+            height: 0.06,
+            max_opacity: 0.8,
+            brightness: 1.6,
+        }
+    }
+}
+
+impl AuroraShader {
+    // Where a point sits across the ring (0 on its equator side, 1 on its pole side) and how
+    // bright the curtain is there, 0 outside the ring
+    fn ribbon(&self, position: &Vec3, uniforms: &Uniforms) -> (f32, f32) {
+        let radius = position.magnitude().max(f32::EPSILON);
+        let latitude = (position.y / radius).clamp(-1.0, 1.0).asin().abs();
+        let longitude = position.z.atan2(position.x);
+        let t = uniforms.time * self.speed;
+
+        let center = self.latitude + self.waviness * (longitude * 5.0 + t).sin();
+        let offset = (latitude - center) / self.width;
+        if offset.abs() >= 1.0 {
+            return (0.0, 0.0);
+        }
+
+        let rays = uniforms.noise.get_noise_3d(longitude * self.ray_count, t * 40.0, position.y.signum() * 100.0) * 0.5 + 0.5;
+        let curtain = (1.0 - offset * offset) * rays * rays;
+        ((offset + 1.0) / 2.0, curtain)
+    }
+}
+
+impl PlanetShader for AuroraShader {
+
+    fn tune(&mut self, params: &Table) -> Result<(), String> {
+        overlay_params(self, params)
+    }
+    fn gpu_surface(&self) -> GpuSurface {
+        GpuSurface::new(SurfaceStyle::Aurora, [self.low_color, self.low_color, self.high_color, self.high_color])
+    }
+    fn is_transparent(&self) -> bool {
+        true
+    }
+
+    fn is_additive(&self) -> bool {
+        true
+    }
+
+    fn animated_vertices(&self) -> bool {
+        true
+    }
+
+    // Bright curtains stand taller, so the ribbons ripple above the shell
+    fn vertex(&self, vertex: &Vertex, uniforms: &Uniforms) -> Vertex {
+        let (_, curtain) = self.ribbon(&vertex.position, uniforms);
+        displaced_vertex_shader(vertex, uniforms, curtain * self.height)
+    }
+
+    fn opacity(&self, fragment: &Fragment, uniforms: &Uniforms) -> f32 {
+        self.ribbon(&fragment.vertex_position, uniforms).1 * self.max_opacity
+    }
+
+    // The aurora gives off all of its light; the suns don't light it
+    fn fragment(&self, _fragment: &Fragment, _uniforms: &Uniforms) -> Color {
+        Color::black()
+    }
+
+    fn emission(&self, fragment: &Fragment, uniforms: &Uniforms) -> Color {
+        let (across, _) = self.ribbon(&fragment.vertex_position, uniforms);
+        self.low_color.lerp(&self.high_color, across) * self.brightness
+    }
+}
+
+pub struct MaterialShader {
+    pub material: Material,
+}
+
+impl MaterialShader {
+    pub fn new(material: Material) -> Self {
+        MaterialShader { material }
+    }
+}
+
+fn vec3_to_color(v: &Vec3) -> Color {
+    Color::new(
+        (v.x * 255.0).clamp(0.0, 255.0) as u8,
+        (v.y * 255.0).clamp(0.0, 255.0) as u8,
+        (v.z * 255.0).clamp(0.0, 255.0) as u8,
+    )
+}
+
+impl PlanetShader for MaterialShader {
+    fn fragment(&self, fragment: &Fragment, uniforms: &Uniforms) -> Color {
+        let material = &self.material;
+
+        let diffuse = vec3_to_color(&material.diffuse);
+        let albedo = match &material.diffuse_texture {
+            Some(texture) => texture.sample(fragment.tex_coords).blend_multiply(&diffuse),
+            None => diffuse,
+        };
+
+        let highlight = if material.shininess > 0.0 {
+            vec3_to_color(&material.specular).blend_multiply(&specular(fragment, uniforms, material.shininess))
+        } else {
+            Color::black()
+        };
+
+        fragment.lit(albedo) + highlight
+    }
+}
@@ -0,0 +1,146 @@
+use nalgebra_glm::{Vec3, Mat4};
+use crate::mesh::IndexedMesh;
+use crate::obj::Obj;
+use crate::renderer::create_model_matrix;
+use crate::collision::{BoundingSphere, BODY_RADIUS};
+use crate::gravity::attraction;
+use crate::scene::Scene;
+
+pub const SHIP_MODEL: &str = "assets/models/ship.obj";
+
+const THRUST: f32 = 2.0;
+const YAW_RATE: f32 = 1.6;
+const PITCH_RATE: f32 = 1.2;
+// Fraction of the velocity lost per second; small enough that gravity still shapes the path
+const DRAG: f32 = 0.15;
+const MAX_PITCH: f32 = 1.4;
+// Share of the speed into a surface kept when bouncing off it
+const RESTITUTION: f32 = 0.5;
+// Hitting a body faster than this destroys the ship instead of bouncing
+pub const CRASH_SPEED: f32 = 1.5;
+// How far ahead, in seconds, the predicted trajectory reaches and in how many steps
+const PREDICTION_TIME: f32 = 6.0;
+const PREDICTION_STEPS: usize = 120;
+
+// Inputs for one frame, each in -1..=1
+#[derive(Debug, Default, Clone, Copy)]
+pub struct ShipControls {
+    pub thrust: f32,
+    pub yaw: f32,
+    pub pitch: f32,
+}
+
+pub struct Ship {
+    pub position: Vec3,
+    pub velocity: Vec3,
+    pub yaw: f32,
+    pub pitch: f32,
+    pub scale: f32,
+    pub shader: String,
+    pub mesh: IndexedMesh,
+    mesh_radius: f32,
+}
+
+impl Ship {
+    pub fn load(filename: &str, position: Vec3, forward: Vec3) -> Result<Self, String> {
+        let obj = Obj::load(filename).map_err(|e| format!("Failed to load {}: {}", filename, e))?;
+        let forward = forward.try_normalize(f32::EPSILON).unwrap_or(Vec3::z());
+        let mesh = obj.get_indexed_mesh();
+        let mesh_radius = mesh.vertices.iter()
+            .map(|vertex| vertex.position.magnitude())
+            .fold(0.0, f32::max);
+
+        Ok(Ship {
+            position,
+            velocity: Vec3::zeros(),
+            yaw: forward.x.atan2(forward.z),
+            pitch: forward.y.asin().clamp(-MAX_PITCH, MAX_PITCH),
+            scale: 0.2,
+            shader: "ship".to_string(),
+            mesh,
+            mesh_radius,
+        })
+    }
+
+    // The model's nose points along +Z; pitch turns it up, yaw turns it around Y
+    pub fn forward(&self) -> Vec3 {
+        Vec3::new(
+            self.pitch.cos() * self.yaw.sin(),
+            self.pitch.sin(),
+            self.pitch.cos() * self.yaw.cos(),
+        )
+    }
+
+    pub fn up(&self) -> Vec3 {
+        Vec3::new(
+            -self.pitch.sin() * self.yaw.sin(),
+            self.pitch.cos(),
+            -self.pitch.sin() * self.yaw.cos(),
+        )
+    }
+
+    pub fn model_matrix(&self) -> Mat4 {
+        create_model_matrix(self.position, self.scale, Vec3::new(-self.pitch, self.yaw, 0.0))
+    }
+
+    pub fn bounds(&self) -> BoundingSphere {
+        BoundingSphere { center: self.position, radius: self.mesh_radius * self.scale }
+    }
+
+    // Pushes the ship out of a surface and reflects the part of its velocity heading into it
+    pub fn bounce(&mut self, normal: Vec3, depth: f32) {
+        self.position += normal * depth;
+        let approach = self.velocity.dot(&normal);
+        if approach < 0.0 {
+            self.velocity -= normal * approach * (1.0 + RESTITUTION);
+        }
+    }
+
+    pub fn update(&mut self, controls: &ShipControls, gravity: Vec3, delta_time: f32) {
+        self.yaw += controls.yaw * YAW_RATE * delta_time;
+        self.pitch = (self.pitch + controls.pitch * PITCH_RATE * delta_time).clamp(-MAX_PITCH, MAX_PITCH);
+
+        let acceleration = self.forward() * (controls.thrust * THRUST) + gravity;
+        let (position, velocity) = integrate(self.position, self.velocity, acceleration, delta_time);
+        self.position = position;
+        self.velocity = velocity;
+    }
+
+    // Coasting path over the next PREDICTION_TIME seconds, while the bodies keep moving at
+    // `time_rate` simulated seconds per second. Stops early where it would hit a body.
+    pub fn predict(&self, scene: &Scene, time: f32, time_rate: f32, path: &mut Vec<Vec3>) {
+        let step = PREDICTION_TIME / PREDICTION_STEPS as f32;
+        let mut gravity = scene.gravity.clone();
+        let (mut position, mut velocity) = (self.position, self.velocity);
+
+        path.clear();
+        path.push(position);
+        for i in 0..PREDICTION_STEPS {
+            let body_time = time + i as f32 * step * time_rate;
+            let body_position = |index: usize| match &gravity {
+                Some(gravity) => gravity.bodies[index].position,
+                None => scene.position(index, body_time),
+            };
+
+            let pull = attraction(position, (0..scene.objects.len()).map(|index| (body_position(index), scene.objects[index].mass)));
+            (position, velocity) = integrate(position, velocity, pull, step);
+            path.push(position);
+
+            let crashed = (0..scene.objects.len()).any(|index| {
+                (position - body_position(index)).magnitude() < scene.objects[index].scale * BODY_RADIUS
+            });
+            if crashed {
+                break;
+            }
+            if let Some(gravity) = gravity.as_mut() {
+                gravity.step(step * time_rate);
+            }
+        }
+    }
+}
+
+// Semi-implicit Euler step with drag, shared by the ship and its prediction so they agree
+fn integrate(position: Vec3, velocity: Vec3, acceleration: Vec3, delta_time: f32) -> (Vec3, Vec3) {
+    let velocity = (velocity + acceleration * delta_time) * (1.0 - DRAG * delta_time).max(0.0);
+    (position + velocity * delta_time, velocity)
+}
@@ -0,0 +1,66 @@
+use nalgebra_glm::{Vec2, Vec3};
+use crate::color::Color;
+use crate::texture::Texture;
+
+// Cube map around the viewer, one square texture per face: +X, -X, +Y, -Y, +Z, -Z.
+// Each face spans the other two axes from -1 to 1, in x, y, z order.
+pub struct Skybox {
+    faces: Vec<Texture>,
+}
+
+impl Skybox {
+    // Bakes every texel from the direction through its center
+    pub fn from_fn(resolution: usize, color: impl Fn(Vec3) -> Color) -> Self {
+        let faces = (0..6).map(|face| {
+            let mut pixels = Vec::with_capacity(resolution * resolution);
+            for y in 0..resolution {
+                for x in 0..resolution {
+                    let u = (x as f32 + 0.5) / resolution as f32 * 2.0 - 1.0;
+                    let v = (y as f32 + 0.5) / resolution as f32 * 2.0 - 1.0;
+                    pixels.push(color(face_direction(face, u, v).normalize()));
+                }
+            }
+            Texture::new(resolution, resolution, pixels)
+        }).collect();
+
+        Skybox { faces }
+    }
+
+    pub fn faces(&self) -> &[Texture] {
+        &self.faces
+    }
+
+    // `direction` needn't be normalized
+    pub fn sample(&self, direction: &Vec3) -> Color {
+        let Some((face, u, v)) = face_coordinates(direction) else {
+            return Color::black();
+        };
+        self.faces[face].sample_bilinear(Vec2::new((u + 1.0) / 2.0, (v + 1.0) / 2.0))
+    }
+}
+
+fn face_direction(face: usize, u: f32, v: f32) -> Vec3 {
+    let sign = [1.0, -1.0][face % 2];
+    match face / 2 {
+        0 => Vec3::new(sign, u, v),
+        1 => Vec3::new(u, sign, v),
+        _ => Vec3::new(u, v, sign),
+    }
+}
+
+fn face_coordinates(direction: &Vec3) -> Option<(usize, f32, f32)> {
+    let a = direction.abs();
+    let (axis, major, u, v) = if a.x >= a.y && a.x >= a.z {
+        (0, direction.x, direction.y, direction.z)
+    } else if a.y >= a.z {
+        (1, direction.y, direction.x, direction.z)
+    } else {
+        (2, direction.z, direction.x, direction.y)
+    };
+    if major == 0.0 {
+        return None;
+    }
+
+    let face = axis * 2 + if major > 0.0 { 0 } else { 1 };
+    Some((face, u / major.abs(), v / major.abs()))
+}
@@ -0,0 +1,201 @@
+use std::f32::consts::TAU;
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
+use nalgebra_glm::{Vec3, Vec4, Mat4};
+use crate::color::Color;
+use crate::framebuffer::Framebuffer;
+use crate::scene::StarFieldConfig;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+// Blackbody tints from red dwarfs to blue giants, by temperature in kelvin
+const TEMPERATURE_COLORS: [(f32, u32); 6] = [
+    (3000.0, 0xFFB46B),
+    (4500.0, 0xFFD2A1),
+    (6000.0, 0xFFF4EA),
+    (7500.0, 0xF8F7FF),
+    (10000.0, 0xCAD7FF),
+    (12000.0, 0xB5C6FF),
+];
+
+// Half-thickness of the Milky Way as a fraction of a right angle, for its stars and its glow
+const BAND_SPREAD: f32 = 0.08;
+const GLOW_SPREAD: f32 = 0.14;
+// Dark dust lane down the middle of the band
+const RIFT_WIDTH: f32 = 0.025;
+
+struct Star {
+    direction: Vec3,
+    color: Color,
+    // Pixels; zero for sharp points
+    radius: f32,
+    twinkle_speed: f32,
+    twinkle_phase: f32,
+}
+
+// Stars fixed on the sky, generated once and projected every frame with the camera's
+// rotation only, so they never move as the camera travels
+pub struct StarField {
+    stars: Vec<Star>,
+    // Soft puffs that blend into the Milky Way's diffuse glow
+    glow: Vec<Star>,
+    twinkle: f32,
+    // Hash of the config, so the kept glow can tell one star field from another
+    fingerprint: u64,
+}
+
+impl StarField {
+    pub fn new(config: &StarFieldConfig) -> Self {
+        let mut rng = StdRng::seed_from_u64(config.seed);
+
+        let tilt = config.milky_way_tilt.to_radians();
+        let band_normal = Vec3::new(0.0, -tilt.sin(), tilt.cos());
+        let band_u = Vec3::x();
+        let band_v = band_normal.cross(&band_u);
+        let in_band = |rng: &mut StdRng, spread: f32| {
+            let angle = rng.gen_range(0.0..TAU);
+            // Sum of uniforms, roughly normal, so the band thins out towards its edges
+            let offset = (rng.gen::<f32>() + rng.gen::<f32>() - rng.gen::<f32>() - rng.gen::<f32>()) * spread;
+            let direction = (band_u * angle.cos() + band_v * angle.sin() + band_normal * offset).normalize();
+            (direction, angle, offset)
+        };
+
+        let band_share = (config.milky_way * 0.4).clamp(0.0, 0.6);
+        let stars = (0..config.count).map(|_| {
+            let direction = if rng.gen::<f32>() < band_share {
+                in_band(&mut rng, BAND_SPREAD).0
+            } else {
+                random_direction(&mut rng)
+            };
+
+            // Faint stars vastly outnumber bright ones
+            let brightness = 0.35 + 2.0 * rng.gen::<f32>().powi(6);
+            let temperature = rng.gen_range(3000.0..12000.0);
+            Star {
+                direction,
+                color: temperature_color(temperature) * (brightness * config.brightness),
+                radius: 0.0,
+                twinkle_speed: rng.gen_range(1.0..4.0),
+                twinkle_phase: rng.gen_range(0.0..TAU),
+            }
+        }).collect();
+
+        let glow_count = (config.count as f32 * config.milky_way * 2.0) as usize;
+        let glow = (0..glow_count).map(|_| {
+            let (direction, angle, offset) = in_band(&mut rng, GLOW_SPREAD);
+            // Brighter and dimmer stretches along the band, and the rift down its middle
+            let clumps = 0.6 + 0.4 * (angle * 3.0 + 1.7).sin() * (angle * 7.0).cos();
+            let rift = if offset.abs() < RIFT_WIDTH { 0.3 } else { 1.0 };
+            let brightness = rng.gen_range(0.3..1.0) * clumps * rift * 0.15 * config.milky_way * config.brightness;
+            Star {
+                direction,
+                color: Color::new(255, 235, 210) * brightness,
+                radius: rng.gen_range(5.0..12.0),
+                twinkle_speed: 0.0,
+                twinkle_phase: 0.0,
+            }
+        }).collect();
+
+        StarField { stars, glow, twinkle: config.twinkle, fingerprint: fingerprint(config) }
+    }
+
+    pub fn fingerprint(&self) -> u64 {
+        self.fingerprint
+    }
+
+    // Depth tested against whatever has been drawn, so bodies hide the stars behind them
+    pub fn draw(&self, framebuffer: &mut Framebuffer, view_projection: &Mat4, viewport_matrix: &Mat4, time: f32) {
+        // The Milky Way's glow never changes, so it is kept with the nebula for clean tiles;
+        // the stars twinkle and go on top every frame
+        for puff in &self.glow {
+            if let Some((x, y)) = project(puff, view_projection, viewport_matrix) {
+                splat(framebuffer, x, y, puff.radius, puff.color, Framebuffer::add_sky);
+            }
+        }
+
+        for star in &self.stars {
+            let Some((x, y)) = project(star, view_projection, viewport_matrix) else {
+                continue;
+            };
+            let wave = 0.5 + 0.5 * (time * star.twinkle_speed + star.twinkle_phase).sin();
+            let color = star.color * (1.0 - self.twinkle * wave);
+            splat(framebuffer, x, y, star.radius, color, Framebuffer::add_background);
+        }
+    }
+}
+
+fn fingerprint(config: &StarFieldConfig) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    (config.seed, config.count).hash(&mut hasher);
+    for value in [config.brightness, config.twinkle, config.milky_way, config.milky_way_tilt] {
+        value.to_bits().hash(&mut hasher);
+    }
+    hasher.finish()
+}
+
+fn random_direction(rng: &mut StdRng) -> Vec3 {
+    let z: f32 = rng.gen_range(-1.0..=1.0);
+    let angle = rng.gen_range(0.0..TAU);
+    let ring = (1.0 - z * z).sqrt();
+    Vec3::new(ring * angle.cos(), ring * angle.sin(), z)
+}
+
+fn temperature_color(kelvin: f32) -> Color {
+    let (mut low, mut high) = (TEMPERATURE_COLORS[0], TEMPERATURE_COLORS[0]);
+    for &entry in &TEMPERATURE_COLORS {
+        high = entry;
+        if entry.0 >= kelvin {
+            break;
+        }
+        low = entry;
+    }
+
+    let t = if high.0 > low.0 { ((kelvin - low.0) / (high.0 - low.0)).clamp(0.0, 1.0) } else { 0.0 };
+    Color::from_hex(low.1).lerp(&Color::from_hex(high.1), t)
+}
+
+// Directions have no position, so w = 0 drops the camera's translation
+fn project(star: &Star, view_projection: &Mat4, viewport_matrix: &Mat4) -> Option<(f32, f32)> {
+    let direction = star.direction;
+    let clip = view_projection * Vec4::new(direction.x, direction.y, direction.z, 0.0);
+    if clip.w <= 0.0 {
+        return None;
+    }
+
+    let screen = viewport_matrix * Vec4::new(clip.x / clip.w, clip.y / clip.w, 0.0, 1.0);
+    Some((screen.x, screen.y))
+}
+
+// Points spread over the four nearest pixels so they glide smoothly as the camera turns;
+// puffs fall off towards their radius
+fn splat(framebuffer: &mut Framebuffer, x: f32, y: f32, radius: f32, color: Color, add: fn(&mut Framebuffer, usize, usize, Color)) {
+    let reach = radius.max(1.0);
+    if x < -reach || y < -reach || x >= framebuffer.width as f32 + reach || y >= framebuffer.height as f32 + reach {
+        return;
+    }
+
+    if radius <= 0.0 {
+        let (x, y) = (x - 0.5, y - 0.5);
+        let (left, top) = (x.floor(), y.floor());
+        let (fx, fy) = (x - left, y - top);
+        for (dx, dy, weight) in [(0, 0, (1.0 - fx) * (1.0 - fy)), (1, 0, fx * (1.0 - fy)), (0, 1, (1.0 - fx) * fy), (1, 1, fx * fy)] {
+            let (px, py) = (left as i64 + dx, top as i64 + dy);
+            if px >= 0 && py >= 0 {
+                add(framebuffer, px as usize, py as usize, color * weight);
+            }
+        }
+        return;
+    }
+
+    let (min_x, max_x) = ((x - radius).floor().max(0.0) as usize, (x + radius).ceil() as usize);
+    let (min_y, max_y) = ((y - radius).floor().max(0.0) as usize, (y + radius).ceil() as usize);
+    for py in min_y..=max_y {
+        for px in min_x..=max_x {
+            let distance = (px as f32 + 0.5 - x).hypot(py as f32 + 0.5 - y);
+            if distance < radius {
+                let falloff = 1.0 - distance / radius;
+                add(framebuffer, px, py, color * (falloff * falloff));
+            }
+        }
+    }
+}
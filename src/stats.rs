@@ -0,0 +1,117 @@
+use crate::framebuffer::Framebuffer;
+use crate::profiler::ObjectProfile;
+use crate::renderer::RenderStats;
+use crate::text::{draw_text, GLYPH_SIZE};
+
+// The profiler table sits right of the counters, clear of the debug panel below them
+const PROFILE_X: usize = 240;
+const PROFILE_OBJECTS: usize = 8;
+const NAME_WIDTH: usize = 14;
+// Column headings for profiler::STAGES
+const STAGE_HEADINGS: [&str; 5] = ["vert", "asm", "rast", "frag", "write"];
+// Objects whose smoothed cost falls below this (culled for a while) leave the table
+const MIN_OBJECT_TIME: f32 = 0.001;
+
+pub struct FrameStats {
+    pub visible: bool,
+    frame_time: f32,
+    // Milliseconds per stage for the whole frame and for each object by name, smoothed
+    // like the frame time
+    stages: [f32; 5],
+    objects: Vec<(String, [f32; 5])>,
+}
+
+impl FrameStats {
+    pub fn new() -> Self {
+        FrameStats {
+            visible: false,
+            frame_time: 0.0,
+            stages: [0.0; 5],
+            objects: Vec::new(),
+        }
+    }
+
+    pub fn update(&mut self, delta_time: f32, render_stats: &RenderStats, profile: &[ObjectProfile]) {
+        // Exponential moving average so the numbers are readable
+        if self.frame_time == 0.0 {
+            self.frame_time = delta_time;
+        } else {
+            self.frame_time += (delta_time - self.frame_time) * 0.1;
+        }
+
+        smooth(&mut self.stages, render_stats);
+        // An object drawn by several views this frame shows their sum
+        let mut drawn: Vec<(&str, RenderStats)> = Vec::new();
+        for object in profile {
+            match drawn.iter_mut().find(|(name, _)| *name == object.name) {
+                Some((_, stats)) => *stats += object.stats,
+                None => drawn.push((&object.name, object.stats)),
+            }
+        }
+        for (name, stages) in &mut self.objects {
+            let stats = drawn.iter().find(|(drawn_name, _)| drawn_name == name).map(|(_, stats)| *stats);
+            smooth(stages, &stats.unwrap_or_default());
+        }
+        for (name, stats) in drawn {
+            if !self.objects.iter().any(|(existing, _)| existing == name) {
+                self.objects.push((name.to_string(), milliseconds(&stats)));
+            }
+        }
+        self.objects.retain(|(_, stages)| stages.iter().sum::<f32>() >= MIN_OBJECT_TIME);
+    }
+
+    pub fn draw(&self, framebuffer: &mut Framebuffer, render_stats: &RenderStats) {
+        let fps = if self.frame_time > 0.0 { 1.0 / self.frame_time } else { 0.0 };
+        let lines = [
+            format!("FPS: {:.1}", fps),
+            format!("Frame: {:.2} ms", self.frame_time * 1000.0),
+            format!("Triangles: {}", render_stats.triangles),
+            format!("Fragments: {}", render_stats.fragments),
+            format!("Shaded: {}", render_stats.shaded_fragments),
+            format!("Vertex cache hits: {}", render_stats.vertex_cache_hits),
+            format!("Culled: {}", render_stats.culled_objects),
+            format!("Occluded: {}", render_stats.occluded_objects),
+            format!("Clean tiles: {}", render_stats.clean_tiles),
+        ];
+
+        for (i, line) in lines.iter().enumerate() {
+            draw_text(framebuffer, line, 8, 8 + i * (GLYPH_SIZE + 2), 1, 0x00FF00);
+        }
+
+        // Per-stage milliseconds: the frame, then the most expensive objects
+        let mut header = format!("{:<width$}", "ms", width = NAME_WIDTH);
+        for heading in STAGE_HEADINGS {
+            header.push_str(&format!("{:>6}", heading));
+        }
+        let mut objects: Vec<&(String, [f32; 5])> = self.objects.iter().collect();
+        objects.sort_by(|a, b| b.1.iter().sum::<f32>().total_cmp(&a.1.iter().sum::<f32>()));
+        let rows = std::iter::once(("frame", &self.stages))
+            .chain(objects.into_iter().take(PROFILE_OBJECTS).map(|(name, stages)| (name.as_str(), stages)));
+
+        draw_text(framebuffer, &header, PROFILE_X, 8, 1, 0x00FF00);
+        for (i, (name, stages)) in rows.enumerate() {
+            let mut line = format!("{:<width$}", name.chars().take(NAME_WIDTH - 1).collect::<String>(), width = NAME_WIDTH);
+            for time in stages {
+                line.push_str(&format!("{:>6.2}", time));
+            }
+            let color = if i == 0 { 0xFFFF00 } else { 0x00FF00 };
+            draw_text(framebuffer, &line, PROFILE_X, 8 + (i + 1) * (GLYPH_SIZE + 2), 1, color);
+        }
+    }
+}
+
+impl Default for FrameStats {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn milliseconds(stats: &RenderStats) -> [f32; 5] {
+    stats.stage_times().map(|time| time.as_secs_f32() * 1000.0)
+}
+
+fn smooth(smoothed: &mut [f32; 5], stats: &RenderStats) {
+    for (value, time) in smoothed.iter_mut().zip(milliseconds(stats)) {
+        *value += (time - *value) * 0.1;
+    }
+}
@@ -0,0 +1,140 @@
+use crate::framebuffer::Framebuffer;
+
+const FIRST_CHAR: u8 = b' ';
+const LAST_CHAR: u8 = b'~';
+pub const GLYPH_SIZE: usize = 8;
+pub const GLYPH_ADVANCE: usize = 6;
+
+// Printable ASCII glyphs from the public domain X11 misc-fixed 5x8 font,
+// padded to 8x8 cells. Each byte is a row, most significant bit on the left.
+const FONT: [[u8; GLYPH_SIZE]; (LAST_CHAR - FIRST_CHAR + 1) as usize] = [
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00], // ' '
+    [0x00, 0x20, 0x20, 0x20, 0x20, 0x00, 0x20, 0x00], // '!'
+    [0x00, 0x50, 0x50, 0x50, 0x00, 0x00, 0x00, 0x00], // '"'
+    [0x50, 0x50, 0xF8, 0x50, 0xF8, 0x50, 0x50, 0x00], // '#'
+    [0x20, 0x70, 0xA0, 0x70, 0x28, 0x70, 0x20, 0x00], // '$'
+    [0x00, 0x40, 0x50, 0x20, 0x50, 0x10, 0x00, 0x00], // '%'
+    [0x40, 0xA0, 0xA0, 0x40, 0xA0, 0xA0, 0x50, 0x00], // '&'
+    [0x00, 0x20, 0x20, 0x20, 0x00, 0x00, 0x00, 0x00], // '\''
+    [0x00, 0x20, 0x40, 0x40, 0x40, 0x40, 0x20, 0x00], // '('
+    [0x00, 0x40, 0x20, 0x20, 0x20, 0x20, 0x40, 0x00], // ')'
+    [0x00, 0x00, 0x90, 0x60, 0xF0, 0x60, 0x90, 0x00], // '*'
+    [0x00, 0x00, 0x20, 0x20, 0xF8, 0x20, 0x20, 0x00], // '+'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x30, 0x20, 0x40], // ','
+    [0x00, 0x00, 0x00, 0x00, 0xF0, 0x00, 0x00, 0x00], // '-'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x20, 0x70, 0x20], // '.'
+    [0x00, 0x10, 0x10, 0x20, 0x40, 0x80, 0x80, 0x00], // '/'
+    [0x00, 0x20, 0x50, 0x50, 0x50, 0x50, 0x20, 0x00], // '0'
+    [0x00, 0x20, 0x60, 0x20, 0x20, 0x20, 0x70, 0x00], // '1'
+    [0x00, 0x60, 0x90, 0x10, 0x60, 0x80, 0xF0, 0x00], // '2'
+    [0x00, 0xF0, 0x20, 0x60, 0x10, 0x90, 0x60, 0x00], // '3'
+    [0x00, 0x20, 0x60, 0xA0, 0xF0, 0x20, 0x20, 0x00], // '4'
+    [0x00, 0xF0, 0x80, 0xE0, 0x10, 0x90, 0x60, 0x00], // '5'
+    [0x00, 0x60, 0x80, 0xE0, 0x90, 0x90, 0x60, 0x00], // '6'
+    [0x00, 0xF0, 0x10, 0x20, 0x20, 0x40, 0x40, 0x00], // '7'
+    [0x00, 0x60, 0x90, 0x60, 0x90, 0x90, 0x60, 0x00], // '8'
+    [0x00, 0x60, 0x90, 0x90, 0x70, 0x10, 0x60, 0x00], // '9'
+    [0x00, 0x00, 0x60, 0x60, 0x00, 0x60, 0x60, 0x00], // ':'
+    [0x00, 0x00, 0x30, 0x30, 0x00, 0x30, 0x20, 0x40], // ';'
+    [0x00, 0x10, 0x20, 0x40, 0x40, 0x20, 0x10, 0x00], // '<'
+    [0x00, 0x00, 0x00, 0xF0, 0x00, 0xF0, 0x00, 0x00], // '='
+    [0x00, 0x40, 0x20, 0x10, 0x10, 0x20, 0x40, 0x00], // '>'
+    [0x00, 0x20, 0x50, 0x10, 0x20, 0x00, 0x20, 0x00], // '?'
+    [0x30, 0x48, 0x98, 0xA8, 0xA8, 0x90, 0x40, 0x30], // '@'
+    [0x00, 0x60, 0x90, 0x90, 0xF0, 0x90, 0x90, 0x00], // 'A'
+    [0x00, 0xE0, 0x90, 0xE0, 0x90, 0x90, 0xE0, 0x00], // 'B'
+    [0x00, 0x60, 0x90, 0x80, 0x80, 0x90, 0x60, 0x00], // 'C'
+    [0x00, 0xE0, 0x90, 0x90, 0x90, 0x90, 0xE0, 0x00], // 'D'
+    [0x00, 0xF0, 0x80, 0xE0, 0x80, 0x80, 0xF0, 0x00], // 'E'
+    [0x00, 0xF0, 0x80, 0xE0, 0x80, 0x80, 0x80, 0x00], // 'F'
+    [0x00, 0x60, 0x90, 0x80, 0xB0, 0x90, 0x60, 0x00], // 'G'
+    [0x00, 0x90, 0x90, 0xF0, 0x90, 0x90, 0x90, 0x00], // 'H'
+    [0x00, 0x70, 0x20, 0x20, 0x20, 0x20, 0x70, 0x00], // 'I'
+    [0x00, 0x70, 0x20, 0x20, 0x20, 0xA0, 0x40, 0x00], // 'J'
+    [0x00, 0x90, 0xA0, 0xC0, 0xA0, 0xA0, 0x90, 0x00], // 'K'
+    [0x00, 0x80, 0x80, 0x80, 0x80, 0x80, 0xF0, 0x00], // 'L'
+    [0x00, 0x90, 0xF0, 0xF0, 0x90, 0x90, 0x90, 0x00], // 'M'
+    [0x00, 0x90, 0xD0, 0xF0, 0xB0, 0xB0, 0x90, 0x00], // 'N'
+    [0x00, 0x60, 0x90, 0x90, 0x90, 0x90, 0x60, 0x00], // 'O'
+    [0x00, 0xE0, 0x90, 0x90, 0xE0, 0x80, 0x80, 0x00], // 'P'
+    [0x00, 0x60, 0x90, 0x90, 0xD0, 0xB0, 0x60, 0x10], // 'Q'
+    [0x00, 0xE0, 0x90, 0x90, 0xE0, 0x90, 0x90, 0x00], // 'R'
+    [0x00, 0x60, 0x90, 0x40, 0x20, 0x90, 0x60, 0x00], // 'S'
+    [0x00, 0x70, 0x20, 0x20, 0x20, 0x20, 0x20, 0x00], // 'T'
+    [0x00, 0x90, 0x90, 0x90, 0x90, 0x90, 0x60, 0x00], // 'U'
+    [0x00, 0x90, 0x90, 0x90, 0x90, 0x60, 0x60, 0x00], // 'V'
+    [0x00, 0x90, 0x90, 0x90, 0xF0, 0xF0, 0x90, 0x00], // 'W'
+    [0x00, 0x90, 0x90, 0x60, 0x60, 0x90, 0x90, 0x00], // 'X'
+    [0x00, 0x88, 0x88, 0x50, 0x20, 0x20, 0x20, 0x00], // 'Y'
+    [0x00, 0xF0, 0x10, 0x20, 0x40, 0x80, 0xF0, 0x00], // 'Z'
+    [0x00, 0x70, 0x40, 0x40, 0x40, 0x40, 0x70, 0x00], // '['
+    [0x00, 0x80, 0x80, 0x40, 0x20, 0x10, 0x10, 0x00], // '\\'
+    [0x00, 0x70, 0x10, 0x10, 0x10, 0x10, 0x70, 0x00], // ']'
+    [0x00, 0x20, 0x50, 0x00, 0x00, 0x00, 0x00, 0x00], // '^'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xF0], // '_'
+    [0x00, 0x40, 0x20, 0x00, 0x00, 0x00, 0x00, 0x00], // '`'
+    [0x00, 0x00, 0x00, 0x70, 0x90, 0x90, 0x70, 0x00], // 'a'
+    [0x00, 0x80, 0x80, 0xE0, 0x90, 0x90, 0xE0, 0x00], // 'b'
+    [0x00, 0x00, 0x00, 0x30, 0x40, 0x40, 0x30, 0x00], // 'c'
+    [0x00, 0x10, 0x10, 0x70, 0x90, 0x90, 0x70, 0x00], // 'd'
+    [0x00, 0x00, 0x00, 0x60, 0xB0, 0xC0, 0x60, 0x00], // 'e'
+    [0x00, 0x20, 0x50, 0x40, 0xE0, 0x40, 0x40, 0x00], // 'f'
+    [0x00, 0x00, 0x00, 0x60, 0x90, 0x70, 0x10, 0x60], // 'g'
+    [0x00, 0x80, 0x80, 0xE0, 0x90, 0x90, 0x90, 0x00], // 'h'
+    [0x00, 0x20, 0x00, 0x60, 0x20, 0x20, 0x70, 0x00], // 'i'
+    [0x00, 0x10, 0x00, 0x10, 0x10, 0x10, 0x50, 0x20], // 'j'
+    [0x00, 0x80, 0x80, 0x90, 0xE0, 0x90, 0x90, 0x00], // 'k'
+    [0x00, 0x60, 0x20, 0x20, 0x20, 0x20, 0x70, 0x00], // 'l'
+    [0x00, 0x00, 0x00, 0xD0, 0xA8, 0xA8, 0xA8, 0x00], // 'm'
+    [0x00, 0x00, 0x00, 0xE0, 0x90, 0x90, 0x90, 0x00], // 'n'
+    [0x00, 0x00, 0x00, 0x60, 0x90, 0x90, 0x60, 0x00], // 'o'
+    [0x00, 0x00, 0x00, 0xE0, 0x90, 0xE0, 0x80, 0x80], // 'p'
+    [0x00, 0x00, 0x00, 0x70, 0x90, 0x70, 0x10, 0x10], // 'q'
+    [0x00, 0x00, 0x00, 0xA0, 0xD0, 0x80, 0x80, 0x00], // 'r'
+    [0x00, 0x00, 0x00, 0x30, 0x60, 0x10, 0x60, 0x00], // 's'
+    [0x00, 0x40, 0x40, 0xE0, 0x40, 0x50, 0x20, 0x00], // 't'
+    [0x00, 0x00, 0x00, 0x90, 0x90, 0x90, 0x70, 0x00], // 'u'
+    [0x00, 0x00, 0x00, 0x50, 0x50, 0x50, 0x20, 0x00], // 'v'
+    [0x00, 0x00, 0x00, 0x88, 0xA8, 0xA8, 0x50, 0x00], // 'w'
+    [0x00, 0x00, 0x00, 0x90, 0x60, 0x60, 0x90, 0x00], // 'x'
+    [0x00, 0x00, 0x00, 0x90, 0x90, 0x70, 0x90, 0x60], // 'y'
+    [0x00, 0x00, 0x00, 0xF0, 0x20, 0x40, 0xF0, 0x00], // 'z'
+    [0x30, 0x40, 0x20, 0xC0, 0x20, 0x40, 0x30, 0x00], // '{'
+    [0x00, 0x20, 0x20, 0x20, 0x20, 0x20, 0x20, 0x00], // '|'
+    [0xC0, 0x20, 0x40, 0x30, 0x40, 0x20, 0xC0, 0x00], // '}'
+    [0x00, 0x50, 0xA0, 0x00, 0x00, 0x00, 0x00, 0x00], // '~'
+];
+
+fn glyph(c: char) -> &'static [u8; GLYPH_SIZE] {
+    let code = c as u32;
+    if code >= FIRST_CHAR as u32 && code <= LAST_CHAR as u32 {
+        &FONT[(code - FIRST_CHAR as u32) as usize]
+    } else {
+        &FONT[(b'?' - FIRST_CHAR) as usize]
+    }
+}
+
+pub fn text_width(text: &str, scale: usize) -> usize {
+    text.chars().count() * GLYPH_ADVANCE * scale
+}
+
+pub fn draw_text(framebuffer: &mut Framebuffer, text: &str, x: usize, y: usize, scale: usize, color: u32) {
+    for (i, c) in text.chars().enumerate() {
+        let rows = glyph(c);
+        let origin_x = x + i * GLYPH_ADVANCE * scale;
+
+        for (row, bits) in rows.iter().enumerate() {
+            for column in 0..GLYPH_SIZE {
+                if bits & (0x80 >> column) == 0 {
+                    continue;
+                }
+
+                for dy in 0..scale {
+                    for dx in 0..scale {
+                        framebuffer.draw_pixel(origin_x + column * scale + dx, y + row * scale + dy, color);
+                    }
+                }
+            }
+        }
+    }
+}
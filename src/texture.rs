@@ -0,0 +1,100 @@
+use std::fs::File;
+use nalgebra_glm::Vec2;
+use crate::color::Color;
+
+#[derive(Clone)]
+pub struct Texture {
+    pub width: usize,
+    pub height: usize,
+    pixels: Vec<Color>,
+    alpha: Vec<u8>,
+}
+
+impl Texture {
+    pub fn new(width: usize, height: usize, pixels: Vec<Color>) -> Self {
+        let alpha = vec![255; pixels.len()];
+        Texture { width, height, pixels, alpha }
+    }
+
+    pub fn with_alpha(width: usize, height: usize, pixels: Vec<Color>, alpha: Vec<u8>) -> Self {
+        Texture { width, height, pixels, alpha }
+    }
+
+    // White disc whose alpha falls off quadratically towards the edge
+    pub fn soft_circle(size: usize) -> Self {
+        let mut alpha = Vec::with_capacity(size * size);
+        for y in 0..size {
+            for x in 0..size {
+                let dx = (x as f32 + 0.5) / size as f32 * 2.0 - 1.0;
+                let dy = (y as f32 + 0.5) / size as f32 * 2.0 - 1.0;
+                let falloff = (1.0 - (dx * dx + dy * dy).sqrt()).max(0.0);
+                alpha.push((falloff * falloff * 255.0) as u8);
+            }
+        }
+
+        Self::with_alpha(size, size, vec![Color::new(255, 255, 255); size * size], alpha)
+    }
+
+    pub fn load(path: &str) -> Result<Self, String> {
+        let file = File::open(path).map_err(|e| format!("Failed to open {}: {}", path, e))?;
+        let mut decoder = png::Decoder::new(file);
+        decoder.set_transformations(png::Transformations::normalize_to_color8());
+        let mut reader = decoder.read_info().map_err(|e| format!("Failed to read {}: {}", path, e))?;
+
+        let mut data = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut data).map_err(|e| format!("Failed to decode {}: {}", path, e))?;
+
+        let channels = info.color_type.samples();
+        let texels = data[..info.buffer_size()].chunks_exact(channels);
+        let pixels = texels.clone()
+            .map(|p| match channels {
+                1 | 2 => Color::new(p[0], p[0], p[0]),
+                _ => Color::new(p[0], p[1], p[2]),
+            })
+            .collect();
+        let alpha = texels
+            .map(|p| match channels {
+                2 => p[1],
+                4 => p[3],
+                _ => 255,
+            })
+            .collect();
+
+        Ok(Self::with_alpha(info.width as usize, info.height as usize, pixels, alpha))
+    }
+
+    fn texel_index(&self, uv: Vec2) -> usize {
+        // Nearest neighbour with wrap-around addressing
+        let u = uv.x - uv.x.floor();
+        let v = uv.y - uv.y.floor();
+        let x = ((u * self.width as f32) as usize).min(self.width - 1);
+        let y = ((v * self.height as f32) as usize).min(self.height - 1);
+        y * self.width + x
+    }
+
+    pub fn sample(&self, uv: Vec2) -> Color {
+        self.pixels[self.texel_index(uv)]
+    }
+
+    // Bilinear between the four nearest texels, clamped at the borders so faces of a cube
+    // map don't bleed into each other
+    // Row by row from the top left
+    pub fn pixels(&self) -> &[Color] {
+        &self.pixels
+    }
+
+    pub fn sample_bilinear(&self, uv: Vec2) -> Color {
+        let x = (uv.x * self.width as f32 - 0.5).clamp(0.0, (self.width - 1) as f32);
+        let y = (uv.y * self.height as f32 - 0.5).clamp(0.0, (self.height - 1) as f32);
+        let (left, top) = (x as usize, y as usize);
+        let (right, bottom) = ((left + 1).min(self.width - 1), (top + 1).min(self.height - 1));
+        let (fx, fy) = (x - left as f32, y - top as f32);
+
+        let row = |y: usize| self.pixels[y * self.width + left].lerp(&self.pixels[y * self.width + right], fx);
+        row(top).lerp(&row(bottom), fy)
+    }
+
+    pub fn sample_alpha(&self, uv: Vec2) -> f32 {
+        self.alpha[self.texel_index(uv)] as f32 / 255.0
+    }
+}
@@ -1,41 +1,90 @@
-use nalgebra_glm::{Vec3, dot};
-use crate::fragment::Fragment;
+use nalgebra_glm::Vec3;
+use crate::fragment::{lighting, Fragment};
 use crate::vertex::Vertex;
 use crate::color::Color;
+use crate::framebuffer::Rect;
+use crate::Light;
 
-pub fn triangle(v1: &Vertex, v2: &Vertex, v3: &Vertex) -> Vec<Fragment> {
-  let mut fragments = Vec::new();
+// Pixels are walked in horizontal quads of four so the edge tests run as 4-wide lanes
+const QUAD: usize = 4;
+
+// Vertices snap to 1/256 of a pixel and the edge tests run on integers, so the two
+// triangles sharing an edge see exactly the same edge and agree on every pixel along it
+const SUBPIXEL_BITS: u32 = 8;
+const SUBPIXEL: i64 = 1 << SUBPIXEL_BITS;
+// Keeps the edge products inside i64 for vertices far off screen
+const MAX_COORDINATE: i64 = 1 << 29;
+
+// Hands each covered pixel inside `clip` to `emit` as soon as it is interpolated instead of collecting them
+pub fn triangle(v1: &Vertex, v2: &Vertex, v3: &Vertex, lights: &[Light], clip: Rect, mut emit: impl FnMut(Fragment)) {
   let (a, b, c) = (v1.transformed_position, v2.transformed_position, v3.transformed_position);
+  let (fixed_a, fixed_b, fixed_c) = (to_fixed(&a), to_fixed(&b), to_fixed(&c));
 
-  let (min_x, min_y, max_x, max_y) = calculate_bounding_box(&a, &b, &c);
+  // Nothing clips x and y before this, so a vertex near the near plane can project far
+  // off screen; only the pixels that can be written are walked
+  let Some((min_x, min_y, max_x, max_y)) = calculate_bounding_box(&a, &b, &c, clip) else {
+    return;
+  };
 
-  let light_dir = Vec3::new(0.0, 0.0, 1.0);
+  let triangle_area = edge_function(fixed_a, fixed_b, fixed_c);
+  if triangle_area == 0 {
+    return;
+  }
+  // Both windings are drawn; flipping the sign keeps the inside positive either way
+  let sign = triangle_area.signum();
+  let inverse_area = 1.0 / (triangle_area * sign) as f32;
 
-  let triangle_area = edge_function(&a, &b, &c);
+  // Edge values are linear in screen space, so they are stepped from the first pixel center
+  let origin = (min_x as i64 * SUBPIXEL + SUBPIXEL / 2, min_y as i64 * SUBPIXEL + SUBPIXEL / 2);
+  let e1 = Edge::new(fixed_b, fixed_c, sign, origin);
+  let e2 = Edge::new(fixed_c, fixed_a, sign, origin);
+  let e3 = Edge::new(fixed_a, fixed_b, sign, origin);
 
   for y in min_y..=max_y {
-    for x in min_x..=max_x {
-      let point = Vec3::new(x as f32 + 0.5, y as f32 + 0.5, 0.0);
-
-      let (w1, w2, w3) = barycentric_coordinates(&point, &a, &b, &c, triangle_area);
+    let row = (y - min_y) as i64;
+    let (row1, row2, row3) = (e1.row(row), e2.row(row), e3.row(row));
+
+    for quad_x in (min_x..=max_x).step_by(QUAD) {
+      let offset = (quad_x - min_x) as i64;
+      let w1 = e1.quad(row1, offset);
+      let w2 = e2.quad(row2, offset);
+      let w3 = e3.quad(row3, offset);
+
+      let inside: [bool; QUAD] = std::array::from_fn(|lane| {
+        e1.covers(w1[lane]) && e2.covers(w2[lane]) && e3.covers(w3[lane])
+      });
+      if !inside.contains(&true) {
+        continue;
+      }
 
-      if w1 >= 0.0 && w1 <= 1.0 && 
-         w2 >= 0.0 && w2 <= 1.0 &&
-         w3 >= 0.0 && w3 <= 1.0 {
+      for lane in 0..QUAD {
+        let x = quad_x + lane as i32;
+        if !inside[lane] || x > max_x {
+          continue;
+        }
+        let (w1, w2, w3) = (
+          w1[lane] as f32 * inverse_area,
+          w2[lane] as f32 * inverse_area,
+          w3[lane] as f32 * inverse_area,
+        );
 
         let normal = v1.transformed_normal * w1 + v2.transformed_normal * w2 + v3.transformed_normal * w3;
         let normal = normal.normalize();
 
-        let intensity = dot(&normal, &light_dir).max(0.0);
+        let world_position = v1.world_position * w1 + v2.world_position * w2 + v3.world_position * w3;
+
+        let (intensity, light) = lighting(&normal, &world_position, lights);
 
         let base_color = Color::new(100, 100, 100);
-        let lit_color = base_color * intensity;
+        let lit_color = base_color.blend_multiply(&light);
 
         let depth = a.z * w1 + b.z * w2 + c.z * w3;
 
         let vertex_position = v1.position * w1 + v2.position * w2 + v3.position * w3;
 
-        fragments.push(
+        let tex_coords = v1.tex_coords * w1 + v2.tex_coords * w2 + v3.tex_coords * w3;
+
+        emit(
             Fragment::new(
                 x as f32,
                 y as f32,
@@ -43,34 +92,74 @@ pub fn triangle(v1: &Vertex, v2: &Vertex, v3: &Vertex) -> Vec<Fragment> {
                 depth,
                 normal,
                 intensity,
+                light,
                 vertex_position,
+                tex_coords,
+                world_position,
             )
         );
       }
     }
   }
+}
 
-  fragments
+fn to_fixed(position: &Vec3) -> (i64, i64) {
+  let snap = |value: f32| ((value * SUBPIXEL as f32).round() as i64).clamp(-MAX_COORDINATE, MAX_COORDINATE);
+  (snap(position.x), snap(position.y))
 }
 
-fn calculate_bounding_box(v1: &Vec3, v2: &Vec3, v3: &Vec3) -> (i32, i32, i32, i32) {
-    let min_x = v1.x.min(v2.x).min(v3.x).floor() as i32;
-    let min_y = v1.y.min(v2.y).min(v3.y).floor() as i32;
-    let max_x = v1.x.max(v2.x).max(v3.x).ceil() as i32;
-    let max_y = v1.y.max(v2.y).max(v3.y).ceil() as i32;
+// edge_function(from, to, p), oriented positive inside, written as start + step_x * x + step_y * y
+// in whole pixels from the bounding box's first pixel center
+struct Edge {
+  step_x: i64,
+  step_y: i64,
+  start: i64,
+  // Top-left fill rule: a pixel center exactly on the edge belongs to the triangle only
+  // when this is a left edge or a flat top edge (y grows downwards), so of two triangles
+  // sharing an edge exactly one draws it
+  min_value: i64,
+  lanes: [i64; QUAD],
+}
 
-    (min_x, min_y, max_x, max_y)
+impl Edge {
+  fn new(from: (i64, i64), to: (i64, i64), sign: i64, origin: (i64, i64)) -> Self {
+    let step_x = sign * (to.1 - from.1);
+    let step_y = -sign * (to.0 - from.0);
+    let start = sign * edge_function(from, to, origin);
+    let top_left = step_x > 0 || (step_x == 0 && step_y > 0);
+    let mut lanes = [0; QUAD];
+    for (lane, value) in lanes.iter_mut().enumerate() {
+      *value = step_x * SUBPIXEL * lane as i64;
+    }
+    Edge { step_x: step_x * SUBPIXEL, step_y: step_y * SUBPIXEL, start, min_value: if top_left { 0 } else { 1 }, lanes }
+  }
+
+  fn row(&self, row: i64) -> i64 {
+    self.start + self.step_y * row
+  }
+
+  fn quad(&self, row_start: i64, offset: i64) -> [i64; QUAD] {
+    let base = row_start + self.step_x * offset;
+    self.lanes.map(|lane| base + lane)
+  }
+
+  fn covers(&self, value: i64) -> bool {
+    value >= self.min_value
+  }
 }
 
-fn barycentric_coordinates(p: &Vec3, a: &Vec3, b: &Vec3, c: &Vec3, area: f32) -> (f32, f32, f32) {
-    let w1 = edge_function(b, c, p) / area;
-    let w2 = edge_function(c, a, p) / area;
-    let w3 = edge_function(a, b, p) / area;
+// None when the box and the clip rectangle don't overlap
+fn calculate_bounding_box(v1: &Vec3, v2: &Vec3, v3: &Vec3, clip: Rect) -> Option<(i32, i32, i32, i32)> {
+    let min_x = (v1.x.min(v2.x).min(v3.x).floor() as i32).max(clip.x as i32);
+    let min_y = (v1.y.min(v2.y).min(v3.y).floor() as i32).max(clip.y as i32);
+    let max_x = (v1.x.max(v2.x).max(v3.x).ceil() as i32).min((clip.x + clip.width) as i32 - 1);
+    let max_y = (v1.y.max(v2.y).max(v3.y).ceil() as i32).min((clip.y + clip.height) as i32 - 1);
 
-    (w1, w2, w3)
+    (min_x <= max_x && min_y <= max_y).then_some((min_x, min_y, max_x, max_y))
 }
 
-fn edge_function(a: &Vec3, b: &Vec3, c: &Vec3) -> f32 {
-    (c.x - a.x) * (b.y - a.y) - (c.y - a.y) * (b.x - a.x)
+// In fixed-point units squared; exact, so shared edges agree bit for bit
+fn edge_function(a: (i64, i64), b: (i64, i64), c: (i64, i64)) -> i64 {
+    (c.0 - a.0) * (b.1 - a.1) - (c.1 - a.1) * (b.0 - a.0)
 }
 
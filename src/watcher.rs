@@ -0,0 +1,39 @@
+use std::fs;
+use std::time::{Duration, Instant, SystemTime};
+
+// How often the modification time is checked, so watching costs nothing most frames
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+// Notices when a file is saved by polling its modification time
+pub struct FileWatcher {
+    path: String,
+    modified: Option<SystemTime>,
+    last_poll: Instant,
+}
+
+impl FileWatcher {
+    pub fn new(path: &str) -> Self {
+        FileWatcher {
+            path: path.to_string(),
+            modified: modified_time(path),
+            last_poll: Instant::now(),
+        }
+    }
+
+    // True once per save; creating the file counts, deleting it doesn't
+    pub fn changed(&mut self) -> bool {
+        if self.last_poll.elapsed() < POLL_INTERVAL {
+            return false;
+        }
+        self.last_poll = Instant::now();
+
+        let modified = modified_time(&self.path);
+        let changed = modified.is_some() && modified != self.modified;
+        self.modified = modified;
+        changed
+    }
+}
+
+fn modified_time(path: &str) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|metadata| metadata.modified()).ok()
+}
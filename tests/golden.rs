@@ -0,0 +1,183 @@
+// Golden-image regression tests: each case renders a scene headlessly at a fixed time and
+// seed and compares it against tests/golden/<name>.png.
+//
+// After an intended visual change, rewrite the references with
+//     GOLDEN_UPDATE=1 cargo test --test golden
+// and check the new images before committing them.
+
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use nalgebra_glm::Vec3;
+use proyecto3graficas::camera::Camera;
+use proyecto3graficas::framebuffer::Framebuffer;
+use proyecto3graficas::mesh;
+use proyecto3graficas::renderer::Renderer;
+use proyecto3graficas::scene::Scene;
+use proyecto3graficas::shaders::ShaderRegistry;
+
+const SCENE: &str = "scenes/solar_system.toml";
+const REFERENCE_DIR: &str = "tests/golden";
+const SEED: u64 = 1;
+const WIDTH: usize = 200;
+const HEIGHT: usize = 150;
+// CIE76 color difference a pixel may drift by before it counts as changed; about two
+// just-noticeable differences, enough to absorb floating-point noise between platforms
+const MAX_DELTA_E: f32 = 5.0;
+// Share of the image allowed to change before the case fails
+const MAX_CHANGED: f32 = 0.002;
+
+struct Case {
+    name: &'static str,
+    time: f32,
+    eye: Vec3,
+    // Scene object the camera looks at, or the origin
+    target: Option<usize>,
+}
+
+const CASES: [Case; 4] = [
+    Case { name: "system_overview", time: 0.0, eye: Vec3::new(0.0, 4.0, 14.0), target: None },
+    Case { name: "tatooine", time: 12.0, eye: Vec3::new(0.0, 0.3, 1.2), target: Some(1) },
+    Case { name: "hoth", time: 30.0, eye: Vec3::new(0.6, 0.4, 0.9), target: Some(2) },
+    Case { name: "death_star", time: 5.0, eye: Vec3::new(-0.5, 0.2, 0.8), target: Some(4) },
+];
+
+#[test]
+fn golden_images() {
+    let shaders = ShaderRegistry::new();
+    let scene = Scene::load(SCENE, &shaders, Some(SEED)).expect("Failed to load scene");
+    let mut renderer = Renderer::with_lods(WIDTH, HEIGHT, mesh::sphere_lods(0.5));
+    renderer.set_seed(SEED);
+
+    let update = std::env::var_os("GOLDEN_UPDATE").is_some();
+    if update {
+        std::fs::create_dir_all(REFERENCE_DIR).expect("Failed to create the reference directory");
+    }
+    let mut failures = Vec::new();
+    for case in &CASES {
+        let framebuffer = render(&mut renderer, &scene, &shaders, case);
+        let reference = Path::new(REFERENCE_DIR).join(format!("{}.png", case.name));
+        if update {
+            framebuffer.save_png(reference.to_str().unwrap()).unwrap();
+            continue;
+        }
+        if let Err(failure) = compare(case.name, framebuffer, &reference) {
+            failures.push(failure);
+        }
+    }
+    assert!(failures.is_empty(), "Golden images changed:\n{}", failures.join("\n"));
+}
+
+fn render<'a>(renderer: &'a mut Renderer, scene: &Scene, shaders: &ShaderRegistry, case: &Case) -> &'a Framebuffer {
+    // Offsets are relative to the target, so the framing holds wherever its orbit puts it
+    let center = case.target.map_or(Vec3::zeros(), |index| scene.position(index, case.time));
+    let camera = Camera::new(center + case.eye, center, Vec3::new(0.0, 1.0, 0.0));
+    renderer.reset_stats();
+    renderer.render_frame(scene, shaders, &camera, case.time);
+    renderer.framebuffer.resolve();
+    &renderer.framebuffer
+}
+
+// On failure the rendered image and a diff (changed pixels in red over the faded
+// reference) are left next to the test binaries
+fn compare(name: &str, framebuffer: &Framebuffer, reference_path: &Path) -> Result<(), String> {
+    let reference = load_png(reference_path)
+        .map_err(|error| format!("{}: {} (create it with GOLDEN_UPDATE=1)", name, error))?;
+    if (reference.width, reference.height) != (framebuffer.width, framebuffer.height) {
+        return Err(format!(
+            "{}: reference is {}x{}, render is {}x{}",
+            name, reference.width, reference.height, framebuffer.width, framebuffer.height
+        ));
+    }
+
+    let mut diff = Framebuffer::new(framebuffer.width, framebuffer.height);
+    let mut changed = 0;
+    for (index, (&actual, &expected)) in framebuffer.buffer.iter().zip(&reference.pixels).enumerate() {
+        let delta = delta_e(actual, expected);
+        diff.buffer[index] = if delta > MAX_DELTA_E {
+            changed += 1;
+            0xFF0000
+        } else {
+            let gray = (luma(expected) * 0.3 * 255.0) as u32;
+            gray << 16 | gray << 8 | gray
+        };
+    }
+
+    let share = changed as f32 / framebuffer.buffer.len() as f32;
+    if share <= MAX_CHANGED {
+        return Ok(());
+    }
+    let artifacts = artifact_dir();
+    let actual_path = artifacts.join(format!("{}.actual.png", name));
+    let diff_path = artifacts.join(format!("{}.diff.png", name));
+    framebuffer.save_png(actual_path.to_str().unwrap())?;
+    diff.save_png(diff_path.to_str().unwrap())?;
+    Err(format!(
+        "{}: {:.2}% of pixels changed (limit {:.2}%), see {} and {}",
+        name,
+        share * 100.0,
+        MAX_CHANGED * 100.0,
+        actual_path.display(),
+        diff_path.display()
+    ))
+}
+
+fn artifact_dir() -> PathBuf {
+    let directory = Path::new(env!("CARGO_TARGET_TMPDIR")).join("golden");
+    std::fs::create_dir_all(&directory).expect("Failed to create the golden artifact directory");
+    directory
+}
+
+struct Image {
+    width: usize,
+    height: usize,
+    // 0xRRGGBB, like Framebuffer::buffer
+    pixels: Vec<u32>,
+}
+
+fn load_png(path: &Path) -> Result<Image, String> {
+    let file = File::open(path).map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+    let mut decoder = png::Decoder::new(file);
+    decoder.set_transformations(png::Transformations::normalize_to_color8());
+    let mut reader = decoder.read_info().map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    let mut data = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut data).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+
+    let channels = info.color_type.samples();
+    let pixels = data[..info.buffer_size()]
+        .chunks_exact(channels)
+        .map(|pixel| match channels {
+            1 | 2 => (pixel[0] as u32) * 0x010101,
+            _ => (pixel[0] as u32) << 16 | (pixel[1] as u32) << 8 | pixel[2] as u32,
+        })
+        .collect();
+    Ok(Image { width: info.width as usize, height: info.height as usize, pixels })
+}
+
+fn luma(pixel: u32) -> f32 {
+    let [r, g, b] = linear_rgb(pixel);
+    0.2126 * r + 0.7152 * g + 0.0722 * b
+}
+
+fn linear_rgb(pixel: u32) -> [f32; 3] {
+    [16, 8, 0].map(|shift| {
+        let value = ((pixel >> shift) & 0xFF) as f32 / 255.0;
+        if value <= 0.04045 { value / 12.92 } else { ((value + 0.055) / 1.055).powf(2.4) }
+    })
+}
+
+// Distance in CIELAB, where equal steps look about equally different
+fn delta_e(a: u32, b: u32) -> f32 {
+    let (a, b) = (lab(a), lab(b));
+    ((a[0] - b[0]).powi(2) + (a[1] - b[1]).powi(2) + (a[2] - b[2]).powi(2)).sqrt()
+}
+
+fn lab(pixel: u32) -> [f32; 3] {
+    let [r, g, b] = linear_rgb(pixel);
+    // sRGB to XYZ, relative to the D65 white point
+    let x = (0.4124 * r + 0.3576 * g + 0.1805 * b) / 0.9505;
+    let y = 0.2126 * r + 0.7152 * g + 0.0722 * b;
+    let z = (0.0193 * r + 0.1192 * g + 0.9505 * b) / 1.089;
+    let f = |t: f32| if t > 0.008856 { t.cbrt() } else { 7.787 * t + 16.0 / 116.0 };
+    let (fx, fy, fz) = (f(x), f(y), f(z));
+    [116.0 * fy - 16.0, 500.0 * (fx - fy), 200.0 * (fy - fz)]
+}